@@ -1,9 +1,19 @@
+use crate::command::cache_cmd::CacheCmd;
 use crate::command::config_cmd::ConfigCmd;
+use crate::command::lint_cmd::LintCmd;
+use crate::command::match_cmd::MatchCmd;
+use crate::command::rollback_cmd::RollbackCmd;
 use crate::command::subscription_cmd::SubscriptionCmd;
+use crate::command::watch_cmd::WatchCmd;
 use clap::Subcommand;
 
+pub mod cache_cmd;
 pub mod config_cmd;
+pub mod lint_cmd;
+pub mod match_cmd;
+pub mod rollback_cmd;
 pub mod subscription_cmd;
+pub mod watch_cmd;
 
 #[derive(Debug, Clone, Subcommand)]
 #[allow(clippy::large_enum_variant)]
@@ -16,4 +26,25 @@ pub enum ConflyCommand {
     /// 获取订阅提供商的订阅链接
     #[command(name = "subs")]
     Subscription(SubscriptionCmd),
+
+    /// 管理 convd 中的缓存
+    /// 需要 convd 已启动, 并与本地配置使用相同的 secret
+    #[command(subcommand)]
+    Cache(CacheCmd),
+
+    /// 模拟一次连接会命中哪条规则, 以及最终使用的策略组和节点
+    #[command(name = "match")]
+    Match(MatchCmd),
+
+    /// 检查配置中节点、策略组与规则之间的引用, 存在错误时以非零状态码退出
+    #[command(name = "lint")]
+    Lint(LintCmd),
+
+    /// 按订阅间隔持续更新本地配置, 内容变化时通知客户端重新加载
+    #[command(name = "watch")]
+    Watch(WatchCmd),
+
+    /// 将 confly 写入的本地配置恢复为上一个版本
+    #[command(name = "rollback")]
+    Rollback(RollbackCmd),
 }
@@ -0,0 +1,93 @@
+use crate::config::ConflyConfig;
+use clap::Subcommand;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::provider::cache_admin::CacheOverview;
+use reqwest::Method;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use url::Url;
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CacheCmd {
+    /// 列出 convd 中的缓存条目
+    #[command(name = "list")]
+    List,
+
+    /// 清理 convd 中的缓存, 未指定条件时需要显式使用 --all
+    #[command(name = "purge")]
+    Purge {
+        /// 只清理该订阅链接相关的缓存
+        #[arg(long)]
+        sub_url: Option<Url>,
+
+        /// 只清理该客户端相关的缓存
+        #[arg(long)]
+        client: Option<ProxyClient>,
+
+        /// 清理全部缓存
+        #[arg(long, default_value_t = false)]
+        all: bool,
+    },
+}
+
+/// convd 接口的响应结构, 仅保留需要的字段
+#[derive(Debug, Deserialize)]
+struct ApiReply<T> {
+    status: String,
+    #[serde(default)]
+    messages: Vec<String>,
+    data: Option<T>,
+}
+
+impl CacheCmd {
+    pub async fn execute(self, config: &ConflyConfig) -> Result<String> {
+        let mut url = config.common.server.join("/api/cache")?;
+        match self {
+            CacheCmd::List => {
+                let overview: CacheOverview = request(config, Method::GET, url).await?;
+                let mut output = String::from("订阅商原始配置缓存\n");
+                for entry in &overview.subscriptions {
+                    output.push_str(&format!("{entry}\n"));
+                }
+                output.push_str("转换后的配置缓存\n");
+                for entry in &overview.profiles {
+                    output.push_str(&format!("{entry}\n"));
+                }
+                Ok(output)
+            }
+            CacheCmd::Purge { sub_url, client, all } => {
+                {
+                    let mut pairs = url.query_pairs_mut();
+                    if let Some(sub_url) = &sub_url {
+                        pairs.append_pair("sub_url", sub_url.as_str());
+                    }
+                    if let Some(client) = client {
+                        pairs.append_pair("client", client.as_str());
+                    }
+                    if all {
+                        pairs.append_pair("all", "true");
+                    }
+                }
+                let removed: usize = request(config, Method::DELETE, url).await?;
+                Ok(format!("已清理缓存, 删除 Redis 缓存 {removed} 条"))
+            }
+        }
+    }
+}
+
+/// 以管理密钥请求 convd 接口, 返回响应中的数据
+pub(crate) async fn request<T: DeserializeOwned>(config: &ConflyConfig, method: Method, url: Url) -> Result<T> {
+    let reply = reqwest::Client::new()
+        .request(method, url)
+        .bearer_auth(&config.common.secret)
+        .send()
+        .await?
+        .json::<ApiReply<T>>()
+        .await?;
+    match reply.data {
+        Some(data) if reply.status == "ok" => Ok(data),
+        _ => Err(eyre!("convd 返回错误 [{}]: {}", reply.status, reply.messages.join("; "))),
+    }
+}
@@ -0,0 +1,65 @@
+use crate::command::subscription_cmd::create_url_builder;
+use crate::config::ConflyConfig;
+use clap::Args;
+use color_eyre::Result;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::lint::LintReport;
+use convertor::core::profile::Profile;
+use convertor::core::profile::clash_profile::ClashProfile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::provider::SubsProvider;
+use std::path::PathBuf;
+use url::Url;
+
+#[derive(Debug, Clone, Args)]
+pub struct LintCmd {
+    /// 配置所属的客户端
+    #[arg(value_enum)]
+    pub client: ProxyClient,
+
+    /// 要检查的配置文件, 未指定时获取订阅并检查转换后的配置
+    #[arg()]
+    pub file: Option<PathBuf>,
+
+    /// 原始订阅链接(raw_url), 默认使用配置中的订阅
+    #[arg(long, conflicts_with = "file")]
+    pub url: Option<Url>,
+
+    /// 以 JSON 格式输出
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+impl LintCmd {
+    pub fn check_file(&self, file: PathBuf) -> Result<LintReport> {
+        let content = std::fs::read_to_string(file)?;
+        Ok(LintReport::check_content(self.client, content))
+    }
+
+    pub async fn check_subscription(&self, config: &ConflyConfig, subs_provider: &SubsProvider) -> Result<LintReport> {
+        let url_builder = create_url_builder(config, self.client, self.url.clone())?;
+        let raw_response = subs_provider
+            .get_raw_profile(url_builder.build_raw_url().into(), [("User-Agent", "Surge Mac/8310")].into(), None)
+            .await?;
+        let report = match self.client {
+            ProxyClient::Surge => {
+                let mut profile = SurgeProfile::parse(raw_response.content)?;
+                profile.convert(&url_builder)?;
+                LintReport::check(&profile)
+            }
+            ProxyClient::Clash => {
+                let mut profile = ClashProfile::parse(raw_response.content)?;
+                profile.convert(&url_builder)?;
+                LintReport::check(&profile)
+            }
+        };
+        Ok(report)
+    }
+
+    pub fn render(&self, report: &LintReport) -> Result<String> {
+        match self.json {
+            true => Ok(format!("{}\n", serde_json::to_string_pretty(report)?)),
+            false => Ok(report.to_string()),
+        }
+    }
+}
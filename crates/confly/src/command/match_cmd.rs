@@ -0,0 +1,84 @@
+use crate::command::subscription_cmd::create_url_builder;
+use crate::config::ConflyConfig;
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::geoip::GeoIp;
+use convertor::core::profile::Profile;
+use convertor::core::profile::clash_profile::ClashProfile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::core::rule_engine::{MatchQuery, MatchResult, RuleEngine};
+use convertor::provider::SubsProvider;
+use std::net::IpAddr;
+use url::Url;
+
+#[derive(Debug, Clone, Args)]
+pub struct MatchCmd {
+    /// 使用哪个客户端的转换结果进行匹配
+    #[arg(value_enum)]
+    pub client: ProxyClient,
+
+    /// 要匹配的域名或 IP
+    #[arg()]
+    pub target: Option<String>,
+
+    /// 目标端口
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /// 发起连接的进程名
+    #[arg(long)]
+    pub process: Option<String>,
+
+    /// 请求的 User-Agent
+    #[arg(long)]
+    pub user_agent: Option<String>,
+
+    /// 原始订阅链接(raw_url), 默认使用配置中的订阅
+    #[arg(long)]
+    pub url: Option<Url>,
+}
+
+impl MatchCmd {
+    pub fn query(&self) -> MatchQuery {
+        let (domain, ip) = match self.target.as_deref().map(|t| (t, t.parse::<IpAddr>())) {
+            Some((_, Ok(ip))) => (None, Some(ip)),
+            Some((target, Err(_))) => (Some(target.to_string()), None),
+            None => (None, None),
+        };
+        MatchQuery {
+            domain,
+            ip,
+            port: self.port,
+            process: self.process.clone(),
+            user_agent: self.user_agent.clone(),
+        }
+    }
+
+    /// 在本地获取并转换订阅, 然后模拟匹配
+    pub async fn execute(self, config: &ConflyConfig, subs_provider: &SubsProvider) -> Result<MatchResult> {
+        let query = self.query();
+        if query.is_empty() {
+            return Err(eyre!("需要提供域名/IP, 或 --port, --process, --user-agent 中的一项"));
+        }
+        let url_builder = create_url_builder(config, self.client, self.url)?;
+        let raw_response = subs_provider
+            .get_raw_profile(url_builder.build_raw_url().into(), [("User-Agent", "Surge Mac/8310")].into(), None)
+            .await?;
+        let geoip = config.common.geoip.as_ref().map(GeoIp::open).transpose()?;
+        let result = match self.client {
+            ProxyClient::Surge => {
+                let mut profile = SurgeProfile::parse(raw_response.content)?;
+                profile.convert(&url_builder)?;
+                RuleEngine::new(&profile).with_geoip(geoip.as_ref()).evaluate(&query)
+            }
+            ProxyClient::Clash => {
+                let mut profile = ClashProfile::parse(raw_response.content)?;
+                profile.convert(&url_builder)?;
+                RuleEngine::new(&profile).with_geoip(geoip.as_ref()).evaluate(&query)
+            }
+        };
+        Ok(result)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::config::ConflyConfig;
+use crate::file_provider::{FileProvider, new_generation};
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use std::fmt::Write;
+use tracing::warn;
+
+#[derive(Debug, Clone, Args)]
+pub struct RollbackCmd {
+    /// 需要回滚的客户端配置名称
+    #[arg()]
+    pub name: String,
+
+    /// 只列出已有的备份, 不做恢复
+    #[arg(long, default_value_t = false)]
+    pub list: bool,
+}
+
+impl RollbackCmd {
+    /// 将最近一次同步写入的文件恢复为同步前的版本, 恢复后通知客户端重新加载
+    pub async fn execute(&self, config: &ConflyConfig, file_provider: &FileProvider) -> Result<String> {
+        let client_config = config.client(&self.name)?;
+
+        let mut output = String::new();
+        if self.list {
+            for path in client_config.managed_paths() {
+                writeln!(output, "{}", path.display())?;
+                for backup in file_provider.backups(&path)? {
+                    writeln!(output, "  {}", backup.display())?;
+                }
+            }
+            return Ok(output);
+        }
+
+        let paths = client_config.managed_paths();
+        let Some(generation) = file_provider.latest_generation(&paths)? else {
+            return Err(eyre!("{} 的配置没有可用的备份", self.name));
+        };
+        // 只恢复最近一次同步写入的文件, 恢复前的内容备份为新的批次
+        let backup_generation = new_generation();
+        for path in &paths {
+            if let Some(backup) = file_provider.restore(path, &generation, &backup_generation, client_config.backups())? {
+                writeln!(output, "已恢复 {} <- {}", path.display(), backup.display())?;
+            }
+        }
+        if let Some(reload) = client_config.reload()
+            && let Err(e) = reload.trigger(&client_config.main_profile_path()).await
+        {
+            warn!("通知 {} 重新加载配置失败: {e}", self.name);
+        }
+        Ok(output)
+    }
+}
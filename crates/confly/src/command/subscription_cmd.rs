@@ -1,10 +1,13 @@
-use crate::config::ConflyConfig;
+use crate::command::cache_cmd::request;
+use crate::config::{ClientConfig, ConflyConfig};
 use crate::file_provider::FileProvider;
-use clap::Args;
+use clap::{Args, Subcommand, ValueEnum};
 use color_eyre::Result;
-use color_eyre::eyre::OptionExt;
+use color_eyre::eyre::{OptionExt, eyre};
+use console::{Alignment, measure_text_width, pad_str};
 use convertor::common::encrypt::encrypt;
 use convertor::config::proxy_client::ProxyClient;
+use convertor::core::diff::ProfileDiff;
 use convertor::core::profile::Profile;
 use convertor::core::profile::clash_profile::ClashProfile;
 use convertor::core::profile::extract_policies_for_rule_provider;
@@ -12,15 +15,23 @@ use convertor::core::profile::policy::Policy;
 use convertor::core::profile::surge_profile::SurgeProfile;
 use convertor::error::UrlBuilderError;
 use convertor::provider::SubsProvider;
+use convertor::url::qr_code::QrCodeRenderer;
 use convertor::url::url_builder::{HostPort, UrlBuilder};
 use convertor::url::url_result::UrlResult;
+use reqwest::Method;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use url::Url;
 
 #[derive(Default, Debug, Clone, Hash, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct SubscriptionCmd {
-    /// 构造适用于不同客户端的订阅地址
-    #[arg(value_enum)]
-    pub client: ProxyClient,
+    #[command(subcommand)]
+    pub action: Option<SubscriptionAction>,
+
+    /// 客户端配置的名称, 例如 surge 或 work, 没有对应配置时按客户端类型构造订阅地址, 使用子命令时不需要
+    #[arg(required_unless_present = "all")]
+    pub name: Option<String>,
 
     /// 原始订阅链接(raw_url)
     #[arg()]
@@ -29,6 +40,50 @@ pub struct SubscriptionCmd {
     /// 是否更新本地订阅文件
     #[arg(short, long, default_value_t = false)]
     pub update: bool,
+
+    /// 依次更新配置中的全部客户端
+    #[arg(long, default_value_t = false, conflicts_with_all = ["name", "url"])]
+    pub all: bool,
+
+    /// 订阅地址的输出格式
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// 以终端二维码输出配置链接与规则集链接, 等同于 --format qr
+    #[arg(long, default_value_t = false, conflicts_with = "format")]
+    pub qr: bool,
+
+    /// 二维码颜色反转, 适用于部分深色背景的终端
+    #[arg(long, default_value_t = false)]
+    pub invert: bool,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, ValueEnum)]
+pub enum OutputFormat {
+    /// 描述与链接各占一行
+    #[default]
+    Text,
+    /// 每行一个链接, 左侧为描述
+    Table,
+    /// UrlResult 的 JSON
+    Json,
+    /// 配置链接与规则集链接的终端二维码
+    Qr,
+}
+
+#[derive(Debug, Clone, Hash, Subcommand)]
+pub enum SubscriptionAction {
+    /// 查看 convd 记录的订阅最近一次变化
+    /// 需要 convd 已启动, 并与本地配置使用相同的 secret
+    #[command(name = "diff")]
+    Diff {
+        /// 客户端配置的名称, 与 `confly subs <name>` 相同, 使用该配置自己的 sub_url
+        name: String,
+
+        /// 原始订阅链接(raw_url)
+        #[arg()]
+        url: Option<Url>,
+    },
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -44,25 +99,25 @@ impl SubscriptionCmd {
         subs_provider: &SubsProvider,
         file_provider: &FileProvider,
     ) -> Result<(UrlBuilder, UrlResult)> {
-        let url_builder = self.create_url_builder(config).await?;
+        let name = self.name.as_deref().ok_or_eyre("未指定客户端")?;
+        let (client, client_config) = resolve_client(config, name)?;
+        let url = self.url.clone().or_else(|| client_config.and_then(|c| c.sub_url().cloned()));
+        let url_builder = create_url_builder(config, client, url)?;
         let raw_url = url_builder.build_raw_url();
-        let raw_profile_content = subs_provider
-            .get_raw_profile(raw_url.into(), [("User-Agent", "Surge Mac/8310")].into())
+        let raw_response = subs_provider
+            .get_raw_profile(raw_url.into(), [("User-Agent", "Surge Mac/8310")].into(), None)
             .await?;
-        let sub_host = url_builder
-            .sub_url
-            .host_port()
-            .ok_or_eyre("无法从 sub_url 中提取 host port")?;
-        let (client_profile, policies) = match self.client {
+        let sub_host = url_builder.sub_url.host_port().ok_or_eyre("无法从 sub_url 中提取 host port")?;
+        let (client_profile, policies) = match client {
             ProxyClient::Surge => {
-                let mut raw_profile = SurgeProfile::parse(raw_profile_content)?;
+                let mut raw_profile = SurgeProfile::parse(raw_response.content)?;
                 raw_profile.convert(&url_builder)?;
                 let mut policies: Vec<Policy> = raw_profile.policy_of_rules.keys().cloned().collect();
                 policies.sort();
                 (ClientProfile::Surge, policies)
             }
             ProxyClient::Clash => {
-                let raw_profile = ClashProfile::parse(raw_profile_content)?;
+                let raw_profile = ClashProfile::parse(raw_response.content)?;
                 let policies = extract_policies_for_rule_provider(&raw_profile.rules, sub_host);
                 (ClientProfile::Clash(raw_profile), policies)
             }
@@ -80,11 +135,12 @@ impl SubscriptionCmd {
             raw_profile_url,
             profile_url,
             rule_providers_url: rule_provider_urls,
+            userinfo: raw_response.userinfo,
         };
 
         // 副作用逻辑后置，主流程只负责数据流
         if self.update {
-            match (client_profile, config.clients.get(&self.client)) {
+            match (client_profile, client_config) {
                 (ClientProfile::Surge, Some(client_config)) => {
                     client_config.update_surge_config(file_provider, &url_builder, &policies)?;
                 }
@@ -97,31 +153,151 @@ impl SubscriptionCmd {
         Ok((url_builder, result))
     }
 
-    async fn create_url_builder(&self, config: &ConflyConfig) -> Result<UrlBuilder> {
-        let SubscriptionCmd { client, url, update: _ } = &self;
-        let subscription_config = &config.common.subscription;
-
-        let sub_url = url.clone().unwrap_or(subscription_config.sub_url.clone());
-        let server = config.common.server.clone();
-        let secret = config.common.secret.clone();
-        let enc_secret = encrypt(secret.as_bytes(), &secret)?;
-        let enc_sub_url = encrypt(secret.as_bytes(), sub_url.as_str())?;
-        let interval = subscription_config.interval;
-        let strict = subscription_config.strict;
-
-        let url_builder = UrlBuilder::new(
-            secret,
-            Some(enc_secret),
-            *client,
-            server,
-            sub_url,
-            Some(enc_sub_url),
-            interval,
-            strict,
-        )?;
-
-        Ok(url_builder)
+    /// 按名称顺序更新全部客户端配置, 返回每个配置的名称与订阅地址
+    pub async fn execute_all(
+        &self,
+        config: &ConflyConfig,
+        subs_provider: &SubsProvider,
+        file_provider: &FileProvider,
+    ) -> Vec<(String, Result<UrlResult>)> {
+        let mut results = vec![];
+        // 单个配置失败不影响其余配置的更新
+        for (name, _) in config.sorted_clients() {
+            let cmd = SubscriptionCmd {
+                name: Some(name.clone()),
+                update: true,
+                all: false,
+                ..self.clone()
+            };
+            let result = cmd.execute(config, subs_provider, file_provider).await;
+            results.push((name.clone(), result.map(|(_url_builder, result)| result)));
+        }
+        results
+    }
+
+    pub fn output_format(&self) -> OutputFormat {
+        match self.qr {
+            true => OutputFormat::Qr,
+            false => self.format,
+        }
+    }
+
+    pub fn render(&self, url_result: &UrlResult) -> Result<String> {
+        match self.output_format() {
+            OutputFormat::Text => Ok(url_result.to_string()),
+            OutputFormat::Table => Ok(render_table(url_result)),
+            OutputFormat::Json => Ok(format!("{}\n", serde_json::to_string_pretty(url_result)?)),
+            OutputFormat::Qr => render_qr(url_result, self.invert),
+        }
     }
+
+    /// `--all` 的输出, JSON 格式时以配置名称为键合并为一个对象, 失败的配置输出 `{"error": ...}`
+    pub fn render_all(&self, results: &[(String, Result<UrlResult>)]) -> Result<String> {
+        if self.output_format() == OutputFormat::Json {
+            let results = results
+                .iter()
+                .map(|(name, result)| {
+                    let value = match result {
+                        Ok(result) => serde_json::to_value(result)?,
+                        Err(e) => serde_json::json!({ "error": error_chain(e) }),
+                    };
+                    Ok((name, value))
+                })
+                .collect::<Result<BTreeMap<_, _>>>()?;
+            return Ok(format!("{}\n", serde_json::to_string_pretty(&results)?));
+        }
+        let mut output = String::new();
+        for (name, result) in results {
+            writeln!(output, "[{name}]")?;
+            match result {
+                Ok(result) => output.push_str(&self.render(result)?),
+                Err(e) => writeln!(output, "更新失败: {}", error_chain(e))?,
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn error_chain(error: &color_eyre::Report) -> String {
+    error.chain().map(ToString::to_string).collect::<Vec<_>>().join(": ")
+}
+
+fn render_table(url_result: &UrlResult) -> String {
+    let mut rows = vec![&url_result.raw_url, &url_result.profile_url, &url_result.raw_profile_url]
+        .into_iter()
+        .chain(&url_result.rule_providers_url)
+        .map(|url| (url.desc.clone(), url.to_string()))
+        .collect::<Vec<_>>();
+    if let Some(userinfo) = &url_result.userinfo {
+        rows.push(("订阅流量信息".to_string(), userinfo.summary()));
+    }
+    let width = rows.iter().map(|(desc, _)| measure_text_width(desc)).max().unwrap_or(0);
+    rows.iter()
+        .map(|(desc, value)| format!("{}  {value}\n", pad_str(desc, width, Alignment::Left, None)))
+        .collect()
+}
+
+/// 手机客户端只需要导入配置链接与规则集链接, 原始订阅链接不生成二维码
+fn render_qr(url_result: &UrlResult, invert: bool) -> Result<String> {
+    let mut output = String::new();
+    for url in std::iter::once(&url_result.profile_url).chain(&url_result.rule_providers_url) {
+        let url_str = url.to_string();
+        writeln!(output, "{}", url.desc)?;
+        writeln!(output, "{url_str}")?;
+        writeln!(output, "{}", QrCodeRenderer::new(&url_str)?.render_terminal(invert))?;
+    }
+    Ok(output)
+}
+
+impl SubscriptionAction {
+    pub async fn execute(self, config: &ConflyConfig) -> Result<String> {
+        match self {
+            SubscriptionAction::Diff { name, url } => {
+                let (client, client_config) = resolve_client(config, &name)?;
+                let url = url.or_else(|| client_config.and_then(|c| c.sub_url().cloned()));
+                let url_builder = create_url_builder(config, client, url)?;
+                let diff: ProfileDiff = request(config, Method::GET, url_builder.build_diff_url()?).await?;
+                Ok(diff.to_string())
+            }
+        }
+    }
+}
+
+/// 按名称查找客户端配置, 没有对应配置时把名称当作客户端类型
+fn resolve_client<'a>(config: &'a ConflyConfig, name: &str) -> Result<(ProxyClient, Option<&'a ClientConfig>)> {
+    match config.clients.get(name) {
+        Some(client_config) => Ok((client_config.client(), Some(client_config))),
+        None => {
+            let client = name.parse::<ProxyClient>().map_err(|_| eyre!("没有找到 [{name}] 客户端配置"))?;
+            Ok((client, None))
+        }
+    }
+}
+
+pub(crate) fn create_url_builder(config: &ConflyConfig, client: ProxyClient, url: Option<Url>) -> Result<UrlBuilder> {
+    let subscription_config = &config.common.subscription;
+
+    let sub_url = url.unwrap_or(subscription_config.sub_url.clone());
+    let server = config.common.server.clone();
+    let secret = config.common.secret.clone();
+    let enc_secret = encrypt(secret.as_bytes(), &secret)?;
+    let enc_sub_url = encrypt(secret.as_bytes(), sub_url.as_str())?;
+    let interval = subscription_config.interval;
+    let strict = subscription_config.strict;
+
+    let url_builder = UrlBuilder::new(
+        secret,
+        Some(enc_secret),
+        client,
+        server,
+        sub_url,
+        Some(enc_sub_url),
+        interval,
+        strict,
+    )?
+    .with_token(config.token.clone());
+
+    Ok(url_builder)
 }
 
 // impl SubscriptionCmd {
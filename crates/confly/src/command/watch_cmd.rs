@@ -0,0 +1,172 @@
+use crate::command::subscription_cmd::create_url_builder;
+use crate::config::{ClientConfig, ConflyConfig, ReloadConfig};
+use crate::file_provider::{FileChange, FileProvider};
+use clap::Args;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::diff::{ProfileDiff, ProfileSnapshot};
+use convertor::core::profile::Profile;
+use convertor::core::profile::clash_profile::ClashProfile;
+use convertor::core::profile::policy::Policy;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::provider::SubsProvider;
+use convertor::url::url_builder::UrlBuilder;
+use std::path::Path;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use url::Url;
+
+#[derive(Debug, Clone, Args)]
+pub struct WatchCmd {
+    /// 需要保持同步的客户端配置名称
+    #[arg()]
+    pub name: String,
+
+    /// 原始订阅链接(raw_url), 默认使用该客户端配置或全局配置中的订阅
+    #[arg()]
+    pub url: Option<Url>,
+
+    /// 两次同步之间的间隔, 单位秒, 默认使用订阅配置中的 interval
+    #[arg(short, long)]
+    pub interval: Option<u64>,
+
+    /// 只同步一次, 失败时以非零状态码退出
+    #[arg(long, default_value_t = false)]
+    pub once: bool,
+}
+
+/// 一次同步的结果
+#[derive(Debug, Clone)]
+pub struct SyncResult {
+    /// 本次获取的订阅快照, 用于与下一次比较
+    pub snapshot: ProfileSnapshot,
+    /// 内容发生变化而被重写的本地文件
+    pub changes: Vec<FileChange>,
+}
+
+impl WatchCmd {
+    /// 按间隔重新获取订阅并更新本地文件, 直到收到退出信号
+    pub async fn run(&self, config: &ConflyConfig, subs_provider: &SubsProvider, file_provider: &FileProvider) -> Result<()> {
+        let client_config = config.client(&self.name)?;
+        let interval = Duration::from_secs(self.interval.unwrap_or(config.common.subscription.interval).max(1));
+        // 加密后的订阅链接每次都不同, 整个同步过程复用同一个 UrlBuilder, 避免每轮都改写文件
+        let url_builder = self.url_builder(config)?;
+        info!("开始同步 {} 的本地配置, 间隔 {}s", self.name, interval.as_secs());
+
+        let mut previous: Option<ProfileSnapshot> = None;
+        loop {
+            match self
+                .sync(config, client_config, &url_builder, subs_provider, file_provider)
+                .await
+            {
+                Ok(result) => {
+                    report(previous.as_ref(), &result);
+                    if !result.changes.is_empty()
+                        && let Some(reload) = client_config.reload()
+                    {
+                        match reload.trigger(&client_config.main_profile_path()).await {
+                            Ok(()) => info!("已通知 {} 重新加载配置", self.name),
+                            Err(e) => warn!("通知 {} 重新加载配置失败: {e}", self.name),
+                        }
+                    }
+                    previous = Some(result.snapshot);
+                }
+                Err(e) if self.once => return Err(e),
+                Err(e) => error!("同步订阅失败, 将在下个周期重试: {e}"),
+            }
+            if self.once {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    info!("收到退出信号, 停止同步");
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn url_builder(&self, config: &ConflyConfig) -> Result<UrlBuilder> {
+        let client_config = config.client(&self.name)?;
+        let url = self.url.clone().or_else(|| client_config.sub_url().cloned());
+        create_url_builder(config, client_config.client(), url)
+    }
+
+    /// 跳过缓存获取订阅, 仅重写内容有变化的本地文件
+    pub async fn sync(
+        &self,
+        config: &ConflyConfig,
+        client_config: &ClientConfig,
+        url_builder: &UrlBuilder,
+        subs_provider: &SubsProvider,
+        file_provider: &FileProvider,
+    ) -> Result<SyncResult> {
+        let raw_response = subs_provider
+            .refresh_raw_profile(
+                url_builder.build_raw_url().into(),
+                [("User-Agent", "Surge Mac/8310")].into(),
+                None,
+            )
+            .await?;
+        let (snapshot, changes) = match client_config.client() {
+            ProxyClient::Surge => {
+                let mut profile = SurgeProfile::parse(raw_response.content)?;
+                let snapshot = ProfileSnapshot::new(&profile, &raw_response.content_hash);
+                profile.convert(url_builder)?;
+                let mut policies: Vec<Policy> = profile.policy_of_rules.keys().cloned().collect();
+                policies.sort();
+                let changes = client_config.update_surge_config(file_provider, url_builder, &policies)?;
+                (snapshot, changes)
+            }
+            ProxyClient::Clash => {
+                let profile = ClashProfile::parse(raw_response.content)?;
+                let snapshot = ProfileSnapshot::new(&profile, &raw_response.content_hash);
+                let changes =
+                    client_config.update_clash_config(file_provider, url_builder, profile, &config.common.secret)?;
+                (snapshot, changes)
+            }
+        };
+        Ok(SyncResult { snapshot, changes })
+    }
+}
+
+fn report(previous: Option<&ProfileSnapshot>, result: &SyncResult) {
+    if let Some(previous) = previous {
+        let diff = ProfileDiff::between(previous, &result.snapshot);
+        if !diff.is_empty() {
+            info!("订阅发生变化:\n{diff}");
+        }
+    }
+    if result.changes.is_empty() {
+        info!("本地配置没有变化");
+    }
+    for change in &result.changes {
+        info!("已更新 {change}");
+    }
+}
+
+impl ReloadConfig {
+    /// 依次调用 external-controller 与自定义命令, 任一失败都返回错误
+    pub async fn trigger(&self, main_profile: &Path) -> Result<()> {
+        if let Some(controller) = &self.controller {
+            let url = controller.join("configs?force=true")?;
+            let mut request = reqwest::Client::new()
+                .put(url)
+                .json(&serde_json::json!({ "path": main_profile }));
+            if let Some(secret) = &self.controller_secret {
+                request = request.bearer_auth(secret);
+            }
+            request.send().await?.error_for_status()?;
+        }
+        if let Some((program, args)) = self.command.split_first() {
+            let status = tokio::process::Command::new(program).args(args).status().await?;
+            if !status.success() {
+                return Err(eyre!("重新加载命令执行失败: {status}"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
+use url::Url;
 
 use convertor::config::proxy_client::ProxyClient;
 
@@ -14,13 +15,19 @@ pub struct ConflyConfig {
     #[serde(flatten)]
     pub common: Config,
 
+    /// 用户令牌, convd 开启鉴权后生成的链接必须携带
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// 以名称区分的客户端配置, 同一种客户端可以有多份, 例如 `[work]` 与 `[personal]`
     #[serde(flatten)]
-    pub clients: HashMap<ProxyClient, ClientConfig>,
+    pub clients: HashMap<String, ClientConfig>,
 }
 
 impl ConflyConfig {
     pub fn search(cwd: impl AsRef<Path>, config_path: Option<impl AsRef<Path>>) -> Result<Self> {
-        let config: ConflyConfig = Config::search(&cwd, config_path)?;
+        let mut config: ConflyConfig = Config::search(&cwd, config_path)?;
+        config.resolve_clients()?;
         Ok(config)
     }
 
@@ -30,18 +37,50 @@ impl ConflyConfig {
             return Err(eyre!("配置文件不是一个合法的文件: {}", path.display()));
         }
         let content = std::fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&content)?;
+        let mut config: Self = toml::from_str(&content)?;
+        config.resolve_clients()?;
         Ok(config)
     }
+
+    /// 没有指定 client 的配置以名称推断客户端类型, 兼容 `[surge]` 与 `[clash]` 的写法
+    fn resolve_clients(&mut self) -> Result<()> {
+        for (name, client_config) in self.clients.iter_mut() {
+            if client_config.client.is_none() {
+                let client = name
+                    .parse::<ProxyClient>()
+                    .map_err(|_| eyre!("无法确定 [{name}] 的客户端类型, 请指定 client"))?;
+                client_config.client = Some(client);
+            }
+        }
+        Ok(())
+    }
+
+    /// 按名称查找客户端配置
+    pub fn client(&self, name: &str) -> Result<&ClientConfig> {
+        self.clients
+            .get(name)
+            .ok_or_else(|| eyre!("没有找到 [{name}] 客户端配置"))
+    }
+
+    /// 全部客户端配置, 按名称排序
+    pub fn sorted_clients(&self) -> Vec<(&String, &ClientConfig)> {
+        let mut clients = self.clients.iter().collect::<Vec<_>>();
+        clients.sort_by_key(|(name, _)| *name);
+        clients
+    }
 }
 
 impl ConflyConfig {
     pub fn template() -> Self {
         let common = Config::template();
         let mut clients = HashMap::new();
-        clients.insert(ProxyClient::Surge, ClientConfig::surge_template());
-        clients.insert(ProxyClient::Clash, ClientConfig::clash_template());
-        Self { common, clients }
+        clients.insert(ProxyClient::Surge.to_string(), ClientConfig::surge_template());
+        clients.insert(ProxyClient::Clash.to_string(), ClientConfig::clash_template());
+        Self {
+            common,
+            token: None,
+            clients,
+        }
     }
 }
 
@@ -54,16 +93,49 @@ impl Display for ConflyConfig {
 #[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
 #[derive(Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// 客户端类型, 缺省时由配置的名称推断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<ProxyClient>,
+    /// 该配置使用的订阅链接, 缺省时使用 `[subscription]` 中的 sub_url
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sub_url: Option<Url>,
+    /// 本地主配置为空时作为初始内容的模板文件, 相对路径基于 config_dir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    template: Option<PathBuf>,
     config_dir: PathBuf,
     main_profile: String,
     raw: Option<String>,
     raw_profile: Option<String>,
     rules: Option<String>,
+    /// 本地文件更新后通知客户端重新加载, 仅 `confly watch` 使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reload: Option<ReloadConfig>,
+    /// 每个被管理的文件保留的备份数, 默认为 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backups: Option<usize>,
+}
+
+const DEFAULT_BACKUPS: usize = 5;
+
+/// 通知客户端重新加载配置的方式, 可以同时配置
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct ReloadConfig {
+    /// mihomo 的 external-controller 地址, 通过 `PUT /configs` 重新加载主配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controller: Option<Url>,
+    /// external-controller 的 secret
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub controller_secret: Option<String>,
+    /// 自定义命令, 第一项为程序, 其余为参数
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
 }
 
 impl ClientConfig {
     pub fn surge_template() -> Self {
         Self {
+            client: Some(ProxyClient::Surge),
             config_dir: PathBuf::from("/path/to/surge"),
             main_profile: "surge.conf".to_string(),
             ..Default::default()
@@ -72,6 +144,7 @@ impl ClientConfig {
 
     pub fn clash_template() -> Self {
         Self {
+            client: Some(ProxyClient::Clash),
             config_dir: PathBuf::from("/path/to/mihomo"),
             main_profile: "config.yaml".to_string(),
             ..Default::default()
@@ -80,6 +153,26 @@ impl ClientConfig {
 }
 
 impl ClientConfig {
+    pub fn client(&self) -> ProxyClient {
+        self.client.unwrap_or_default()
+    }
+
+    pub fn sub_url(&self) -> Option<&Url> {
+        self.sub_url.as_ref()
+    }
+
+    pub fn set_sub_url(&mut self, sub_url: Option<Url>) {
+        self.sub_url = sub_url;
+    }
+
+    pub fn template_path(&self) -> Option<PathBuf> {
+        self.template.as_ref().map(|path| self.config_dir().join(path))
+    }
+
+    pub fn set_template(&mut self, template: Option<PathBuf>) {
+        self.template = template;
+    }
+
     pub fn set_config_dir(&mut self, config_dir: impl AsRef<Path>) {
         self.config_dir = config_dir.as_ref().to_path_buf();
     }
@@ -103,4 +196,29 @@ impl ClientConfig {
     pub fn rules_path(&self) -> Option<PathBuf> {
         self.rules.as_ref().map(|name| self.config_dir().join(name))
     }
+
+    pub fn reload(&self) -> Option<&ReloadConfig> {
+        self.reload.as_ref()
+    }
+
+    pub fn set_reload(&mut self, reload: Option<ReloadConfig>) {
+        self.reload = reload;
+    }
+
+    pub fn backups(&self) -> usize {
+        self.backups.unwrap_or(DEFAULT_BACKUPS)
+    }
+
+    pub fn set_backups(&mut self, backups: Option<usize>) {
+        self.backups = backups;
+    }
+
+    /// 由 confly 写入的全部文件
+    pub fn managed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.main_profile_path()];
+        paths.extend(self.raw_path());
+        paths.extend(self.raw_profile_path());
+        paths.extend(self.rules_path());
+        paths
+    }
 }
@@ -1,9 +1,23 @@
+use chrono::Utc;
 use color_eyre::Result;
 use color_eyre::eyre::{OptionExt, eyre};
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
+/// 备份文件存放在被管理文件所在目录下的该目录中
+pub const BACKUP_DIR: &str = ".confly-backup";
+
+/// 一次写入对文件造成的变化, 按行统计
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+}
+
 #[derive(Clone)]
 pub enum FileProvider {
     FileSystem,
@@ -24,9 +38,10 @@ impl FileProvider {
         Ok(content)
     }
 
+    /// 写入文件系统时先写入同目录下的临时文件再重命名, 中途失败不会破坏原文件
     pub fn write(&self, path: impl AsRef<Path>, content: impl Into<String>) -> Result<()> {
         match self {
-            FileProvider::FileSystem => std::fs::write(path, content.into())?,
+            FileProvider::FileSystem => write_atomic(path.as_ref(), content.into())?,
             FileProvider::Memory(mem) => {
                 mem.write()
                     .map_err(|e| eyre!("{e}"))?
@@ -36,3 +51,215 @@ impl FileProvider {
         Ok(())
     }
 }
+
+impl FileProvider {
+    /// 仅在内容变化时写入, 写入前将原文件备份到 `generation` 批次, 文件不存在时视为空文件
+    pub fn write_if_changed(
+        &self,
+        path: impl AsRef<Path>,
+        content: impl Into<String>,
+        generation: &str,
+        keep_backups: usize,
+    ) -> Result<Option<FileChange>> {
+        let path = path.as_ref();
+        let content = content.into();
+        let previous = match self {
+            FileProvider::FileSystem if !path.exists() => String::new(),
+            _ => self.read(path)?,
+        };
+        if previous == content {
+            return Ok(None);
+        }
+        let (added, removed) = count_line_changes(&previous, &content);
+        self.backup(path, generation, keep_backups)?;
+        self.write(path, content)?;
+        Ok(Some(FileChange {
+            path: path.to_path_buf(),
+            added,
+            removed,
+        }))
+    }
+}
+
+/// 新的备份批次, 一次同步中写入的所有文件共用同一个批次, 回滚时整批恢复
+pub fn new_generation() -> String {
+    Utc::now().format("%Y%m%dT%H%M%S%.6f").to_string()
+}
+
+impl FileProvider {
+    /// 将文件当前的内容保存为 `generation` 批次的备份, 只保留最近的 `keep` 份, 文件不存在时不备份
+    ///
+    /// 同一批次中文件已有备份时保留最早的一份, 即这一批次写入之前的内容
+    pub fn backup(&self, path: impl AsRef<Path>, generation: &str, keep: usize) -> Result<Option<PathBuf>> {
+        let path = path.as_ref();
+        if keep == 0 || !self.exists(path)? {
+            return Ok(None);
+        }
+        let backup_path = backup_dir(path).join(format!("{}.{generation}", file_name(path)?));
+        if self.exists(&backup_path)? {
+            return Ok(Some(backup_path));
+        }
+        if let FileProvider::FileSystem = self {
+            std::fs::create_dir_all(backup_dir(path))?;
+        }
+        self.write(&backup_path, self.read(path)?)?;
+        for stale in self.backups(path)?.into_iter().skip(keep) {
+            self.remove(stale)?;
+        }
+        Ok(Some(backup_path))
+    }
+
+    /// 文件的全部备份, 最新的在前
+    pub fn backups(&self, path: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let path = path.as_ref();
+        let dir = backup_dir(path);
+        let mut backups = match self {
+            FileProvider::FileSystem if !dir.is_dir() => vec![],
+            FileProvider::FileSystem => std::fs::read_dir(&dir)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?,
+            FileProvider::Memory(mem) => mem
+                .read()
+                .map_err(|e| eyre!("{e}"))?
+                .keys()
+                .filter(|key| key.parent() == Some(dir.as_path()))
+                .cloned()
+                .collect(),
+        };
+        backups.retain(|backup| generation_of(path, backup).is_some());
+        backups.sort_by(|a, b| b.cmp(a));
+        Ok(backups)
+    }
+
+    /// 这些文件的备份中最新的批次
+    pub fn latest_generation(&self, paths: &[PathBuf]) -> Result<Option<String>> {
+        let mut latest = None::<String>;
+        for path in paths {
+            for backup in self.backups(path)? {
+                if let Some(generation) = generation_of(path, &backup)
+                    && latest.as_deref().is_none_or(|latest| generation > latest)
+                {
+                    latest = Some(generation.to_string());
+                }
+            }
+        }
+        Ok(latest)
+    }
+
+    /// 用 `generation` 批次的备份恢复文件, 该备份随之被移除, 文件在这一批次没有备份时不做改动
+    ///
+    /// 恢复前将当前内容备份到 `backup_generation` 批次, 再次回滚即可撤销本次回滚
+    pub fn restore(
+        &self,
+        path: impl AsRef<Path>,
+        generation: &str,
+        backup_generation: &str,
+        keep: usize,
+    ) -> Result<Option<PathBuf>> {
+        let path = path.as_ref();
+        let Some(backup) = self
+            .backups(path)?
+            .into_iter()
+            .find(|backup| generation_of(path, backup) == Some(generation))
+        else {
+            return Ok(None);
+        };
+        let content = self.read(&backup)?;
+        self.backup(path, backup_generation, keep)?;
+        self.write(path, content)?;
+        if self.exists(&backup)? {
+            self.remove(&backup)?;
+        }
+        Ok(Some(backup))
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool> {
+        match self {
+            FileProvider::FileSystem => Ok(path.exists()),
+            FileProvider::Memory(mem) => Ok(mem.read().map_err(|e| eyre!("{e}"))?.contains_key(path)),
+        }
+    }
+
+    fn remove(&self, path: impl AsRef<Path>) -> Result<()> {
+        match self {
+            FileProvider::FileSystem => std::fs::remove_file(path)?,
+            FileProvider::Memory(mem) => {
+                mem.write().map_err(|e| eyre!("{e}"))?.remove(path.as_ref());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_eyre(format!("无法获取文件名: {}", path.display()))
+}
+
+/// 备份文件名为 `<文件名>.<批次>`, 批次以数字开头
+fn generation_of<'a>(path: &Path, backup: &'a Path) -> Option<&'a str> {
+    backup
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(path.file_name()?.to_str()?))
+        .and_then(|rest| rest.strip_prefix('.'))
+        .filter(|generation| generation.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).join(BACKUP_DIR)
+}
+
+/// 被管理的文件可能是指向其它位置的符号链接, 写入链接指向的文件而不是替换链接本身
+fn resolve_symlink(path: &Path) -> PathBuf {
+    match std::fs::canonicalize(path) {
+        Ok(real) => real,
+        // 指向的文件尚不存在
+        Err(_) => match std::fs::read_link(path) {
+            Ok(target) => path.parent().unwrap_or(Path::new("")).join(target),
+            Err(_) => path.to_path_buf(),
+        },
+    }
+}
+
+fn write_atomic(path: &Path, content: String) -> Result<()> {
+    // 临时文件需要与目标文件在同一目录, 保证重命名不会跨文件系统
+    let path = &resolve_symlink(path);
+    let tmp_path = path.with_file_name(format!(".{}.confly-tmp", file_name(path)?));
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        // 沿用原文件的权限
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// 不考虑顺序, 统计新增与删除的行数
+fn count_line_changes(previous: &str, current: &str) -> (usize, usize) {
+    let mut counts = HashMap::<&str, isize>::new();
+    for line in previous.lines() {
+        *counts.entry(line).or_default() -= 1;
+    }
+    for line in current.lines() {
+        *counts.entry(line).or_default() += 1;
+    }
+    let added = counts.values().filter(|c| **c > 0).map(|c| *c as usize).sum();
+    let removed = counts.values().filter(|c| **c < 0).map(|c| c.unsigned_abs()).sum();
+    (added, removed)
+}
+
+impl Display for FileChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (+{} -{})", self.path.display(), self.added, self.removed)
+    }
+}
@@ -1,6 +1,7 @@
 use clap::Parser;
 use color_eyre::Result;
 use confly::command::ConflyCommand;
+use confly::command::subscription_cmd::SubscriptionCmd;
 use confly::config::ConflyConfig;
 use confly::file_provider::FileProvider;
 use convertor::common::clap_style::SONOKAI_TC;
@@ -37,15 +38,76 @@ async fn main() -> Result<()> {
         ConflyCommand::Config(config_cmd) => {
             config_cmd.execute(base_dir, args.config).await?;
         }
+        ConflyCommand::Subscription(SubscriptionCmd {
+            action: Some(action), ..
+        }) => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            println!("{}", action.execute(&config).await?);
+        }
+        ConflyCommand::Subscription(sub_cmd) if sub_cmd.all => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            let subs_provider = subs_provider(&config)?;
+            let results = sub_cmd
+                .execute_all(&config, &subs_provider, &FileProvider::FileSystem)
+                .await;
+            print!("{}", sub_cmd.render_all(&results)?);
+            if results.iter().any(|(_, result)| result.is_err()) {
+                std::process::exit(1);
+            }
+        }
         ConflyCommand::Subscription(sub_cmd) => {
             let config = ConflyConfig::search(&base_dir, args.config)?;
-            let subs_provider = SubsProvider::new(None, config.common.redis.as_ref().map(|r| r.prefix.as_str()));
+            let subs_provider = subs_provider(&config)?;
             let (_url_builder, url_result) = sub_cmd
+                .clone()
                 .execute(&config, &subs_provider, &FileProvider::FileSystem)
                 .await?;
-            println!("{url_result}");
+            print!("{}", sub_cmd.render(&url_result)?);
+        }
+        ConflyCommand::Cache(cache_cmd) => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            println!("{}", cache_cmd.execute(&config).await?);
+        }
+        ConflyCommand::Match(match_cmd) => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            let subs_provider = subs_provider(&config)?;
+            print!("{}", match_cmd.execute(&config, &subs_provider).await?);
+        }
+        ConflyCommand::Lint(lint_cmd) => {
+            let report = match lint_cmd.file.clone() {
+                Some(file) => lint_cmd.check_file(file)?,
+                None => {
+                    let config = ConflyConfig::search(&base_dir, args.config)?;
+                    let subs_provider = subs_provider(&config)?;
+                    lint_cmd.check_subscription(&config, &subs_provider).await?
+                }
+            };
+            print!("{}", lint_cmd.render(&report)?);
+            if report.has_errors() {
+                std::process::exit(1);
+            }
+        }
+        ConflyCommand::Watch(watch_cmd) => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            let subs_provider = subs_provider(&config)?;
+            watch_cmd.run(&config, &subs_provider, &FileProvider::FileSystem).await?;
+        }
+        ConflyCommand::Rollback(rollback_cmd) => {
+            let config = ConflyConfig::search(&base_dir, args.config)?;
+            print!("{}", rollback_cmd.execute(&config, &FileProvider::FileSystem).await?);
         }
     }
 
     Ok(())
 }
+
+fn subs_provider(config: &ConflyConfig) -> Result<SubsProvider> {
+    let store = config.common.cache.build_store(None)?;
+    let subs_provider = SubsProvider::new(
+        store,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+    Ok(subs_provider)
+}
@@ -1,18 +1,24 @@
 use crate::config::ClientConfig;
-use crate::file_provider::FileProvider;
+use crate::file_provider::{FileChange, FileProvider, new_generation};
+use color_eyre::eyre::{WrapErr, eyre};
 use convertor::core::profile::Profile;
 use convertor::core::profile::clash_profile::ClashProfile;
 use convertor::core::profile::policy::Policy;
 use convertor::core::profile::rule::Rule;
 use convertor::core::profile::surge_header::SurgeHeader;
-use convertor::core::renderer::Renderer;
-use convertor::core::renderer::clash_renderer::ClashRenderer;
+use convertor::core::parser::surge_parser::{
+    MANAGED_CONFIG_HEADER, PROXY_GROUP_SECTION, PROXY_SECTION, RULE_SECTION, SurgeParser,
+};
+use convertor::core::renderer::clash_renderer::{CLASH_RULE_COMMENT_END, CLASH_RULE_COMMENT_START, ClashRenderer};
 use convertor::core::renderer::surge_renderer::{
     SURGE_RULE_PROVIDER_COMMENT_END, SURGE_RULE_PROVIDER_COMMENT_START, SurgeRenderer,
 };
+use convertor::core::renderer::{INDENT, Renderer};
 use convertor::url::convertor_url::UrlType;
 use convertor::url::url_builder::UrlBuilder;
 use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
 
 impl ClientConfig {
     pub fn update_surge_config<'a>(
@@ -20,20 +26,28 @@ impl ClientConfig {
         file_provider: &FileProvider,
         url_builder: &UrlBuilder,
         policies: impl IntoIterator<Item = &'a Policy>,
-    ) -> color_eyre::Result<()> {
+    ) -> color_eyre::Result<Vec<FileChange>> {
+        let mut changes = vec![];
+        let generation = new_generation();
         // 更新主订阅配置，即由 convertor 生成的订阅配置
         let main_profile = Self::update_surge_conf(
-            file_provider.read(self.main_profile_path())?,
+            self.read_main_profile(file_provider)?,
             url_builder.build_surge_header(UrlType::Profile)?,
         )?;
-        file_provider.write(self.main_profile_path(), main_profile)?;
+        changes.extend(self.write_managed(
+            file_provider,
+            self.main_profile_path(),
+            main_profile,
+            &generation,
+            validate_surge_conf,
+        )?);
 
         if let Some(path) = self.raw_path() {
             let raw = Self::update_surge_conf(
                 file_provider.read(&path)?,
                 url_builder.build_surge_header(UrlType::Raw)?,
             )?;
-            file_provider.write(path, raw)?;
+            changes.extend(self.write_managed(file_provider, path, raw, &generation, validate_surge_conf)?);
         }
 
         // 更新转发原始订阅配置，即由 convertor 生成的原始订阅配置
@@ -42,27 +56,26 @@ impl ClientConfig {
                 file_provider.read(&path)?,
                 url_builder.build_surge_header(UrlType::RawProfile)?,
             )?;
-            file_provider.write(path, raw_profile)?;
+            changes.extend(self.write_managed(file_provider, path, raw_profile, &generation, validate_surge_conf)?);
         }
 
         // 更新 rules.dconf 中的 RULE-SET 规则，规则提供者将从 policies 中生成 URL
         if let Some(path) = self.rules_path() {
             let rules = Self::update_surge_rule_providers(file_provider.read(&path)?, url_builder, policies)?;
-            file_provider.write(path, rules)?;
+            changes.extend(self.write_managed(file_provider, path, rules, &generation, validate_surge_rules)?);
         }
 
-        Ok(())
+        Ok(changes)
     }
 
-    fn update_surge_conf(mut content: String, header: SurgeHeader) -> color_eyre::Result<String> {
+    /// 替换本地文件的配置头, 文件中已有的其它选项保留, 没有配置头时插入到第一行
+    fn update_surge_conf(content: String, header: SurgeHeader) -> color_eyre::Result<String> {
         let mut lines = content.lines().map(Cow::Borrowed).collect::<Vec<_>>();
-        if lines.is_empty() {
-            Ok(header.to_string())
-        } else {
-            lines[0] = Cow::Owned(header.to_string());
-            content = lines.join("\n");
-            Ok(content)
+        match lines.first().and_then(SurgeHeader::parse) {
+            Some(base) => lines[0] = Cow::Owned(header.inherit(&base).to_string()),
+            None => lines.insert(0, Cow::Owned(header.to_string())),
         }
+        Ok(lines.join("\n"))
     }
 
     fn update_surge_rule_providers<'a>(
@@ -108,13 +121,189 @@ impl ClientConfig {
         url_builder: &UrlBuilder,
         raw_profile: ClashProfile,
         secret: impl AsRef<str>,
-    ) -> color_eyre::Result<()> {
+    ) -> color_eyre::Result<Vec<FileChange>> {
         let mut template = ClashProfile::template()?;
         template.patch(raw_profile)?;
         template.convert(url_builder)?;
         template.secret = Some(secret.as_ref().to_string());
-        let main_profile = ClashRenderer::render_profile(&template)?;
-        file_provider.write(self.main_profile_path(), main_profile)?;
-        Ok(())
+        // 本地文件为空且没有模板文件时以完整的内置模板为基础, 否则只更新由 convertor 管理的部分
+        let content = match self.read_main_profile(file_provider)? {
+            content if content.trim().is_empty() => ClashRenderer::render_profile(&template)?,
+            content => content,
+        };
+        let main_profile = Self::update_clash_conf(content, &template)?;
+        let change = self.write_managed(
+            file_provider,
+            self.main_profile_path(),
+            main_profile,
+            &new_generation(),
+            validate_clash_conf,
+        )?;
+        Ok(change.into_iter().collect())
+    }
+
+    /// 替换 secret, proxies, proxy-groups, rule-providers 以及 rules 中起止注释之间的规则, 其余内容保持原样
+    ///
+    /// rules 中没有起止注释时补上起止注释, 已有的规则移到起始注释之前, 之后写在注释之外的规则不会被覆盖
+    fn update_clash_conf(content: String, profile: &ClashProfile) -> color_eyre::Result<String> {
+        let mut lines = content.lines().map(Cow::Borrowed).collect::<Vec<_>>();
+
+        let sections = [
+            ("proxies", ClashRenderer::render_proxies(&profile.proxies)?),
+            ("proxy-groups", ClashRenderer::render_proxy_groups(&profile.proxy_groups)?),
+            ("rule-providers", ClashRenderer::render_rule_providers(&profile.rule_providers)?),
+        ];
+        for (key, body) in sections {
+            let mut output = vec![Cow::Owned(format!("{key}:"))];
+            output.extend(body.lines().map(|line| Cow::Owned(line.to_string())));
+            match clash_section(&lines, key) {
+                Some(range) => {
+                    lines.splice(range, output);
+                }
+                None => lines.extend(output),
+            }
+        }
+
+        let indent = |line: &str| Cow::Owned(format!("{:indent$}{line}", "", indent = INDENT));
+        let mut output = vec![indent(CLASH_RULE_COMMENT_START)];
+        output.extend(
+            ClashRenderer::render_rules(&profile.rules)?
+                .lines()
+                .map(|line| Cow::Owned(line.to_string())),
+        );
+        output.push(indent(CLASH_RULE_COMMENT_END));
+        match clash_section(&lines, "rules") {
+            Some(range) => {
+                let position = |comment: &str| {
+                    lines[range.clone()]
+                        .iter()
+                        .position(|line| line.trim() == comment)
+                        .map(|no| range.start + no)
+                };
+                match (position(CLASH_RULE_COMMENT_START), position(CLASH_RULE_COMMENT_END)) {
+                    (Some(start), Some(end)) if start < end => {
+                        lines.splice(start..=end, output);
+                    }
+                    _ => {
+                        let generated = output.iter().map(|line| line.trim().to_string()).collect::<Vec<_>>();
+                        // 与生成的规则重复的以及兜底的 MATCH 规则会遮蔽生成的规则, 不再保留
+                        let mut kept = lines[range.start + 1..range.end]
+                            .iter()
+                            .filter(|line| {
+                                let rule = line.trim();
+                                !rule.is_empty()
+                                    && !generated.iter().any(|generated| generated == rule)
+                                    && !rule.trim_start_matches(['-', ' ']).starts_with("MATCH,")
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        kept.insert(0, Cow::Borrowed("rules:"));
+                        kept.extend(output);
+                        lines.splice(range, kept);
+                    }
+                }
+            }
+            None => {
+                output.insert(0, Cow::Borrowed("rules:"));
+                lines.extend(output);
+            }
+        }
+
+        if let Some(secret) = &profile.secret {
+            let output = Cow::Owned(format!(r#"secret: "{secret}""#));
+            match clash_section(&lines, "secret") {
+                Some(range) => {
+                    lines.splice(range, [output]);
+                }
+                None => lines.push(output),
+            }
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// 读取本地主配置, 内容为空时改用配置的模板文件
+    fn read_main_profile(&self, file_provider: &FileProvider) -> color_eyre::Result<String> {
+        let content = file_provider.read(self.main_profile_path())?;
+        match self.template_path() {
+            Some(template) if content.trim().is_empty() => file_provider.read(template),
+            _ => Ok(content),
+        }
+    }
+
+    /// 校验新内容后再写入, 内容有变化时先将原文件备份到 `generation` 批次
+    fn write_managed(
+        &self,
+        file_provider: &FileProvider,
+        path: impl AsRef<Path>,
+        content: String,
+        generation: &str,
+        validate: fn(&str) -> color_eyre::Result<()>,
+    ) -> color_eyre::Result<Option<FileChange>> {
+        let path = path.as_ref();
+        validate(&content).wrap_err_with(|| format!("生成的 {} 未通过校验, 保留原文件", path.display()))?;
+        file_provider.write_if_changed(path, content, generation, self.backups())
+    }
+}
+
+/// 以 convertor 的解析器解析已有的配置段, 并要求配置头带有托管链接
+///
+/// 本地文件可能只有托管配置头, 缺少的配置段由 Surge 更新托管配置时补全, 因此不要求配置段齐全
+fn validate_surge_conf(content: &str) -> color_eyre::Result<()> {
+    let mut sections = SurgeParser::parse_raw(content);
+    let header = sections
+        .remove(MANAGED_CONFIG_HEADER)
+        .map(SurgeParser::parse_header)
+        .transpose()?
+        .flatten();
+    if header.is_none_or(|header| header.url.is_none()) {
+        return Err(eyre!("第一行不是合法的托管配置头"));
+    }
+    if let Some(section) = sections.remove(PROXY_SECTION) {
+        SurgeParser::parse_proxies(section)?;
+    }
+    if let Some(section) = sections.remove(PROXY_GROUP_SECTION) {
+        SurgeParser::parse_proxy_groups(section)?;
+    }
+    if let Some(section) = sections.remove(RULE_SECTION) {
+        SurgeParser::parse_rules(section)?;
+    }
+    Ok(())
+}
+
+fn validate_surge_rules(content: &str) -> color_eyre::Result<()> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let start = lines.iter().position(|line| *line == SURGE_RULE_PROVIDER_COMMENT_START);
+    let end = lines.iter().position(|line| *line == SURGE_RULE_PROVIDER_COMMENT_END);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => Ok(()),
+        _ => Err(eyre!("缺少规则集的起止注释")),
+    }
+}
+
+/// 顶层键所在的行直到下一个顶层键之前, 紧挨着下一个顶层键的空行与顶格注释归属于下一个键
+fn clash_section(lines: &[Cow<str>], key: &str) -> Option<Range<usize>> {
+    let is_top_level = |line: &str| !line.is_empty() && !line.starts_with([' ', '\t', '#', '-']);
+    let start = lines
+        .iter()
+        .position(|line| line.strip_prefix(key).is_some_and(|rest| rest.starts_with(':')))?;
+    let mut end = lines[start + 1..]
+        .iter()
+        .position(|line| is_top_level(line))
+        .map_or(lines.len(), |no| start + 1 + no);
+    while end > start + 1 && (lines[end - 1].trim().is_empty() || lines[end - 1].starts_with('#')) {
+        end -= 1;
+    }
+    Some(start..end)
+}
+
+/// 用户的配置中可能有 convertor 不认识的字段, 因此只校验 YAML 结构以及由 convertor 管理的几个键
+fn validate_clash_conf(content: &str) -> color_eyre::Result<()> {
+    let config: serde_yaml::Mapping = serde_yaml::from_str(content)?;
+    for key in ["proxies", "proxy-groups", "rules"] {
+        if !config.get(key).is_some_and(serde_yaml::Value::is_sequence) {
+            return Err(eyre!("缺少 {key} 或 {key} 不是列表"));
+        }
     }
+    Ok(())
 }
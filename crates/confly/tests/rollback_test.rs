@@ -0,0 +1,135 @@
+use confly::command::rollback_cmd::RollbackCmd;
+use confly::command::watch_cmd::WatchCmd;
+use confly::config::ConflyConfig;
+use confly::file_provider::{BACKUP_DIR, FileProvider};
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use convertor::provider::SubsProvider;
+use convertor::testkit::start_mock_provider_server;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+#[test]
+fn test_file_system_backups() -> color_eyre::Result<()> {
+    let dir = std::env::temp_dir().join(format!("confly-backup-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("surge.conf");
+    let file_provider = FileProvider::FileSystem;
+
+    for i in 0..5 {
+        file_provider.write_if_changed(&path, format!("version {i}"), &format!("2025010{i}"), 3)?;
+    }
+    // 内容不变时既不写入也不备份
+    assert!(file_provider.write_if_changed(&path, "version 4", "20250105", 3)?.is_none());
+    assert_eq!(file_provider.backups(&path)?.len(), 3);
+    assert!(dir.join(BACKUP_DIR).is_dir());
+    // 临时文件已被重命名
+    assert_eq!(std::fs::read_dir(&dir)?.count(), 2);
+
+    let paths = [path.clone()];
+    assert_eq!(file_provider.latest_generation(&paths)?.as_deref(), Some("20250104"));
+    assert!(file_provider.restore(&path, "20250104", "20250106", 3)?.is_some());
+    assert_eq!(file_provider.read(&path)?, "version 3");
+    // 回滚前的内容同样被备份, 再次回滚即撤销
+    assert_eq!(file_provider.latest_generation(&paths)?.as_deref(), Some("20250106"));
+    assert!(file_provider.restore(&path, "20250106", "20250107", 3)?.is_some());
+    assert_eq!(file_provider.read(&path)?, "version 4");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_restore_only_latest_generation() -> color_eyre::Result<()> {
+    let file_provider = FileProvider::Memory(Arc::new(RwLock::new(HashMap::new())));
+    let surge = PathBuf::from("surge/surge.conf");
+    let rules = PathBuf::from("surge/rules.dconf");
+    file_provider.write(&surge, "surge 0")?;
+    file_provider.write(&rules, "rules 0")?;
+
+    // 第一次同步写入两个文件, 第二次只有 surge.conf 发生变化
+    file_provider.write_if_changed(&surge, "surge 1", "20250101", 3)?;
+    file_provider.write_if_changed(&rules, "rules 1", "20250101", 3)?;
+    file_provider.write_if_changed(&surge, "surge 2", "20250102", 3)?;
+
+    let paths = [surge.clone(), rules.clone()];
+    let generation = file_provider.latest_generation(&paths)?.expect("应当有备份");
+    assert_eq!(generation, "20250102");
+    assert!(file_provider.restore(&surge, &generation, "20250103", 3)?.is_some());
+    assert!(file_provider.restore(&rules, &generation, "20250103", 3)?.is_none());
+    assert_eq!(file_provider.read(&surge)?, "surge 1");
+    assert_eq!(file_provider.read(&rules)?, "rules 1");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_through_symlink() -> color_eyre::Result<()> {
+    let dir = std::env::temp_dir().join(format!("confly-symlink-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("dotfiles"))?;
+    let target = dir.join("dotfiles").join("config.yaml");
+    let link = dir.join("config.yaml");
+    std::fs::write(&target, "version 0")?;
+    std::os::unix::fs::symlink(&target, &link)?;
+
+    FileProvider::FileSystem.write_if_changed(&link, "version 1", "20250101", 3)?;
+    assert!(std::fs::symlink_metadata(&link)?.file_type().is_symlink());
+    assert_eq!(std::fs::read_to_string(&target)?, "version 1");
+    // 临时文件写在链接指向的目录中, 重命名后不留残余
+    assert_eq!(std::fs::read_dir(dir.join("dotfiles"))?.count(), 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rollback_after_sync() -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    start_mock_provider_server(&mut config.common).await?;
+    let client = ProxyClient::Clash;
+    let client_config = config.client(client.as_str())?;
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+    let mut files = HashMap::new();
+    files.insert(client_config.main_profile_path(), "mode: rule".to_string());
+    let file_provider = FileProvider::Memory(Arc::new(RwLock::new(files)));
+
+    let rollback = RollbackCmd {
+        name: client.to_string(),
+        list: false,
+    };
+    assert!(rollback.execute(&config, &file_provider).await.is_err());
+
+    let watch = WatchCmd {
+        name: client.to_string(),
+        url: None,
+        interval: None,
+        once: true,
+    };
+    let url_builder = watch.url_builder(&config)?;
+    watch
+        .sync(&config, client_config, &url_builder, &subs_provider, &file_provider)
+        .await?;
+    assert_ne!(file_provider.read(client_config.main_profile_path())?, "mode: rule");
+
+    let list = RollbackCmd {
+        name: client.to_string(),
+        list: true,
+    }
+    .execute(&config, &file_provider).await?;
+    assert!(list.contains(BACKUP_DIR));
+    let synced = file_provider.read(client_config.main_profile_path())?;
+    rollback.execute(&config, &file_provider).await?;
+    assert_eq!(file_provider.read(client_config.main_profile_path())?, "mode: rule");
+    // 同步后的内容在回滚前被备份, 再次回滚即撤销
+    assert_eq!(file_provider.backups(client_config.main_profile_path())?.len(), 1);
+    rollback.execute(&config, &file_provider).await?;
+    assert_eq!(file_provider.read(client_config.main_profile_path())?, synced);
+    Ok(())
+}
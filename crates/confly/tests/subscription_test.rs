@@ -1,30 +1,45 @@
 use color_eyre::eyre::OptionExt;
-use confly::command::subscription_cmd::SubscriptionCmd;
+use confly::command::subscription_cmd::{OutputFormat, SubscriptionAction, SubscriptionCmd};
 use confly::config::{ClientConfig, ConflyConfig};
 use confly::file_provider::FileProvider;
+use convertor::common::encrypt::decrypt;
 use convertor::config::proxy_client::ProxyClient;
+use convertor::core::diff::ProfileDiff;
 use convertor::init_test;
 use convertor::provider::SubsProvider;
 use convertor::testkit::start_mock_provider_server;
+use convertor::url::url_result::UrlResult;
+use httpmock::Method::GET;
+use httpmock::MockServer;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use url::Url;
 
 pub fn cmds(client: ProxyClient) -> [SubscriptionCmd; 2] {
     [
         SubscriptionCmd {
-            client,
+            action: None,
+            name: Some(client.to_string()),
             url: None,
             update: false,
+            ..Default::default()
         },
         SubscriptionCmd {
-            client,
+            action: None,
+            name: Some(client.to_string()),
             url: None,
             update: true,
+            ..Default::default()
         },
     ]
 }
 
 pub fn file_provider(config: &ClientConfig) -> FileProvider {
+    FileProvider::Memory(Arc::new(RwLock::new(files(config))))
+}
+
+pub fn files(config: &ClientConfig) -> HashMap<PathBuf, String> {
     let mut test_assets_dir = HashMap::new();
     test_assets_dir.insert(config.main_profile_path(), "".to_string());
     if let Some(raw_path) = config.raw_path() {
@@ -39,19 +54,21 @@ pub fn file_provider(config: &ClientConfig) -> FileProvider {
             "# Rule Provider from convertor\n# End of Rule Provider".to_string(),
         );
     }
-    FileProvider::Memory(Arc::new(RwLock::new(test_assets_dir)))
+    test_assets_dir
 }
 
 async fn test_subscription(client: ProxyClient) -> color_eyre::Result<()> {
     let base_dir = init_test!();
     let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
-    let client_config = config
-        .clients
-        .get(&client)
-        .ok_or_eyre(format!("没有找到 {client} 客户端配置"))?;
     start_mock_provider_server(&mut config.common).await?;
+    let client_config = config.client(client.as_str())?;
 
-    let subs_provider = SubsProvider::new(None, config.common.redis.as_ref().map(|r| r.prefix.as_str()));
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
     let cmds = cmds(client);
     for (i, cmd) in cmds.into_iter().enumerate() {
         let ctx = format!("test_subscription_{client}_cmd_{i}");
@@ -120,3 +137,202 @@ async fn test_subscription_surge() -> color_eyre::Result<()> {
 async fn test_subscription_clash() -> color_eyre::Result<()> {
     test_subscription(ProxyClient::Clash).await
 }
+
+#[tokio::test]
+async fn test_subscription_clash_preserves_user_edits() -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    let client = ProxyClient::Clash;
+    start_mock_provider_server(&mut config.common).await?;
+    let client_config = config.client(client.as_str())?;
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+
+    let local = [
+        "mixed-port: 7890",
+        r#"secret: "mine""#,
+        "proxies:",
+        r#"    - { name: "old", type: "ss", server: "old.com", port: 1, password: "old" }"#,
+        "proxy-groups: []",
+        "# 自定义 DNS",
+        "dns:",
+        "    enable: true",
+        "rules:",
+        "    - DOMAIN,intranet.example.com,DIRECT",
+        "    - MATCH,old",
+        "tun:",
+        "    enable: true",
+    ]
+    .join("\n");
+    let mut files = HashMap::new();
+    files.insert(client_config.main_profile_path(), local);
+    let file_provider = FileProvider::Memory(Arc::new(RwLock::new(files)));
+    let [_, cmd] = cmds(client);
+
+    cmd.clone().execute(&config, &subs_provider, &file_provider).await?;
+    let updated = file_provider.read(client_config.main_profile_path())?;
+    // secret 仍由 convertor 管理
+    let secret = format!("secret: \"{}\"", config.common.secret);
+    assert!(updated.starts_with(&format!("mixed-port: 7890\n{secret}\nproxies:\n")));
+    // 首次更新时已有的规则移到起始注释之前, 兜底的 MATCH 规则不再保留
+    assert!(updated.contains(
+        "# 自定义 DNS\ndns:\n    enable: true\nrules:\n    - DOMAIN,intranet.example.com,DIRECT\n    # Rules from convertor\n"
+    ));
+    assert!(!updated.contains("MATCH,old"));
+    // 本地没有的 rule-providers 追加到文件末尾
+    assert!(updated.contains("    # End of Rules\ntun:\n    enable: true\nrule-providers:\n"));
+    assert!(!updated.contains("old.com"));
+
+    // 写在起止注释之外的规则在后续的更新中保留
+    let edited = updated.replace("rules:\n", "rules:\n    - DOMAIN,nas.example.com,DIRECT\n");
+    file_provider.write(client_config.main_profile_path(), edited.clone())?;
+    cmd.execute(&config, &subs_provider, &file_provider).await?;
+    // 规则集的 URL 中加密的 sub_url 每次都不同, 只比较规则集之前的部分
+    let updated = file_provider.read(client_config.main_profile_path())?;
+    assert_eq!(updated.split("rule-providers:").next(), edited.split("rule-providers:").next());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subscription_all_named_clients() -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    start_mock_provider_server(&mut config.common).await?;
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+
+    // 与 [clash] 并存的另一份 mihomo 配置, 使用自己的目录和模板
+    let mut work = ClientConfig::clash_template();
+    work.set_config_dir("work");
+    work.set_template(Some("template.yaml".into()));
+    let template = ["mixed-port: 7890", "dns:", "    enable: true"].join("\n");
+    config.clients.insert("work".to_string(), work);
+    // 本地文件缺失的配置更新失败, 不影响其余配置
+    let mut broken = ClientConfig::clash_template();
+    broken.set_config_dir("broken");
+    config.clients.insert("broken".to_string(), broken);
+
+    let mut files = HashMap::new();
+    for (name, client_config) in config.sorted_clients() {
+        if name != "broken" {
+            files.extend(self::files(client_config));
+        }
+    }
+    let work = config.client("work")?;
+    files.insert(work.template_path().ok_or_eyre("没有模板")?, template);
+    let file_provider = FileProvider::Memory(Arc::new(RwLock::new(files)));
+
+    let cmd = SubscriptionCmd {
+        all: true,
+        ..Default::default()
+    };
+    let results = cmd.execute_all(&config, &subs_provider, &file_provider).await;
+    let names = results.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["broken", "clash", "surge", "work"]);
+    let failed = results.iter().filter(|(_, result)| result.is_err()).map(|(name, _)| name.as_str());
+    assert_eq!(failed.collect::<Vec<_>>(), ["broken"]);
+    let output = cmd.render_all(&results)?;
+    assert!(output.starts_with("[broken]\n更新失败: "));
+    assert!(output.contains("[work]\n"));
+
+    let clash = file_provider.read(config.client("clash")?.main_profile_path())?;
+    assert!(clash.starts_with("port: 7890"));
+    let work = file_provider.read(work.main_profile_path())?;
+    assert!(work.starts_with("mixed-port: 7890\ndns:\n    enable: true\nproxies:\n"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subscription_diff_uses_named_entry() -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    let server = MockServer::start_async().await;
+    config.common.server = Url::parse(&server.base_url())?;
+    // 具名配置使用自己的 sub_url 查询订阅变化
+    let work_sub_url = "https://work.example.com/sub";
+    let mut work = ClientConfig::clash_template();
+    work.set_sub_url(Some(Url::parse(work_sub_url)?));
+    config.clients.insert("work".to_string(), work);
+
+    let secret = config.common.secret.clone();
+    let diff_mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/api/subscription/clash/diff").is_true(move |request| {
+                request
+                    .query_params()
+                    .iter()
+                    .any(|(k, v)| k == "sub_url" && decrypt(secret.as_bytes(), v).is_ok_and(|url| url == work_sub_url))
+            });
+            then.status(200)
+                .json_body(serde_json::json!({ "status": "ok", "data": ProfileDiff::default() }));
+        })
+        .await;
+
+    let action = SubscriptionAction::Diff {
+        name: "work".to_string(),
+        url: None,
+    };
+    assert_eq!(action.execute(&config).await?, ProfileDiff::default().to_string());
+    diff_mock.assert_calls_async(1).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_subscription_output_formats() -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    start_mock_provider_server(&mut config.common).await?;
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+    let [cmd, _] = cmds(ProxyClient::Clash);
+    let file_provider = file_provider(config.client("clash")?);
+    let (_, result) = cmd.clone().execute(&config, &subs_provider, &file_provider).await?;
+    let urls = 3 + result.rule_providers_url.len();
+
+    // 默认输出 UrlResult 原有的文本格式, 表格需要显式指定
+    assert_eq!(cmd.render(&result)?, result.to_string());
+    let table = SubscriptionCmd {
+        format: OutputFormat::Table,
+        ..cmd.clone()
+    }
+    .render(&result)?;
+    assert_eq!(table.lines().count(), urls + usize::from(result.userinfo.is_some()));
+    assert!(table.contains(&result.profile_url.to_string()));
+
+    let json = SubscriptionCmd {
+        format: OutputFormat::Json,
+        ..cmd.clone()
+    }
+    .render(&result)?;
+    let parsed: UrlResult = serde_json::from_str(&json)?;
+    assert_eq!(parsed.profile_url.to_string(), result.profile_url.to_string());
+
+    // 原始订阅链接不生成二维码
+    let qr = SubscriptionCmd { qr: true, ..cmd.clone() }.render(&result)?;
+    assert!(qr.contains('█'));
+    let inverted = SubscriptionCmd {
+        qr: true,
+        invert: true,
+        ..cmd
+    }
+    .render(&result)?;
+    assert_ne!(qr, inverted);
+    assert!(qr.contains(&result.profile_url.desc));
+    assert!(!qr.contains(&result.raw_url.to_string()));
+    for url in &result.rule_providers_url {
+        assert!(qr.contains(&url.to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use confly::command::watch_cmd::WatchCmd;
+use confly::config::{ConflyConfig, ReloadConfig};
+use confly::file_provider::FileProvider;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use convertor::provider::SubsProvider;
+use convertor::testkit::start_mock_provider_server;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+async fn test_watch_sync(client: ProxyClient) -> color_eyre::Result<()> {
+    let base_dir = init_test!();
+    let mut config = ConflyConfig::search(&base_dir, None::<&str>)?;
+    start_mock_provider_server(&mut config.common).await?;
+    let client_config = config.client(client.as_str())?;
+    let subs_provider = SubsProvider::new(
+        None,
+        config.common.redis.as_ref().map(|r| r.prefix.as_str()),
+        &config.common.cache.subscription,
+    )
+    .with_config(&config.common)?;
+    let mut files = HashMap::new();
+    files.insert(client_config.main_profile_path(), "".to_string());
+    for path in [client_config.raw_path(), client_config.raw_profile_path()].into_iter().flatten() {
+        files.insert(path, "".to_string());
+    }
+    if let Some(rules_path) = client_config.rules_path() {
+        files.insert(
+            rules_path,
+            "# Rule Provider from convertor\n# End of Rule Provider".to_string(),
+        );
+    }
+    let file_provider = FileProvider::Memory(Arc::new(RwLock::new(files)));
+    let cmd = WatchCmd {
+        name: client.to_string(),
+        url: None,
+        interval: None,
+        once: true,
+    };
+    let url_builder = cmd.url_builder(&config)?;
+
+    // 第一次同步写入全部文件, 订阅不变时第二次不再改写
+    let first = cmd.sync(&config, client_config, &url_builder, &subs_provider, &file_provider).await?;
+    assert!(!first.changes.is_empty());
+    assert!(first.changes.iter().all(|change| change.added > 0));
+    let main_profile = file_provider.read(client_config.main_profile_path())?;
+    let second = cmd.sync(&config, client_config, &url_builder, &subs_provider, &file_provider).await?;
+    assert!(second.changes.is_empty(), "{:?}", second.changes);
+    assert_eq!(file_provider.read(client_config.main_profile_path())?, main_profile);
+    assert_eq!(first.snapshot.content_hash, second.snapshot.content_hash);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_watch_sync_surge() -> color_eyre::Result<()> {
+    test_watch_sync(ProxyClient::Surge).await
+}
+
+#[tokio::test]
+async fn test_watch_sync_clash() -> color_eyre::Result<()> {
+    test_watch_sync(ProxyClient::Clash).await
+}
+
+#[tokio::test]
+async fn test_reload_command() -> color_eyre::Result<()> {
+    let marker = std::env::temp_dir().join(format!("confly-reload-{}", std::process::id()));
+    let reload = ReloadConfig {
+        command: vec!["touch".to_string(), marker.display().to_string()],
+        ..Default::default()
+    };
+    reload.trigger(&marker).await?;
+    assert!(marker.exists());
+    std::fs::remove_file(&marker)?;
+
+    let reload = ReloadConfig {
+        command: vec!["false".to_string()],
+        ..Default::default()
+    };
+    assert!(reload.trigger(&marker).await.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_url_builder_carries_token() -> color_eyre::Result<()> {
+    let mut config = ConflyConfig::template();
+    config.token = Some("user-token".to_string());
+    // 顶层的 token 不会被当作客户端配置
+    let config = toml::from_str::<ConflyConfig>(&config.to_string())?;
+    assert_eq!(config.token.as_deref(), Some("user-token"));
+    assert_eq!(config.clients.len(), 2);
+
+    let cmd = WatchCmd {
+        name: ProxyClient::Surge.to_string(),
+        url: None,
+        interval: None,
+        once: true,
+    };
+    let url_builder = cmd.url_builder(&config)?;
+    assert!(url_builder.build_profile_url()?.to_string().ends_with("&token=user-token"));
+    Ok(())
+}
@@ -6,12 +6,12 @@ use tokio::time::{Duration, Instant, sleep_until};
 #[allow(unused)]
 pub trait WatchDebounceExt<T> {
     /// 等到有变化后，静默 `duration`，期间若继续变化就重置计时；
-    /// 静默期结束返回“最后一个值”。发送端全关返回 None。
+    /// 静默期结束返回“最后一个值”。等待首次变化时发送端全关返回 Err。
     async fn recv_debounced<'a>(&'a mut self, duration: Duration) -> Result<watch::Ref<'a, T>, watch::error::RecvError>
     where
         T: 'a;
 
-    /// 去抖 + 去重：与上次返回相同则继续等待（需要 PartialEq）
+    /// 去抖 + 过滤：`accept` 返回 false 时丢弃本次结果，继续等待下一次变化
     async fn recv_debounced_distinct<'a, F>(
         &'a mut self,
         duration: Duration,
@@ -19,7 +19,7 @@ pub trait WatchDebounceExt<T> {
     ) -> Result<watch::Ref<'a, T>, watch::error::RecvError>
     where
         T: 'a,
-        F: FnMut(&watch::Ref<'a, T>) -> bool;
+        F: FnMut(&T) -> bool;
 }
 
 impl<T: Clone> WatchDebounceExt<T> for watch::Receiver<T> {
@@ -33,7 +33,11 @@ impl<T: Clone> WatchDebounceExt<T> for watch::Receiver<T> {
 
         loop {
             select! {
-                _ = async { self.changed().await.ok() } => {
+                changed = self.changed() => {
+                    // sender 全关后不会再有变化，直接返回最后一个值，避免空转
+                    if changed.is_err() {
+                        return Ok(self.borrow());
+                    }
                     deadline = Instant::now() + duration;
                 }
                 _ = sleep_until(deadline) => {
@@ -50,13 +54,13 @@ impl<T: Clone> WatchDebounceExt<T> for watch::Receiver<T> {
     ) -> Result<watch::Ref<'a, T>, watch::error::RecvError>
     where
         T: 'a,
-        F: FnMut(&watch::Ref<'a, T>) -> bool,
+        F: FnMut(&T) -> bool,
     {
-        // 以当前快照为“上一次值”的基准
-        let last = self.recv_debounced(duration).await?;
         loop {
-            if accept(&last) {
-                return Ok(last);
+            // 不接受时释放本次借用，重新等待下一次变化
+            let accepted = accept(&*self.recv_debounced(duration).await?);
+            if accepted {
+                return Ok(self.borrow());
             }
         }
     }
@@ -1,10 +1,11 @@
 use clap::{Parser, Subcommand};
 use color_eyre::Result;
+use convd::server::listen::ListenAddr;
+use convd::server::reload::ConfigSource;
 use convd::server::start_server;
 use convertor::common::clap_style::SONOKAI_TC;
 use convertor::common::once::{init_backtrace, init_base_dir, init_log, shutdown_telemetry};
 use convertor::config::Config;
-use std::net::SocketAddrV4;
 use std::path::PathBuf;
 use tracing::info;
 
@@ -12,9 +13,10 @@ use tracing::info;
 #[clap(version, author, styles = SONOKAI_TC)]
 /// 启动 Convertor 服务
 struct Convd {
-    /// 监听地址, 不需要指定协议
-    #[arg(default_value = "127.0.0.1:8080")]
-    listen: SocketAddrV4,
+    /// 监听地址, 不需要指定协议, 可以指定多个
+    /// 支持 `127.0.0.1:8080`, `[::]:8080` 以及 `unix:/run/convd.sock`
+    #[arg(default_value = "127.0.0.1:8080", num_args = 1..)]
+    listen: Vec<ListenAddr>,
 
     /// 如果你想特别指定配置文件, 可以使用此参数
     #[arg(short)]
@@ -57,10 +59,11 @@ async fn main() -> Result<()> {
     info!("+──────────────────────────────────────────────+");
     info!("│               加载配置文件...                │");
     info!("+──────────────────────────────────────────────+");
-    let config: Config = Config::search(&base_dir, args.config)?;
+    let source = ConfigSource::new(&base_dir, args.config);
+    let config: Config = source.load()?;
     info!("配置文件加载完成");
 
-    start_server(args.listen, config).await?;
+    start_server(args.listen, config, source).await?;
 
     // 优雅关闭：确保所有 pending 的 spans 都导出到 Tempo
     info!("正在关闭遥测系统，确保所有追踪数据已导出...");
@@ -1,21 +1,33 @@
 use crate::server::app_state::AppState;
-use axum::Router;
+use crate::server::listen::ListenAddr;
+use crate::server::reload::{ConfigSource, Reloader};
+use crate::server::tls::{TlsListener, TlsTerminator};
+use axum::extract::ConnectInfo;
+use axum::serve::IncomingStream;
+use axum::{Extension, Router};
 use color_eyre::Result;
 use convertor::config::Config;
-use std::net::{SocketAddr, SocketAddrV4};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::{TcpListener, TcpSocket};
 use tokio::signal;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tower::Layer;
 use tracing::{info, warn};
 
 pub mod app_state;
 pub mod layer;
+pub mod listen;
 pub mod query;
+pub mod reload;
 pub mod response;
 pub mod router;
 pub mod service;
+pub mod tls;
 
-pub async fn start_server(listen_addr: SocketAddrV4, config: Config) -> Result<()> {
+pub async fn start_server(listen_addrs: Vec<ListenAddr>, config: Config, source: ConfigSource) -> Result<()> {
     let (redis_client, connection_manager) = match config.redis.as_ref() {
         Some(redis_config) => {
             info!("+──────────────────────────────────────────────+");
@@ -30,111 +42,115 @@ pub async fn start_server(listen_addr: SocketAddrV4, config: Config) -> Result<(
                     .set_max_delay(2000),
             )
             .await?;
-            // let mut config_receiver = start_sub_config(redis_client.clone(), config);
-            // let mut current_config = config_receiver.borrow().clone();
             info!("Redis 连接就绪");
             (Some(redis_client), Some(connection_manager))
         }
         None => (None, None),
     };
-    let current_config = config;
+    let tls = config.tls.clone().map(TlsTerminator::new).transpose()?.map(Arc::new);
 
     let cancel_token = CancellationToken::new();
 
     info!("+──────────────────────────────────────────────+");
     info!("│                 启动服务...                  │");
     info!("+──────────────────────────────────────────────+");
-    // loop {
-    // 1) 启一个子 token 控制“本轮” server
-    let stop_this = cancel_token.child_token();
-
-    // 2) 构建 state / router
-    let state = AppState::new(current_config, redis_client.clone(), connection_manager.clone());
-    let app: Router = router::router(state);
-
-    // 3) 绑定端口（Tokio 自带 TcpSocket，可设置 reuseaddr；不引新库）
-    let listener = bind_once(listen_addr)?;
+    // 1) 构建 state / router, 配置变化后只替换路由, 监听地址保持不变
+    let reloader = Reloader::new(config, source, redis_client, connection_manager).await?;
+    let app: Router = reloader.router();
+    tokio::spawn(reloader.run(cancel_token.child_token()));
+    if let Some(tls) = &tls {
+        tls.clone().spawn_reload(cancel_token.child_token());
+    }
 
-    // 4) 跑 server，直到被 cancel
-    let serve_handle = tokio::spawn({
-        let stop_this = stop_this.clone();
-        async move {
-            warn!("使用 Ctrl+C 或 SIGTERM 关闭服务, 建议使用 nginx 等网关进行反向代理, 以开启 HTTPS 支持");
-            info!("服务启动, 监听于: {listen_addr}");
-            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
-                .with_graceful_shutdown(async move { stop_this.cancelled().await })
-                .await
-        }
-    });
+    // 2) 绑定所有监听地址, 任一地址绑定失败则直接退出
+    match &tls {
+        Some(_) => info!("已启用 TLS, TCP 监听地址将直接提供 HTTPS"),
+        None => warn!("未配置 TLS, 建议配置 tls 证书或使用 nginx 等网关进行反向代理, 以开启 HTTPS 支持"),
+    }
+    warn!("使用 Ctrl+C 或 SIGTERM 关闭服务");
+    let mut serve_handles = Vec::with_capacity(listen_addrs.len());
+    for listen_addr in listen_addrs {
+        serve_handles.push(serve(&listen_addr, app.clone(), tls.as_deref(), cancel_token.child_token())?);
+        info!("服务启动, 监听于: {listen_addr}");
+    }
 
-    // 5) 等待：要么全局退出，要么收到“去抖后的配置更新”
-    tokio::select! {
-            _ = shutdown_signal() => {
-                // 收到 Ctrl+C 或 SIGTERM 信号，准备退出
-                info!("收到退出信号，准备关闭服务…");
-                stop_this.cancel();
-                let _ = serve_handle.await;
-                // break;
-            }
-            _ = cancel_token.cancelled() => {
-                // 全局退出：关本轮 server 并退出
-                stop_this.cancel();
-                let _ = serve_handle.await;
-                // break;
-            }
-            // Ok(config) = config_receiver.recv_debounced_distinct(tokio::time::Duration::from_secs(1), |_| true) => {
-            //     info!("收到配置更新通知，准备重启服务…");
-            //     info!("停止旧的服务…");
-            //     stop_this.cancel();
-            //     let _ = serve_handle.await;
-            //     info!("更新配置…");
-            //     current_config = config.clone();
-            //     continue;
-            // }
-        // }
+    // 3) 等待退出信号, 关闭所有监听并等待处理中的请求结束
+    shutdown_signal().await;
+    info!("收到退出信号，准备关闭服务…");
+    cancel_token.cancel();
+    for serve_handle in serve_handles {
+        let _ = serve_handle.await;
     }
     info!("服务关闭");
     Ok(())
 }
 
-fn bind_once(addr: SocketAddrV4) -> Result<TcpListener> {
-    let sock = TcpSocket::new_v4()?;
+/// 在一个地址上启动服务, TCP 地址在配置了证书时提供 HTTPS
+pub fn serve(
+    listen_addr: &ListenAddr,
+    app: Router,
+    tls: Option<&TlsTerminator>,
+    stop: CancellationToken,
+) -> Result<JoinHandle<std::io::Result<()>>> {
+    let shutdown = async move { stop.cancelled().await };
+    let handle = match (listen_addr, tls) {
+        (ListenAddr::Tcp(addr), Some(tls)) => {
+            let listener = TlsListener::new(bind_once(*addr)?, tls.acceptor())?;
+            // axum 只为 TcpListener 实现了 SocketAddr 的 Connected, 这里自行为每个连接注入 ConnectInfo
+            let make_service = tower::service_fn(move |incoming: IncomingStream<'_, TlsListener>| {
+                let connect_info = Extension(ConnectInfo(*incoming.remote_addr()));
+                std::future::ready(Ok::<_, Infallible>(connect_info.layer(app.clone())))
+            });
+            tokio::spawn(async move { axum::serve(listener, make_service).with_graceful_shutdown(shutdown).await })
+        }
+        (ListenAddr::Tcp(addr), None) => {
+            let listener = bind_once(*addr)?;
+            tokio::spawn(async move {
+                axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            })
+        }
+        #[cfg(unix)]
+        (ListenAddr::Unix(path), _) => {
+            use std::os::unix::fs::FileTypeExt;
+            // 清理上次运行遗留的套接字文件, 同名的其它文件不删除
+            match std::fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+                Ok(_) => {
+                    return Err(color_eyre::eyre::eyre!("{} 已存在且不是 Unix 套接字, 拒绝删除", path.display()));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            tokio::spawn(async move { axum::serve(listener, app.into_make_service()).with_graceful_shutdown(shutdown).await })
+        }
+        #[cfg(not(unix))]
+        (ListenAddr::Unix(path), _) => {
+            return Err(color_eyre::eyre::eyre!("当前平台不支持 Unix 套接字: {}", path.display()));
+        }
+    };
+    Ok(handle)
+}
+
+fn bind_once(addr: SocketAddr) -> Result<TcpListener> {
+    let sock = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => {
+            let sock = TcpSocket::new_v6()?;
+            // 显式关闭 IPV6_V6ONLY, 使 [::] 同时接受 IPv4 连接, 不依赖系统默认值
+            socket2::SockRef::from(&sock).set_only_v6(false)?;
+            sock
+        }
+    };
     sock.set_reuseaddr(true)?;
     // #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
     // sock.set_reuseport(true)?; // 可选：看需要
-    sock.bind(SocketAddr::V4(addr))?;
+    sock.bind(addr)?;
     Ok(sock.listen(1024)?)
 }
 
-// 启动 ConvertorConfig 的更新订阅
-// fn start_sub_config(client: redis::Client, config: ConvertorConfig) -> watch::Receiver<ConvertorConfig> {
-//     use color_eyre::eyre::WrapErr;
-//     use convertor::common::redis::REDIS_CONVERTOR_CONFIG_PUBLISH_CHANNEL;
-//     use tokio_stream::StreamExt;
-//
-//     let (tx, rx) = watch::channel(config);
-//     tokio::spawn(async move {
-//         info!("启动 Redis subscribe");
-//         let connection = client.get_multiplexed_async_connection().await?;
-//         let mut sub = client.get_async_pubsub().await?;
-//         sub.subscribe(REDIS_CONVERTOR_CONFIG_PUBLISH_CHANNEL).await?;
-//         let mut stream = sub.into_on_message();
-//         while let Some(msg) = stream.next().await {
-//             info!("Redis sub 接收到: {msg:?}");
-//             if msg.get_channel_name() == REDIS_CONVERTOR_CONFIG_PUBLISH_CHANNEL {
-//                 if let Err(e) = ConvertorConfig::from_redis(connection.clone())
-//                     .await
-//                     .and_then(|config| tx.send(config).wrap_err("无法发送更新配置"))
-//                 {
-//                     error!("{e:?}");
-//                 }
-//             }
-//         }
-//         Ok::<(), color_eyre::Report>(())
-//     });
-//     rx
-// }
-
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
@@ -1,8 +1,27 @@
-use crate::server::service::{ClashService, SurgeService};
+use crate::server::layer::rate_limit::RateLimiter;
+use crate::server::response::AppError;
+use crate::server::service::{ClashService, NodeHealth, SurgeService};
+use axum::http::HeaderMap;
+use axum::http::header::USER_AGENT;
+use convertor::auth::user_store::UserStore;
+use convertor::common::cache::CACHE_INVALIDATION_CHANNEL;
 use convertor::config::Config;
+use convertor::config::subscription_config::Headers;
+use convertor::core::geoip::GeoIp;
+use convertor::error::{ProviderError, UpstreamProxyError};
 use convertor::provider::SubsProvider;
+use convertor::provider::cache_admin::{CacheOverview, CachePurge};
+use convertor::provider::profile_history::ProfileHistory;
+use convertor::provider::sub_log::SubLogs;
+use convertor::provider::subs_response::SubsResponse;
+use convertor::url::short_link::ShortLinkStore;
+use convertor::url::url_builder::UrlBuilder;
+use futures_util::StreamExt;
 use redis::aio::ConnectionManager;
+use redis::{AsyncTypedCommands, RedisError};
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -10,23 +29,219 @@ pub struct AppState {
     pub redis: Option<redis::Client>,
     pub redis_connection: Option<ConnectionManager>,
     pub provider: SubsProvider,
+    pub sub_logs: SubLogs,
+    pub users: UserStore,
+    pub short_links: ShortLinkStore,
+    pub rate_limiter: RateLimiter,
+    pub profile_history: ProfileHistory,
+    /// 规则匹配模拟使用的国家库, 未配置或加载失败时为空
+    pub geoip: Option<Arc<GeoIp>>,
+    pub node_health: NodeHealth,
     pub surge_service: SurgeService,
     pub clash_service: ClashService,
 }
 
 impl AppState {
-    pub fn new(config: Config, redis: Option<redis::Client>, redis_connection: Option<ConnectionManager>) -> Self {
+    pub fn new(config: Config, redis: Option<redis::Client>, redis_connection: Option<ConnectionManager>) -> Result<Self, UpstreamProxyError> {
         let config = Arc::new(config);
-        let surge_service = SurgeService::new(config.clone());
-        let clash_service = ClashService::new(config.clone());
-        let provider = SubsProvider::new(redis_connection.clone(), config.redis.as_ref().map(|r| r.prefix.as_str()));
-        Self {
+        let store = config.cache.build_store(redis_connection.clone()).unwrap_or_else(|e| {
+            error!("无法初始化持久化缓存, 仅使用内存缓存: {e}");
+            None
+        });
+        let provider = SubsProvider::new(store, config.redis.as_ref().map(|r| r.prefix.as_str()), &config.cache.subscription)
+            .with_config(&config)?;
+        let sub_logs = SubLogs::new(redis_connection.clone(), &provider.cache_prefix, config.cache.sub_logs.retention)
+            .with_analyze(config.cache.sub_logs.analyze);
+        let provider = provider.with_sub_logs(sub_logs.clone());
+        let users = UserStore::new(&config.auth, redis_connection.clone(), &provider.cache_prefix);
+        let short_links = ShortLinkStore::new(redis_connection.clone(), &provider.cache_prefix);
+        let rate_limiter = RateLimiter::new(&config, redis_connection.clone(), &provider.cache_prefix);
+        let profile_history = ProfileHistory::new(redis_connection.clone(), &provider.cache_prefix)
+            .with_webhook(provider.client.clone(), config.subscription.diff_webhook.clone());
+        let geoip = open_geoip(&config);
+        let node_health = NodeHealth::new(config.health_check.clone());
+        let surge_service = SurgeService::new(config.clone(), profile_history.clone(), node_health.clone());
+        let clash_service = ClashService::new(config.clone(), profile_history.clone(), node_health.clone());
+        Ok(Self {
             config,
             redis,
             redis_connection,
             provider,
+            sub_logs,
+            users,
+            short_links,
+            rate_limiter,
+            profile_history,
+            geoip,
+            node_health,
             surge_service,
             clash_service,
+        })
+    }
+
+    /// 应用新配置, 对应配置没有变化的部分沿用当前的缓存与运行时数据, 只重建发生变化的部分
+    ///
+    /// Redis 配置变化时无法沿用已有的存储, 全部重建
+    pub fn reload(&self, config: Config) -> Result<Self, UpstreamProxyError> {
+        let previous = self.config.clone();
+        if config.redis != previous.redis {
+            return Self::new(config, self.redis.clone(), self.redis_connection.clone());
+        }
+        let config = Arc::new(config);
+        let redis_connection = self.redis_connection.clone();
+        let prefix = self.provider.cache_prefix.clone();
+
+        let sub_logs = match config.cache.sub_logs.retention == previous.cache.sub_logs.retention {
+            true => self.sub_logs.clone(),
+            false => SubLogs::new(redis_connection.clone(), &prefix, config.cache.sub_logs.retention),
+        }
+        .with_analyze(config.cache.sub_logs.analyze);
+        let provider = match config.cache == previous.cache {
+            true => self.provider.reconfigure(&config)?,
+            false => {
+                let store = config.cache.build_store(redis_connection.clone()).unwrap_or_else(|e| {
+                    error!("无法初始化持久化缓存, 仅使用内存缓存: {e}");
+                    None
+                });
+                SubsProvider::new(store, Some(&prefix), &config.cache.subscription).with_config(&config)?
+            }
+        };
+        let provider = provider.with_sub_logs(sub_logs.clone());
+        let users = match config.auth == previous.auth {
+            true => self.users.clone(),
+            false => UserStore::new(&config.auth, redis_connection.clone(), &prefix),
+        };
+        users.invalidate();
+        let rate_limiter = self.rate_limiter.reconfigure(&config);
+        let profile_history = self
+            .profile_history
+            .clone()
+            .with_webhook(provider.client.clone(), config.subscription.diff_webhook.clone());
+        let geoip = match config.geoip == previous.geoip {
+            true => self.geoip.clone(),
+            false => open_geoip(&config),
+        };
+        let node_health = match config.health_check == previous.health_check {
+            true => self.node_health.clone(),
+            false => NodeHealth::new(config.health_check.clone()),
+        };
+        // 转换后的配置取决于订阅配置与节点状态, 均未变化时沿用已转换的配置
+        let keep_profiles = config.cache.profile == previous.cache.profile
+            && config.subscription == previous.subscription
+            && config.health_check == previous.health_check;
+        let (surge_service, clash_service) = match keep_profiles {
+            true => (
+                SurgeService {
+                    config: config.clone(),
+                    history: profile_history.clone(),
+                    ..self.surge_service.clone()
+                },
+                ClashService {
+                    config: config.clone(),
+                    history: profile_history.clone(),
+                    ..self.clash_service.clone()
+                },
+            ),
+            false => (
+                SurgeService::new(config.clone(), profile_history.clone(), node_health.clone()),
+                ClashService::new(config.clone(), profile_history.clone(), node_health.clone()),
+            ),
+        };
+        Ok(Self {
+            config,
+            redis: self.redis.clone(),
+            redis_connection,
+            provider,
+            sub_logs,
+            users,
+            short_links: self.short_links.clone(),
+            rate_limiter,
+            profile_history,
+            geoip,
+            node_health,
+            surge_service,
+            clash_service,
+        })
+    }
+}
+
+fn open_geoip(config: &Config) -> Option<Arc<GeoIp>> {
+    config.geoip.as_ref().and_then(|path| match GeoIp::open(path) {
+        Ok(geoip) => Some(Arc::new(geoip)),
+        Err(e) => {
+            error!("无法加载 GeoIP 数据库 {}: {e}", path.display());
+            None
         }
+    })
+}
+
+impl AppState {
+    /// 获取订阅商原始配置, `refresh` 为 true 时跳过缓存
+    /// 已转换的配置以订阅内容的哈希为键, 内容变化后自然失效, 无需在此清理
+    pub async fn get_raw_profile(&self, url_builder: &UrlBuilder, header_map: HeaderMap, refresh: bool) -> Result<SubsResponse, ProviderError> {
+        let sub_url = url_builder.build_raw_url();
+        let user_agent = header_map
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let headers = Headers::from_header_map(header_map).patch(&self.config.subscription.headers);
+        if !refresh {
+            return self.provider.get_raw_profile(sub_url.into(), headers, user_agent).await;
+        }
+        self.provider.refresh_raw_profile(sub_url.into(), headers, user_agent).await
+    }
+
+    pub async fn cache_overview(&self) -> CacheOverview {
+        let subscriptions = self.provider.cache_entries().await;
+        let mut profiles = self.surge_service.cache_entries();
+        profiles.extend(self.clash_service.cache_entries());
+        CacheOverview { subscriptions, profiles }
+    }
+
+    /// 清理缓存并通知共享 Redis 的其他实例, 返回删除的持久化缓存数量
+    pub async fn purge_cache(&self, purge: &CachePurge) -> Result<usize, AppError> {
+        let removed = self.provider.purge_cache(purge).await?;
+        self.surge_service.invalidate(purge);
+        self.clash_service.invalidate(purge);
+        if let Some(mut redis) = self.redis_connection.clone() {
+            let message = serde_json::to_string(purge).expect("CachePurge 序列化失败");
+            redis.publish(CACHE_INVALIDATION_CHANNEL, message).await?;
+        }
+        Ok(removed)
+    }
+
+    /// 仅清理当前实例内存中的缓存
+    pub fn invalidate_local_cache(&self, purge: &CachePurge) {
+        self.provider.invalidate_cache(purge);
+        self.surge_service.invalidate(purge);
+        self.clash_service.invalidate(purge);
+    }
+
+    /// 订阅其他实例广播的缓存失效消息, `stop` 取消后停止监听
+    pub async fn subscribe_cache_invalidation(&self, stop: CancellationToken) -> Result<(), RedisError> {
+        let Some(redis) = self.redis.as_ref() else {
+            return Ok(());
+        };
+        let mut pubsub = redis.get_async_pubsub().await?;
+        pubsub.subscribe(CACHE_INVALIDATION_CHANNEL).await?;
+        let state = self.clone();
+        tokio::spawn(async move {
+            info!("开始监听缓存失效消息: {CACHE_INVALIDATION_CHANNEL}");
+            let mut messages = pubsub.into_on_message();
+            while let Some(message) = stop.run_until_cancelled(messages.next()).await.flatten() {
+                let purge = message
+                    .get_payload::<String>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|payload| serde_json::from_str::<CachePurge>(&payload).map_err(|e| e.to_string()));
+                match purge {
+                    Ok(purge) => state.invalidate_local_cache(&purge),
+                    Err(e) => warn!("忽略无法解析的缓存失效消息: {e}"),
+                }
+            }
+            if !stop.is_cancelled() {
+                error!("缓存失效消息监听已断开");
+            }
+        });
+        Ok(())
     }
 }
@@ -0,0 +1,325 @@
+use crate::server::response::{ApiError, RequestError};
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderValue, Request, Uri, header};
+use axum::response::{IntoResponse, Response};
+use convertor::common::encrypt::decrypt_with_any;
+use convertor::config::Config;
+use convertor::config::rate_limit_config::{RateLimitConfig, TokenBucketConfig};
+use convertor::provider::subs_response::content_hash;
+use futures_util::future::BoxFuture;
+use moka::future::Cache as MokaCache;
+use redis::{RedisError, Script};
+use redis::aio::ConnectionManager;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tower::{Layer, Service};
+use tracing::warn;
+
+const RATE_LIMIT_KEY: &str = "rate_limit";
+
+// 令牌桶的原子实现, 多个实例共享同一个桶
+// 所有桶都有令牌时才同时各消耗一个, 被任一桶拒绝的请求不消耗其他桶的令牌
+// KEYS: 各个令牌桶
+// ARGV: 当前时间(毫秒), 之后依次为每个桶的容量与每毫秒补充的令牌数
+// 返回: {拒绝请求的桶的序号(从 1 开始, 0 表示放行), 需要等待的毫秒数}
+// 通过 EVALSHA 调用, 脚本不在 Redis 中时自动加载
+static TOKEN_BUCKET_SCRIPT: LazyLock<Script> = LazyLock::new(|| Script::new(TOKEN_BUCKET_LUA));
+
+const TOKEN_BUCKET_LUA: &str = r#"
+local now = tonumber(ARGV[1])
+local buckets = {}
+local limited = 0
+local retry_after = 0
+for i, key in ipairs(KEYS) do
+    local capacity = tonumber(ARGV[i * 2])
+    local refill_per_ms = tonumber(ARGV[i * 2 + 1])
+    local state = redis.call('HMGET', key, 'tokens', 'updated')
+    local tokens = tonumber(state[1]) or capacity
+    local updated = tonumber(state[2]) or now
+    tokens = math.min(capacity, tokens + math.max(0, now - updated) * refill_per_ms)
+    if limited == 0 and tokens < 1 then
+        limited = i
+        retry_after = math.ceil((1 - tokens) / refill_per_ms)
+    end
+    buckets[i] = {tokens, math.ceil(capacity / refill_per_ms) + 1000}
+end
+for i, key in ipairs(KEYS) do
+    local tokens = buckets[i][1]
+    if limited == 0 then
+        tokens = tokens - 1
+    end
+    redis.call('HSET', key, 'tokens', tostring(tokens), 'updated', now)
+    redis.call('PEXPIRE', key, buckets[i][2])
+end
+return {limited, retry_after}
+"#;
+
+/// 按客户端 IP 与订阅限流, 配置了 Redis 时由所有实例共享令牌桶
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    // 解密 `sub_url` 以识别同一个订阅, 密文每次生成都不同
+    secrets: Arc<Vec<String>>,
+    backend: RateLimitBackend,
+    key: String,
+}
+
+#[derive(Clone)]
+enum RateLimitBackend {
+    Redis(ConnectionManager),
+    Memory(MokaCache<String, Arc<Mutex<TokenBucket>>>),
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 被限流的维度与需要等待的时间
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RateLimited {
+    pub scope: &'static str,
+    pub retry_after: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config, redis: Option<ConnectionManager>, prefix: impl AsRef<str>) -> Self {
+        let backend = match redis {
+            Some(redis) => RateLimitBackend::Redis(redis),
+            None => RateLimitBackend::Memory(MokaCache::builder().time_to_idle(Duration::from_secs(60 * 60)).build()),
+        };
+        let secrets = std::iter::once(config.secret.clone())
+            .chain(config.previous_secrets.iter().cloned())
+            .collect();
+        Self {
+            config: Arc::new(config.rate_limit.clone()),
+            secrets: Arc::new(secrets),
+            backend,
+            key: format!("{RATE_LIMIT_KEY}:{}", prefix.as_ref()),
+        }
+    }
+
+    /// 沿用已有的令牌桶, 按新配置调整限流参数与密钥
+    pub fn reconfigure(&self, config: &Config) -> Self {
+        let secrets = std::iter::once(config.secret.clone())
+            .chain(config.previous_secrets.iter().cloned())
+            .collect();
+        Self {
+            config: Arc::new(config.rate_limit.clone()),
+            secrets: Arc::new(secrets),
+            ..self.clone()
+        }
+    }
+
+    /// 请求需要检查的令牌桶: (维度, 桶的标识, 桶的配置)
+    pub fn buckets<B>(&self, request: &Request<B>) -> Vec<(&'static str, String, TokenBucketConfig)> {
+        if !self.config.enabled {
+            return vec![];
+        }
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ci| ci.0.ip());
+        [
+            ("ip", self.client_ip(peer, request.headers()).map(|ip| ip.to_string()), self.config.per_ip),
+            ("subscription", self.subscription_key(request.uri()), self.config.per_subscription),
+        ]
+        .into_iter()
+        .filter_map(|(scope, id, bucket)| id.map(|id| (scope, id, bucket)))
+        .collect()
+    }
+
+    /// 所有令牌桶都有令牌时才放行并各消耗一个, 否则返回第一个被限流的维度
+    pub async fn check(&self, buckets: Vec<(&'static str, String, TokenBucketConfig)>) -> Option<RateLimited> {
+        // 每分钟补充 0 个令牌视为不限流
+        let buckets = buckets
+            .into_iter()
+            .filter(|(_, _, bucket)| bucket.per_minute != 0)
+            .map(|(scope, id, bucket)| (scope, format!("{}:{scope}:{id}", self.key), bucket))
+            .collect::<Vec<_>>();
+        if buckets.is_empty() {
+            return None;
+        }
+        match self.take(&buckets).await {
+            Ok(None) => None,
+            Ok(Some((index, retry_after))) => {
+                let scope = buckets[index].0;
+                metrics::counter!("convd_rate_limited_total", "scope" => scope).increment(1);
+                Some(RateLimited { scope, retry_after })
+            }
+            Err(e) => {
+                // Redis 不可用时放行, 避免限流拖垮整个服务
+                for (scope, _, _) in &buckets {
+                    metrics::counter!("convd_rate_limit_errors_total", "scope" => *scope).increment(1);
+                }
+                warn!("限流检查失败, 放行请求: {e}");
+                None
+            }
+        }
+    }
+
+    /// 只有直连地址是可信代理时才采用 `X-Forwarded-For`, 从右往左取第一个不可信的地址
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let trusted = &self.config.trusted_proxies;
+        let peer = peer?;
+        if !trusted.contains(&peer) {
+            return Some(peer);
+        }
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        Some(
+            forwarded
+                .iter()
+                .rev()
+                .find(|ip| !trusted.contains(ip))
+                .or(forwarded.first())
+                .copied()
+                .unwrap_or(peer),
+        )
+    }
+
+    /// 短链接按 id 区分, 其他接口按解密后的 `sub_url` 区分
+    pub fn subscription_key(&self, uri: &Uri) -> Option<String> {
+        if let Some(id) = uri.path().strip_prefix("/p/") {
+            return Some(format!("p:{id}"));
+        }
+        let enc_sub_url = url::form_urlencoded::parse(uri.query()?.as_bytes())
+            .find(|(k, _)| k == "sub_url")
+            .map(|(_, v)| v.into_owned())?;
+        let sub_url = decrypt_with_any(&self.secrets, &enc_sub_url).ok()?;
+        Some(content_hash(&sub_url))
+    }
+
+    /// 原子地检查并消耗所有令牌桶, 被限流时返回拒绝请求的桶的下标与需要等待的时间
+    async fn take(
+        &self,
+        buckets: &[(&'static str, String, TokenBucketConfig)],
+    ) -> Result<Option<(usize, Duration)>, RedisError> {
+        let refill_per_ms = |bucket: &TokenBucketConfig| bucket.per_minute as f64 / 60_000.0;
+        match &self.backend {
+            RateLimitBackend::Redis(redis) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+                let mut invocation = TOKEN_BUCKET_SCRIPT.prepare_invoke();
+                invocation.arg(now);
+                for (_, key, bucket) in buckets {
+                    invocation.key(key).arg(bucket.burst).arg(refill_per_ms(bucket));
+                }
+                let (limited, retry_after): (usize, u64) = invocation.invoke_async(&mut redis.clone()).await?;
+                Ok((limited > 0).then(|| (limited - 1, Duration::from_millis(retry_after))))
+            }
+            RateLimitBackend::Memory(states) => {
+                let mut entries = vec![];
+                for (_, key, bucket) in buckets {
+                    let state = states
+                        .get_with(key.clone(), async {
+                            Arc::new(Mutex::new(TokenBucket {
+                                tokens: bucket.burst as f64,
+                                updated: Instant::now(),
+                            }))
+                        })
+                        .await;
+                    entries.push(state);
+                }
+                // 按固定的维度顺序加锁, 检查全部通过后才消耗令牌
+                let mut guards = entries
+                    .iter()
+                    .map(|state| state.lock().unwrap_or_else(|e| e.into_inner()))
+                    .collect::<Vec<_>>();
+                let now = Instant::now();
+                for (state, (_, _, bucket)) in guards.iter_mut().zip(buckets) {
+                    let elapsed = now.duration_since(state.updated).as_millis() as f64;
+                    state.tokens = (state.tokens + elapsed * refill_per_ms(bucket)).min(bucket.burst as f64);
+                    state.updated = now;
+                }
+                let limited = guards.iter().zip(buckets).enumerate().find(|(_, (state, _))| state.tokens < 1.0);
+                if let Some((index, (state, (_, _, bucket)))) = limited {
+                    let retry_after = ((1.0 - state.tokens) / refill_per_ms(bucket)).ceil() as u64;
+                    return Ok(Some((index, Duration::from_millis(retry_after))));
+                }
+                for state in guards.iter_mut() {
+                    state.tokens -= 1.0;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// 限流层, 未开启限流时直接放行
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: RateLimiter,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: RateLimiter,
+}
+
+impl<S> Service<Request<Body>> for RateLimitService<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // 取走已就绪的服务, 留下克隆的服务等待下一次 poll_ready
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+        let buckets = limiter.buckets(&request);
+        Box::pin(async move {
+            match limiter.check(buckets).await {
+                Some(limited) => Ok(too_many_requests(limited)),
+                None => inner.call(request).await,
+            }
+        })
+    }
+}
+
+fn too_many_requests(limited: RateLimited) -> Response {
+    let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let error = ApiError::too_many_requests(RequestError::RateLimited {
+        scope: limited.scope,
+        retry_after,
+    });
+    let mut response = error.into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
@@ -14,10 +14,10 @@ use uuid::Uuid;
 pub fn convd_trace_layer()
 -> TraceLayer<HttpMakeClassifier, ConvdMakeSpan, HttpOnRequest, HttpOnResponse, DefaultOnBodyChunk, DefaultOnEos, HttpOnFailure> {
     TraceLayer::new_for_http()
-        .make_span_with(ConvdMakeSpan::default())
-        .on_request(HttpOnRequest::default())
-        .on_response(HttpOnResponse::default())
-        .on_failure(HttpOnFailure::default())
+        .make_span_with(ConvdMakeSpan)
+        .on_request(HttpOnRequest)
+        .on_response(HttpOnResponse)
+        .on_failure(HttpOnFailure)
 }
 
 // 让 HeaderMap 变成 OTel 的 Extractor
@@ -100,7 +100,7 @@ impl<B> MakeSpan<B> for ConvdMakeSpan {
         let cx = global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(request.headers())));
         let parent_ctx = cx.span().span_context().clone();
         if parent_ctx.is_valid() {
-            span.record("parent_span_id", &field::display(parent_ctx.span_id()));
+            span.record("parent_span_id", field::display(parent_ctx.span_id()));
         }
         if let Err(e) = span.set_parent(cx) {
             tracing::warn!("Failed to extract trace context: {}", e);
@@ -141,12 +141,11 @@ impl<B> tower_http::trace::OnResponse<B> for HttpOnResponse {
         span.record("latency_ms", latency_ms);
 
         // 尝试获取响应体大小
-        if let Some(content_length) = response.headers().get("content-length") {
-            if let Ok(length_str) = content_length.to_str() {
-                if let Ok(length) = length_str.parse::<u64>() {
-                    span.record("bytes_sent", length);
-                }
-            }
+        if let Some(content_length) = response.headers().get("content-length")
+            && let Ok(length_str) = content_length.to_str()
+            && let Ok(length) = length_str.parse::<u64>()
+        {
+            span.record("bytes_sent", length);
         }
 
         // 根据状态码、路径和延迟决定日志级别和内容
@@ -289,7 +288,7 @@ fn record_trace_ids(span: &Span) {
     let ctx = span.context();
     let span_ctx = ctx.span().span_context().clone();
     if span_ctx.is_valid() {
-        span.record("trace_id", &field::display(span_ctx.trace_id()));
-        span.record("span_id", &field::display(span_ctx.span_id()));
+        span.record("trace_id", field::display(span_ctx.trace_id()));
+        span.record("span_id", field::display(span_ctx.span_id()));
     }
 }
@@ -0,0 +1,33 @@
+use std::fmt::{Display, Formatter};
+use std::net::{AddrParseError, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const UNIX_PREFIX: &str = "unix:";
+
+/// convd 的监听地址, 支持 IPv4/IPv6 以及 `unix:/path/to/convd.sock` 形式的 Unix 套接字
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_PREFIX) {
+            Some(path) => Ok(ListenAddr::Unix(PathBuf::from(path))),
+            None => Ok(ListenAddr::Tcp(s.parse()?)),
+        }
+    }
+}
+
+impl Display for ListenAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{addr}"),
+            ListenAddr::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::ext::WatchDebounceExt;
+use crate::server::app_state::AppState;
+use crate::server::response::AppError;
+use crate::server::router;
+use axum::Router;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::Response;
+use convertor::common::cache::CONFIG_RELOAD_CHANNEL;
+use convertor::config::Config;
+use convertor::config::config_error::ConfigError;
+use futures_util::StreamExt;
+use redis::RedisError;
+use redis::aio::{ConnectionManager, PubSub};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tower::Service;
+use tracing::{debug, error, info, warn};
+
+/// 检查配置文件是否变化的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// 连续变化时等待静默的时长, 避免编辑器分多次写入时反复重载
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// 重载频道断开后重新连接的初始等待时长, 每次失败后翻倍
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// 重新连接的最长等待时长
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// 配置的来源, 热重载时按启动时相同的方式重新搜索配置
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub cwd: PathBuf,
+    pub config_path: Option<PathBuf>,
+}
+
+/// 触发重载的原因
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum ReloadRequest {
+    /// 配置文件变化或收到空消息, 重新搜索配置文件
+    Search,
+    /// 通过 Redis 下发的完整配置
+    Inline(String),
+}
+
+/// 持有当前生效的 `AppState`, 新配置校验通过后原子替换
+///
+/// 正在处理的请求继续使用旧的路由直至结束, 监听地址不会中断
+pub struct Reloader {
+    source: ConfigSource,
+    redis: Option<redis::Client>,
+    state: AppState,
+    // 当前这一代 AppState 的后台任务, 被替换后取消
+    generation: CancellationToken,
+    router: watch::Sender<Router>,
+    // 已处理的重载请求数, 无论新配置是否生效
+    processed: watch::Sender<u64>,
+}
+
+/// 每个请求都转发给当前生效的路由
+#[derive(Clone)]
+struct ReloadableRouter {
+    current: watch::Receiver<Router>,
+}
+
+impl ConfigSource {
+    pub fn new(cwd: impl Into<PathBuf>, config_path: Option<PathBuf>) -> Self {
+        Self {
+            cwd: cwd.into(),
+            config_path,
+        }
+    }
+
+    pub fn load(&self) -> Result<Config, ConfigError> {
+        Config::search(&self.cwd, self.config_path.as_ref())
+    }
+
+    /// 参与合并的配置文件及其修改时间, 新增或删除文件同样视为变化
+    fn fingerprint(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        Config::search_files(&self.cwd, self.config_path.as_ref())
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+}
+
+impl Reloader {
+    pub async fn new(
+        config: Config,
+        source: ConfigSource,
+        redis: Option<redis::Client>,
+        redis_connection: Option<ConnectionManager>,
+    ) -> Result<Self, AppError> {
+        config.validate()?;
+        let generation = CancellationToken::new();
+        let state = AppState::new(config, redis.clone(), redis_connection)?;
+        spawn_background(&state, &generation).await;
+        let (router, _) = watch::channel(router::router(state.clone()));
+        Ok(Self {
+            source,
+            redis,
+            state,
+            generation,
+            router,
+            processed: watch::Sender::new(0),
+        })
+    }
+
+    /// 对外提供服务的路由, 始终转发给最新的配置
+    pub fn router(&self) -> Router {
+        Router::new().fallback_service(ReloadableRouter {
+            current: self.router.subscribe(),
+        })
+    }
+
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// 每处理完一次重载请求计数加一, 供调用方等待重载完成
+    pub fn processed(&self) -> watch::Receiver<u64> {
+        self.processed.subscribe()
+    }
+
+    /// 校验并应用新配置, 返回 false 表示配置没有变化
+    pub async fn reload(&mut self, config: Config) -> Result<bool, AppError> {
+        config.validate()?;
+        if config.to_string() == self.state.config.to_string() {
+            return Ok(false);
+        }
+        if config.redis != self.state.config.redis {
+            warn!("Redis 配置的变化需要重启服务才能生效");
+        }
+        if config.tls != self.state.config.tls {
+            warn!("TLS 配置的变化需要重启服务才能生效");
+        }
+        let generation = CancellationToken::new();
+        let state = self.state.reload(config)?;
+        spawn_background(&state, &generation).await;
+        // 旧路由仍被处理中的请求持有, 这些请求结束后随之释放
+        let _ = self.router.send_replace(router::router(state.clone()));
+        self.state = state;
+        std::mem::replace(&mut self.generation, generation).cancel();
+        Ok(true)
+    }
+
+    /// 监听配置文件与 Redis 频道, 去抖后重载, 直到 `stop` 被取消
+    pub async fn run(mut self, stop: CancellationToken) {
+        let (tx, mut rx) = watch::channel(ReloadRequest::Search);
+        tokio::spawn(watch_files(self.source.clone(), tx.clone(), stop.clone()));
+        if let Some(redis) = self.redis.clone() {
+            if let Err(e) = subscribe_reload(redis, tx, stop.clone()).await {
+                warn!("无法订阅配置重载消息, 仅监听配置文件变化: {e}");
+            }
+        } else {
+            drop(tx);
+        }
+
+        loop {
+            let request = tokio::select! {
+                _ = stop.cancelled() => break,
+                request = rx.recv_debounced(DEBOUNCE) => match request {
+                    Ok(request) => request.clone(),
+                    Err(_) => break,
+                },
+            };
+            let config = match request {
+                ReloadRequest::Search => self.source.load(),
+                ReloadRequest::Inline(raw) => raw.parse::<Config>(),
+            };
+            let result = match config {
+                Ok(config) => self.reload(config).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok(true) => info!("配置已重新加载"),
+                Ok(false) => debug!("配置没有变化, 跳过重载"),
+                Err(e) => error!("新配置不可用, 继续使用当前配置: {e}"),
+            }
+            self.processed.send_modify(|n| *n += 1);
+        }
+        self.generation.cancel();
+    }
+}
+
+impl Service<Request<Body>> for ReloadableRouter {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = <Router as Service<Request<Body>>>::Future;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let mut router = self.current.borrow().clone();
+        router.call(request)
+    }
+}
+
+/// 启动随这一代 `AppState` 运行的后台任务, `generation` 取消后停止
+async fn spawn_background(state: &AppState, generation: &CancellationToken) {
+    if let Err(e) = state.subscribe_cache_invalidation(generation.clone()).await {
+        warn!("无法订阅缓存失效消息, 多实例间的缓存清理将不会同步: {e}");
+    }
+    state.node_health.spawn_prober(generation.clone());
+}
+
+async fn watch_files(source: ConfigSource, tx: watch::Sender<ReloadRequest>, stop: CancellationToken) {
+    let mut last = source.fingerprint();
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    while stop.run_until_cancelled(interval.tick()).await.is_some() {
+        let current = source.fingerprint();
+        if current != last {
+            debug!("配置文件发生变化: {current:?}");
+            last = current;
+            tx.send_replace(ReloadRequest::Search);
+        }
+    }
+}
+
+async fn subscribe_reload(
+    redis: redis::Client,
+    tx: watch::Sender<ReloadRequest>,
+    stop: CancellationToken,
+) -> Result<(), RedisError> {
+    let mut pubsub = connect_reload_channel(&redis).await?;
+    tokio::spawn(async move {
+        info!("开始监听配置重载消息: {CONFIG_RELOAD_CHANNEL}");
+        loop {
+            let mut messages = pubsub.into_on_message();
+            while let Some(message) = stop.run_until_cancelled(messages.next()).await.flatten() {
+                match message.get_payload::<String>() {
+                    Ok(payload) if payload.trim().is_empty() => tx.send_replace(ReloadRequest::Search),
+                    Ok(payload) => tx.send_replace(ReloadRequest::Inline(payload)),
+                    Err(e) => {
+                        warn!("忽略无法解析的配置重载消息: {e}");
+                        continue;
+                    }
+                };
+            }
+            if stop.is_cancelled() {
+                return;
+            }
+            // Redis 重启等原因断开后按指数退避重新订阅, 避免热重载静默失效
+            error!("配置重载消息监听已断开, 尝试重新连接");
+            let mut backoff = RECONNECT_INITIAL_BACKOFF;
+            pubsub = loop {
+                if stop.run_until_cancelled(tokio::time::sleep(backoff)).await.is_none() {
+                    return;
+                }
+                match connect_reload_channel(&redis).await {
+                    Ok(pubsub) => break pubsub,
+                    Err(e) => {
+                        backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+                        warn!("重新订阅配置重载消息失败, {}s 后重试: {e}", backoff.as_secs());
+                    }
+                }
+            };
+            info!("已重新订阅配置重载消息: {CONFIG_RELOAD_CHANNEL}");
+        }
+    });
+    Ok(())
+}
+
+async fn connect_reload_channel(redis: &redis::Client) -> Result<PubSub, RedisError> {
+    let mut pubsub = redis.get_async_pubsub().await?;
+    pubsub.subscribe(CONFIG_RELOAD_CHANNEL).await?;
+    Ok(pubsub)
+}
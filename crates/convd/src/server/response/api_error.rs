@@ -1,5 +1,6 @@
 use crate::server::response::{ApiResponse, AppError, RequestSnapshot};
 use axum::response::{IntoResponse, Response};
+use convertor::error::AuthError;
 use tokio_util::bytes::{BufMut, Bytes, BytesMut};
 
 #[derive(Debug)]
@@ -18,6 +19,48 @@ impl ApiError {
         }
     }
 
+    pub fn unauthorized(error: impl Into<AppError>) -> Self {
+        Self {
+            status: axum::http::StatusCode::UNAUTHORIZED,
+            error: error.into(),
+            request: None,
+        }
+    }
+
+    pub fn forbidden(error: impl Into<AppError>) -> Self {
+        Self {
+            status: axum::http::StatusCode::FORBIDDEN,
+            error: error.into(),
+            request: None,
+        }
+    }
+
+    pub fn not_found(error: impl Into<AppError>) -> Self {
+        Self {
+            status: axum::http::StatusCode::NOT_FOUND,
+            error: error.into(),
+            request: None,
+        }
+    }
+
+    pub fn too_many_requests(error: impl Into<AppError>) -> Self {
+        Self {
+            status: axum::http::StatusCode::TOO_MANY_REQUESTS,
+            error: error.into(),
+            request: None,
+        }
+    }
+
+    /// 按鉴权错误的类型选择状态码
+    pub fn auth(error: AuthError) -> Self {
+        match error {
+            AuthError::MissingToken | AuthError::InvalidToken | AuthError::RevokedToken => Self::unauthorized(error),
+            AuthError::SubscriptionNotAllowed { .. } => Self::forbidden(error),
+            AuthError::UserNotFound(_) => Self::not_found(error),
+            AuthError::Random(_) | AuthError::Store(_) => Self::internal_server_error(error),
+        }
+    }
+
     pub fn internal_server_error(error: impl Into<AppError>) -> Self {
         Self {
             status: axum::http::StatusCode::INTERNAL_SERVER_ERROR,
@@ -1,7 +1,10 @@
 use crate::server::response::ApiResponse;
 use axum::http::header::ToStrError;
 use convertor::config::proxy_client::ProxyClient;
-use convertor::error::{ParseError, ProviderError, QueryError, RenderError, UrlBuilderError};
+use convertor::config::config_error::ConfigError;
+use convertor::error::{
+    AuthError, CacheStoreError, ParseError, ProviderError, QueryError, RenderError, UpstreamProxyError, UrlBuilderError,
+};
 use redis::RedisError;
 use std::sync::Arc;
 use thiserror::Error;
@@ -83,8 +86,20 @@ define_error! {
         #[error("Redis 错误: {0:?}")]
         RedisError(#[from] RedisError),
 
+        #[error(transparent)]
+        CacheStoreError(#[from] CacheStoreError),
+
+        #[error(transparent)]
+        AuthError(#[from] AuthError),
+
         #[error(transparent)]
         JsonError(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        ConfigError(#[from] ConfigError),
+
+        #[error(transparent)]
+        UpstreamProxyError(#[from] UpstreamProxyError),
     }
 }
 
@@ -160,4 +175,19 @@ pub enum RequestError {
 
     #[error("请求失败, 未找到有效的 secret 令牌: {0}")]
     Unauthorized(String),
+
+    #[error("清理缓存需要指定 sub_url 或 client, 清理全部缓存请使用 all=true")]
+    EmptyPurgeScope,
+
+    #[error("短链接不存在或已过期: {0}")]
+    ShortLinkNotFound(String),
+
+    #[error("规则匹配需要至少提供 domain, ip, port, process, user_agent 中的一项")]
+    EmptyMatchQuery,
+
+    #[error("订阅结果中没有该链接: {0}")]
+    UrlEntryNotFound(String),
+
+    #[error("请求过于频繁, 触发 {scope} 限流, 请在 {retry_after} 秒后重试")]
+    RateLimited { scope: &'static str, retry_after: u64 },
 }
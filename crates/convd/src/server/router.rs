@@ -4,22 +4,44 @@ pub mod api;
 pub mod profile;
 
 use crate::server::AppState;
+use crate::server::layer::rate_limit::RateLimitLayer;
 use crate::server::layer::trace::convd_trace_layer;
-use crate::server::response::{ApiError, AppError, RequestSnapshot};
+use crate::server::response::{ApiError, AppError, RequestError, RequestSnapshot};
 use axum::Router;
 use axum::extract::FromRequestParts;
+use axum::http::header::AUTHORIZATION;
 use axum::http::request::Parts;
 use axum::response::Redirect;
-use axum::routing::get;
+use axum::routing::{delete, get, post, put};
 use axum_extra::extract::{Host, Scheme};
 use axum_prometheus::PrometheusMetricLayer;
+use axum_prometheus::metrics_exporter_prometheus::PrometheusHandle;
+use convertor::common::encrypt::{decrypt_with_any, secret_eq};
 use convertor::error::QueryError;
 use convertor::url::query::ConvertorQuery;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use url::Url;
 
+/// 全局的 prometheus recorder 只能安装一次, 热重载重建路由时复用同一份
+fn prometheus() -> &'static (PrometheusMetricLayer<'static>, PrometheusHandle) {
+    static PROMETHEUS: OnceLock<(PrometheusMetricLayer<'static>, PrometheusHandle)> = OnceLock::new();
+    PROMETHEUS.get_or_init(PrometheusMetricLayer::pair)
+}
+
 pub fn router(app_state: AppState) -> Router {
-    let (prome_layer, prome_handle) = PrometheusMetricLayer::pair();
+    let (prome_layer, prome_handle) = prometheus().clone();
+    // 会访问订阅商的公开接口, 需要限流
+    let public = Router::new()
+        .route("/raw-profile/{client}", get(profile::raw_profile))
+        .route("/profile/{client}", get(profile::profile))
+        .route("/rule-provider/{client}", get(profile::rule_provider))
+        .route("/p/{id}", get(profile::short_link))
+        .route("/api/subscription/{client}", get(api::subscription::subscription))
+        .route("/api/subscription/{client}/diff", get(api::subscription::diff))
+        .route("/api/subscription/{client}/qr", get(api::subscription::qr))
+        .route("/api/subscription/{client}/short-link", post(api::short_link::create))
+        .route("/api/match/{client}", get(api::rule_match::evaluate))
+        .route_layer(RateLimitLayer::new(app_state.rate_limiter.clone()));
     Router::new()
         .route("/", get(|| async { Redirect::permanent("/dashboard/") }))
         .route("/dashboard", get(|| async { Redirect::permanent("/dashboard/") }))
@@ -28,10 +50,15 @@ pub fn router(app_state: AppState) -> Router {
         .route("/actuator/ready", get(actuator::redis))
         .route("/actuator/redis", get(actuator::redis))
         .route("/actuator/metrics", get(|| async move { prome_handle.render() }))
-        .route("/raw-profile/{client}", get(profile::raw_profile))
-        .route("/profile/{client}", get(profile::profile))
-        .route("/rule-provider/{client}", get(profile::rule_provider))
-        .route("/api/subscription/{client}", get(api::subscription::subscription))
+        .merge(public)
+        .route("/api/cache", get(api::cache::list).delete(api::cache::purge))
+        .route("/api/sub-logs", get(api::sub_logs::list))
+        .route("/api/nodes", get(api::nodes::list))
+        .route("/api/short-links/{id}", delete(api::short_link::remove))
+        .route("/api/users", get(api::users::list))
+        .route("/api/users/{name}", put(api::users::upsert).delete(api::users::remove))
+        .route("/api/users/{name}/tokens", post(api::users::issue_token))
+        .route("/api/users/{name}/tokens/{id}", delete(api::users::revoke_token))
         .route("/api/health", get(|| async { Ok::<_, ApiError>(()) }))
         .nest("/dashboard/", angular::router())
         .with_state(Arc::new(app_state))
@@ -88,8 +115,20 @@ impl FromRequestParts<Arc<AppState>> for ConvertorQueryExtractor {
                 .ok_or(QueryError::EmptyQuery)
                 .map_err(AppError::QueryError)
                 .map_err(ApiError::bad_request)?;
-            let query =
-                ConvertorQuery::parse_from_query_string(query_string, &state.config.secret, server).map_err(ApiError::bad_request)?;
+            let query = ConvertorQuery::parse_from_query_string(
+                query_string,
+                &state.config.secret,
+                &state.config.previous_secrets,
+                server,
+            )
+            .map_err(ApiError::bad_request)?;
+            if state.config.auth.enabled {
+                state
+                    .users
+                    .authenticate(query.token.as_deref(), &query.sub_url)
+                    .await
+                    .map_err(ApiError::auth)?;
+            }
             Ok(ConvertorQueryExtractor(query))
         }
         .await;
@@ -99,3 +138,39 @@ impl FromRequestParts<Arc<AppState>> for ConvertorQueryExtractor {
         }
     }
 }
+
+/// 管理接口鉴权, 支持 `Authorization: Bearer <secret>` 或加密后的 `secret` 查询参数
+pub struct AdminAuth;
+
+impl FromRequestParts<Arc<AppState>> for AdminAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let bearer = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|v| v.trim().to_string());
+        // 轮换前生成的管理链接携带由旧密钥加密的旧密钥, 在旧密钥移除前仍然有效
+        let secrets = std::iter::once(&state.config.secret)
+            .chain(&state.config.previous_secrets)
+            .collect::<Vec<_>>();
+        let query_secret = parts
+            .uri
+            .query()
+            .and_then(|q| url::form_urlencoded::parse(q.as_bytes()).find(|(k, _)| k == "secret"))
+            .and_then(|(_, enc_secret)| decrypt_with_any(&secrets, &enc_secret).ok());
+        let authorized = match (bearer, query_secret) {
+            (Some(bearer), _) => secret_eq(&bearer, &state.config.secret),
+            (None, Some(secret)) => secrets.iter().fold(false, |ok, s| secret_eq(&secret, s) | ok),
+            (None, None) => false,
+        };
+        if !authorized {
+            return Err(ApiError::unauthorized(AppError::RequestError(RequestError::Unauthorized(
+                "无效的管理密钥".to_string(),
+            ))));
+        }
+        Ok(AdminAuth)
+    }
+}
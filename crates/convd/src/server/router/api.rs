@@ -1,23 +1,39 @@
 pub mod subscription {
     use crate::server::app_state::AppState;
-    use crate::server::response::{ApiError, ApiResponse, AppError, RequestSnapshot};
-    use crate::server::router::{ConvertorQueryExtractor, OptionalScheme};
+    use crate::server::response::{ApiError, ApiResponse, RequestError, RequestSnapshot};
+    use crate::server::router::{AdminAuth, ConvertorQueryExtractor, OptionalScheme};
     use axum::body::Body;
-    use axum::extract::{Path, State};
-    use axum::http::Request;
+    use axum::extract::{Path, Query, State};
+    use axum::http::{Request, header};
+    use axum::response::{IntoResponse, Response};
     use axum_extra::extract::Host;
     use axum_extra::headers::HeaderMap;
     use convertor::config::proxy_client::ProxyClient;
-    use convertor::config::subscription_config::Headers;
+    use convertor::core::diff::ProfileDiff;
+    use convertor::error::UrlBuilderError;
+    use convertor::url::qr_code::{QrCodeRenderer, QrImageFormat};
     use convertor::url::query::ConvertorQuery;
     use convertor::url::url_builder::UrlBuilder;
-    use convertor::url::url_result::UrlResult;
+    use convertor::url::url_result::{UrlEntry, UrlResult};
+    use serde::Deserialize;
     use std::sync::Arc;
 
+    #[derive(Debug, Deserialize)]
+    pub struct QrQuery {
+        pub entry: UrlEntry,
+        /// entry 为 rule_provider 时选择第几个规则集
+        #[serde(default)]
+        pub index: usize,
+        #[serde(default)]
+        pub format: QrImageFormat,
+    }
+
+    #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip_all)]
     pub async fn subscription(
         Path(client): Path<ProxyClient>,
         ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+        admin: Result<AdminAuth, ApiError>,
         State(state): State<Arc<AppState>>,
         header_map: HeaderMap,
         Host(host): Host,
@@ -26,9 +42,9 @@ pub mod subscription {
     ) -> Result<ApiResponse<UrlResult>, ApiError> {
         let (parts, _) = request.into_parts();
         let request = RequestSnapshot::from_parts(scheme.unwrap_or("http".to_string()), host, parts);
-        let response = internal_subscription(client, query, state, header_map).await;
+        let response = internal_subscription(client, query, admin, state, header_map).await;
         match response {
-            Ok(response) => Ok(response.with_request(request)),
+            Ok(url_result) => Ok(ApiResponse::ok(url_result).with_request(request)),
             Err(err) => Err(err.with_request(request)),
         }
         // 调试用
@@ -39,18 +55,22 @@ pub mod subscription {
     async fn internal_subscription(
         client: ProxyClient,
         query: ConvertorQuery,
+        admin: Result<AdminAuth, ApiError>,
         state: Arc<AppState>,
         header_map: HeaderMap,
-    ) -> Result<ApiResponse<UrlResult>, ApiError> {
+    ) -> Result<UrlResult, ApiError> {
         let query = query.check_for_subscription().map_err(ApiError::bad_request)?;
+        let refresh = query.refresh;
+        // 与 profile 接口一致, 绕过缓存强制回源需要管理员鉴权
+        if refresh {
+            admin?;
+        }
         let url_builder = UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
-        let sub_url = url_builder.build_raw_url();
-        let headers = Headers::from_header_map(header_map).patch(&state.config.subscription.headers);
         let raw_profile = state
-            .provider
-            .get_raw_profile(sub_url.into(), headers)
+            .get_raw_profile(&url_builder, header_map, refresh)
             .await
             .map_err(ApiError::internal_server_error)?;
+        let userinfo = raw_profile.userinfo;
         let policies = match client {
             ProxyClient::Surge => {
                 let mut profile = state
@@ -74,15 +94,372 @@ pub mod subscription {
         let profile_url = url_builder.build_profile_url().map_err(ApiError::internal_server_error)?;
         let rule_providers_url = policies
             .iter()
-            .map(|policy| url_builder.build_rule_provider_url(policy).map_err(AppError::UrlBuilderError))
-            .collect::<Result<Vec<_>, AppError>>()
+            .map(|policy| url_builder.build_rule_provider_url(policy))
+            .collect::<Result<Vec<_>, UrlBuilderError>>()
             .map_err(ApiError::internal_server_error)?;
         let url_result = UrlResult {
             raw_url,
             raw_profile_url,
             profile_url,
             rule_providers_url,
+            userinfo,
+        };
+        Ok(url_result)
+    }
+
+    /// 将订阅结果中的某个链接渲染为 SVG 或 PNG 二维码, 供 dashboard 展示
+    #[tracing::instrument(skip_all)]
+    pub async fn qr(
+        Path(client): Path<ProxyClient>,
+        ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+        admin: Result<AdminAuth, ApiError>,
+        State(state): State<Arc<AppState>>,
+        Query(qr_query): Query<QrQuery>,
+        header_map: HeaderMap,
+    ) -> Result<Response, ApiError> {
+        let url_result = internal_subscription(client, query, admin, state, header_map).await?;
+        let url = url_result
+            .entry(qr_query.entry, qr_query.index)
+            .ok_or_else(|| {
+                ApiError::not_found(RequestError::UrlEntryNotFound(format!(
+                    "{:?}[{}]",
+                    qr_query.entry, qr_query.index
+                )))
+            })?;
+        let image = QrCodeRenderer::new(url.to_string())
+            .and_then(|renderer| renderer.render_image(qr_query.format))
+            .map_err(ApiError::internal_server_error)?;
+        Ok(([(header::CONTENT_TYPE, qr_query.format.content_type())], image).into_response())
+    }
+
+    /// 订阅最近一次变化的详情, 先获取一次订阅以记录最新内容
+    #[tracing::instrument(skip_all)]
+    pub async fn diff(
+        Path(client): Path<ProxyClient>,
+        ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+        admin: Result<AdminAuth, ApiError>,
+        State(state): State<Arc<AppState>>,
+        header_map: HeaderMap,
+    ) -> Result<ApiResponse<ProfileDiff>, ApiError> {
+        let query = query.check_for_subscription().map_err(ApiError::bad_request)?;
+        let refresh = query.refresh;
+        if refresh {
+            admin?;
+        }
+        let url_builder = UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
+        let raw_profile = state
+            .get_raw_profile(&url_builder, header_map, refresh)
+            .await
+            .map_err(ApiError::internal_server_error)?;
+        match client {
+            ProxyClient::Surge => {
+                state
+                    .surge_service
+                    .try_get_profile(url_builder.clone(), raw_profile)
+                    .await
+                    .map_err(ApiError::internal_server_error)?;
+            }
+            ProxyClient::Clash => {
+                state
+                    .clash_service
+                    .try_get_profile(url_builder.clone(), raw_profile)
+                    .await
+                    .map_err(ApiError::internal_server_error)?;
+            }
+        }
+        let diff = state
+            .profile_history
+            .diff(client, &url_builder.sub_url)
+            .await
+            .map_err(ApiError::internal_server_error)?
+            .unwrap_or_default();
+        Ok(ApiResponse::ok(diff))
+    }
+}
+
+pub mod rule_match {
+    use crate::server::app_state::AppState;
+    use crate::server::response::{ApiError, ApiResponse, RequestError};
+    use crate::server::router::{AdminAuth, ConvertorQueryExtractor};
+    use axum::extract::{Path, Query, State};
+    use axum_extra::headers::HeaderMap;
+    use convertor::config::proxy_client::ProxyClient;
+    use convertor::core::rule_engine::{MatchQuery, MatchResult, RuleEngine};
+    use convertor::url::url_builder::UrlBuilder;
+    use std::sync::Arc;
+
+    /// 模拟一次连接会命中转换后配置中的哪条规则, 以及最终使用的策略组和节点
+    #[tracing::instrument(skip_all)]
+    pub async fn evaluate(
+        Path(client): Path<ProxyClient>,
+        ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+        admin: Result<AdminAuth, ApiError>,
+        State(state): State<Arc<AppState>>,
+        Query(match_query): Query<MatchQuery>,
+        header_map: HeaderMap,
+    ) -> Result<ApiResponse<MatchResult>, ApiError> {
+        if match_query.is_empty() {
+            return Err(ApiError::bad_request(RequestError::EmptyMatchQuery));
+        }
+        let query = query.check_for_profile().map_err(ApiError::bad_request)?;
+        let refresh = query.refresh;
+        if refresh {
+            admin?;
+        }
+        let url_builder =
+            UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
+        let raw_profile = state
+            .get_raw_profile(&url_builder, header_map, refresh)
+            .await
+            .map_err(ApiError::internal_server_error)?;
+        let geoip = state.geoip.as_deref();
+        let result = match client {
+            ProxyClient::Surge => {
+                let profile = state
+                    .surge_service
+                    .try_get_profile(url_builder, raw_profile)
+                    .await
+                    .map_err(ApiError::internal_server_error)?;
+                RuleEngine::new(&profile).with_geoip(geoip).evaluate(&match_query)
+            }
+            ProxyClient::Clash => {
+                let profile = state
+                    .clash_service
+                    .try_get_profile(url_builder, raw_profile)
+                    .await
+                    .map_err(ApiError::internal_server_error)?;
+                RuleEngine::new(&profile).with_geoip(geoip).evaluate(&match_query)
+            }
         };
-        Ok(ApiResponse::ok(url_result))
+        Ok(ApiResponse::ok(result))
+    }
+}
+
+pub mod cache {
+    use crate::server::app_state::AppState;
+    use crate::server::response::{ApiError, ApiResponse, AppError, RequestError};
+    use crate::server::router::AdminAuth;
+    use axum::extract::{Query, State};
+    use convertor::config::proxy_client::ProxyClient;
+    use convertor::provider::cache_admin::{CacheOverview, CachePurge};
+    use serde::Deserialize;
+    use std::sync::Arc;
+    use url::Url;
+
+    #[derive(Debug, Deserialize)]
+    pub struct PurgeQuery {
+        pub sub_url: Option<Url>,
+        pub client: Option<ProxyClient>,
+        #[serde(default)]
+        pub all: bool,
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn list(_: AdminAuth, State(state): State<Arc<AppState>>) -> ApiResponse<CacheOverview> {
+        ApiResponse::ok(state.cache_overview().await)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn purge(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Query(query): Query<PurgeQuery>,
+    ) -> Result<ApiResponse<usize>, ApiError> {
+        let purge = CachePurge {
+            sub_url: query.sub_url,
+            client: query.client,
+        };
+        if purge.is_all() && !query.all {
+            return Err(ApiError::bad_request(AppError::RequestError(RequestError::EmptyPurgeScope)));
+        }
+        let removed = state.purge_cache(&purge).await.map_err(ApiError::internal_server_error)?;
+        Ok(ApiResponse::ok(removed))
+    }
+}
+
+pub mod sub_logs {
+    use crate::server::app_state::AppState;
+    use crate::server::response::{ApiError, ApiResponse};
+    use crate::server::router::AdminAuth;
+    use axum::extract::{Query, State};
+    use convertor::provider::sub_log::SubLog;
+    use serde::Deserialize;
+    use std::sync::Arc;
+
+    #[derive(Debug, Deserialize)]
+    pub struct SubLogsQuery {
+        #[serde(default)]
+        pub offset: usize,
+        #[serde(default = "default_limit")]
+        pub limit: usize,
+    }
+
+    fn default_limit() -> usize {
+        50
+    }
+
+    /// 按时间倒序返回订阅请求日志
+    #[tracing::instrument(skip_all)]
+    pub async fn list(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Query(query): Query<SubLogsQuery>,
+    ) -> Result<ApiResponse<Vec<SubLog>>, ApiError> {
+        let logs = state
+            .sub_logs
+            .list(query.offset, query.limit)
+            .await
+            .map_err(ApiError::internal_server_error)?;
+        Ok(ApiResponse::ok(logs))
+    }
+}
+
+pub mod nodes {
+    use crate::server::app_state::AppState;
+    use crate::server::response::ApiResponse;
+    use crate::server::router::AdminAuth;
+    use crate::server::service::NodeStatus;
+    use axum::extract::State;
+    use std::sync::Arc;
+
+    /// 后台探测到的节点可用性与延迟, 未启用探测时为空
+    #[tracing::instrument(skip_all)]
+    pub async fn list(_: AdminAuth, State(state): State<Arc<AppState>>) -> ApiResponse<Vec<NodeStatus>> {
+        ApiResponse::ok(state.node_health.statuses())
+    }
+}
+
+pub mod users {
+    use crate::server::app_state::AppState;
+    use crate::server::response::{ApiError, ApiResponse};
+    use crate::server::router::AdminAuth;
+    use axum::Json;
+    use axum::extract::{Path, State};
+    use convertor::auth::{IssuedToken, User};
+    use serde::Deserialize;
+    use std::sync::Arc;
+    use url::Url;
+
+    #[derive(Debug, Default, Deserialize)]
+    pub struct UpsertUser {
+        /// 为空时允许使用全部订阅
+        #[serde(default)]
+        pub allowed_subscriptions: Vec<Url>,
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub async fn list(_: AdminAuth, State(state): State<Arc<AppState>>) -> Result<ApiResponse<Vec<User>>, ApiError> {
+        let users = state.users.users().await.map_err(ApiError::auth)?;
+        Ok(ApiResponse::ok(users))
+    }
+
+    #[tracing::instrument(skip_all, fields(name = %name))]
+    pub async fn upsert(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Path(name): Path<String>,
+        body: Option<Json<UpsertUser>>,
+    ) -> Result<ApiResponse<User>, ApiError> {
+        let Json(body) = body.unwrap_or_default();
+        let user = state
+            .users
+            .upsert_user(&name, body.allowed_subscriptions)
+            .await
+            .map_err(ApiError::auth)?;
+        Ok(ApiResponse::ok(user))
+    }
+
+    #[tracing::instrument(skip_all, fields(name = %name))]
+    pub async fn remove(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Path(name): Path<String>,
+    ) -> Result<ApiResponse<bool>, ApiError> {
+        let removed = state.users.remove_user(&name).await.map_err(ApiError::auth)?;
+        Ok(ApiResponse::ok(removed))
+    }
+
+    /// 签发新令牌, 令牌明文只在此处返回一次
+    #[tracing::instrument(skip_all, fields(name = %name))]
+    pub async fn issue_token(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Path(name): Path<String>,
+    ) -> Result<ApiResponse<IssuedToken>, ApiError> {
+        let token = state.users.issue_token(&name).await.map_err(ApiError::auth)?;
+        Ok(ApiResponse::ok(token))
+    }
+
+    #[tracing::instrument(skip_all, fields(name = %name, id = %id))]
+    pub async fn revoke_token(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Path((name, id)): Path<(String, String)>,
+    ) -> Result<ApiResponse<bool>, ApiError> {
+        let revoked = state.users.revoke_token(&name, &id).await.map_err(ApiError::auth)?;
+        Ok(ApiResponse::ok(revoked))
+    }
+}
+
+pub mod short_link {
+    use crate::server::app_state::AppState;
+    use crate::server::response::{ApiError, ApiResponse};
+    use crate::server::router::{AdminAuth, ConvertorQueryExtractor};
+    use axum::extract::{Path, Query, State};
+    use convertor::config::proxy_client::ProxyClient;
+    use convertor::url::url_builder::UrlBuilder;
+    use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use url::Url;
+
+    #[derive(Debug, Deserialize)]
+    pub struct ShortLinkQuery {
+        /// 有效期(秒), 为空时永不过期
+        pub ttl: Option<u64>,
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    #[derive(Serialize, Deserialize)]
+    pub struct ShortLinkResult {
+        pub id: String,
+        pub url: Url,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub expires_at: Option<u64>,
+        pub hits: u64,
+    }
+
+    /// 为订阅配置创建短链接, 相同的配置总是得到相同的短链接
+    #[tracing::instrument(skip_all)]
+    pub async fn create(
+        Path(client): Path<ProxyClient>,
+        ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+        State(state): State<Arc<AppState>>,
+        Query(short_link_query): Query<ShortLinkQuery>,
+    ) -> Result<ApiResponse<ShortLinkResult>, ApiError> {
+        let query = query.check_for_profile().map_err(ApiError::bad_request)?;
+        let url_builder =
+            UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
+        let link = state
+            .short_links
+            .create(&url_builder, short_link_query.ttl.map(Duration::from_secs))
+            .await
+            .map_err(ApiError::internal_server_error)?;
+        Ok(ApiResponse::ok(ShortLinkResult {
+            url: url_builder.build_short_link_url(&link.id),
+            id: link.id,
+            expires_at: link.expires_at,
+            hits: link.hits,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, fields(id = %id))]
+    pub async fn remove(
+        _: AdminAuth,
+        State(state): State<Arc<AppState>>,
+        Path(id): Path<String>,
+    ) -> Result<ApiResponse<bool>, ApiError> {
+        let removed = state.short_links.remove(&id).await.map_err(ApiError::internal_server_error)?;
+        Ok(ApiResponse::ok(removed))
     }
 }
@@ -1,31 +1,39 @@
 use crate::server::app_state::AppState;
 use crate::server::response::{ApiError, AppError, RequestError};
-use crate::server::router::ConvertorQueryExtractor;
+use crate::server::router::{AdminAuth, ConvertorQueryExtractor};
 use axum::extract::{Path, State};
-use axum::http::HeaderMap;
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
 use convertor::config::proxy_client::ProxyClient;
-use convertor::config::subscription_config::Headers;
+use convertor::core::profile::surge_header::SurgeHeader;
+use convertor::provider::subscription_userinfo::{SUBSCRIPTION_USERINFO_HEADER, SubscriptionUserinfo};
+use convertor::url::convertor_url::ConvertorUrl;
 use convertor::url::url_builder::UrlBuilder;
 use std::sync::Arc;
 use tracing::instrument;
+use url::Url;
 
 #[instrument(skip_all)]
 pub async fn raw_profile(
     Path(client): Path<ProxyClient>,
     ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+    admin: Result<AdminAuth, ApiError>,
     State(state): State<Arc<AppState>>,
     header_map: HeaderMap,
-) -> Result<String, ApiError> {
+) -> Result<Response, ApiError> {
     let query = query.check_for_profile().map_err(ApiError::bad_request)?;
+    let refresh = query.refresh;
+    // 跳过缓存会直接请求订阅商, 只允许管理员使用
+    if refresh {
+        admin?;
+    }
     let url_builder =
         UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
-    let sub_url = url_builder.build_raw_url();
-    let headers = Headers::from_header_map(header_map).patch(&state.config.subscription.headers);
     let raw_profile = state
-        .provider
-        .get_raw_profile(sub_url.into(), headers)
+        .get_raw_profile(&url_builder, header_map, refresh)
         .await
         .map_err(ApiError::internal_server_error)?;
+    let (userinfo, stale) = (raw_profile.userinfo, raw_profile.stale);
     match client {
         ProxyClient::Surge => {
             let raw_profile = state
@@ -33,7 +41,7 @@ pub async fn raw_profile(
                 .raw_profile(url_builder, raw_profile)
                 .await
                 .map_err(ApiError::internal_server_error)?;
-            Ok(raw_profile)
+            Ok(with_subs_headers(raw_profile, userinfo, stale))
         }
         ProxyClient::Clash => Err(ApiError::bad_request(AppError::RequestError(
             RequestError::UnsupportedClient(client),
@@ -45,44 +53,112 @@ pub async fn raw_profile(
 pub async fn profile(
     Path(client): Path<ProxyClient>,
     ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+    admin: Result<AdminAuth, ApiError>,
     State(state): State<Arc<AppState>>,
     header_map: HeaderMap,
-) -> Result<String, ApiError> {
+) -> Result<Response, ApiError> {
     let query = query.check_for_profile().map_err(ApiError::bad_request)?;
+    let refresh = query.refresh;
+    if refresh {
+        admin?;
+    }
     let url_builder =
         UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
-    let sub_url = url_builder.build_raw_url();
-    let headers = Headers::from_header_map(header_map).patch(&state.config.subscription.headers);
+    render_profile(&state, url_builder, header_map, refresh, None).await
+}
+
+/// 通过短链接获取配置, 每次访问计入一次使用次数
+#[instrument(skip_all, fields(id = %id))]
+pub async fn short_link(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    header_map: HeaderMap,
+) -> Result<Response, ApiError> {
+    let link = state
+        .short_links
+        .resolve(&id)
+        .await
+        .map_err(ApiError::internal_server_error)?
+        .ok_or_else(|| ApiError::not_found(RequestError::ShortLinkNotFound(id)))?;
+    let (url_builder, reencrypted) = link.url_builder(&state.config.secret).map_err(ApiError::internal_server_error)?;
+    // Surge 按配置头中的地址更新托管配置, 改为短链接使之后的更新同样经过短链接
+    let managed_url = url_builder.build_short_link_url(&link.id);
+    if state.config.auth.enabled {
+        state
+            .users
+            .authenticate(url_builder.token.as_deref(), &url_builder.sub_url)
+            .await
+            .map_err(ApiError::auth)?;
+    }
+    // 通过鉴权后才写回重新加密的链接
+    if reencrypted {
+        let mut link = link;
+        link.url_builder = url_builder.clone();
+        link.url_builder.secret = String::new();
+        state.short_links.save(&link).await.map_err(ApiError::internal_server_error)?;
+    }
+    render_profile(&state, url_builder, header_map, false, Some(managed_url)).await
+}
+
+async fn render_profile(
+    state: &AppState,
+    url_builder: UrlBuilder,
+    header_map: HeaderMap,
+    refresh: bool,
+    managed_url: Option<Url>,
+) -> Result<Response, ApiError> {
     let raw_profile = state
-        .provider
-        .get_raw_profile(sub_url.into(), headers)
+        .get_raw_profile(&url_builder, header_map, refresh)
         .await
         .map_err(ApiError::internal_server_error)?;
-    let profile = match client {
-        ProxyClient::Surge => state.surge_service.profile(url_builder, raw_profile).await,
+    let (userinfo, stale) = (raw_profile.userinfo, raw_profile.stale);
+    let profile = match url_builder.client {
+        ProxyClient::Surge => state
+            .surge_service
+            .profile(url_builder, raw_profile)
+            .await
+            .map(|profile| match managed_url {
+                Some(url) => with_managed_url(profile, url),
+                None => profile,
+            }),
         ProxyClient::Clash => state.clash_service.profile(url_builder, raw_profile).await,
     }
     .map_err(ApiError::internal_server_error)?;
-    Ok(profile)
+    Ok(with_subs_headers(profile, userinfo, stale))
+}
+
+/// 替换 Surge 配置头中的托管地址, 其余选项保持不变
+fn with_managed_url(profile: String, url: Url) -> String {
+    let (first, rest) = profile.split_once('\n').unwrap_or((&profile, ""));
+    match SurgeHeader::parse(first) {
+        Some(mut header) => {
+            header.url = Some(ConvertorUrl::raw(url));
+            format!("{header}\n{rest}")
+        }
+        None => profile,
+    }
 }
 
 #[instrument(skip_all)]
 pub async fn rule_provider(
     Path(client): Path<ProxyClient>,
     ConvertorQueryExtractor(query): ConvertorQueryExtractor,
+    admin: Result<AdminAuth, ApiError>,
     State(state): State<Arc<AppState>>,
     header_map: HeaderMap,
-) -> Result<String, ApiError> {
+) -> Result<Response, ApiError> {
     let (query, policy) = query.check_for_rule_provider().map_err(ApiError::bad_request)?;
+    let refresh = query.refresh;
+    if refresh {
+        admin?;
+    }
     let url_builder =
         UrlBuilder::from_convertor_query(query, &state.config.secret, client).map_err(ApiError::bad_request)?;
-    let sub_url = url_builder.build_raw_url();
-    let headers = Headers::from_header_map(header_map).patch(&state.config.subscription.headers);
     let raw_profile = state
-        .provider
-        .get_raw_profile(sub_url.into(), headers)
+        .get_raw_profile(&url_builder, header_map, refresh)
         .await
         .map_err(ApiError::internal_server_error)?;
+    let stale = raw_profile.stale;
     let rules = match client {
         ProxyClient::Surge => {
             state
@@ -98,5 +174,23 @@ pub async fn rule_provider(
         }
     }
     .map_err(ApiError::internal_server_error)?;
-    Ok(rules)
+    Ok(with_subs_headers(rules, None, stale))
+}
+
+/// 上游不可用时返回的是旧数据, 通过 Cache-Status 头(RFC 9211)告知客户端
+pub const CACHE_STATUS_HEADER: &str = "cache-status";
+const STALE_CACHE_STATUS: &str = "convd; hit; detail=stale";
+
+/// 将订阅商下发的流量信息原样透传给客户端, 以便 Surge/Clash 展示
+fn with_subs_headers(body: String, userinfo: Option<SubscriptionUserinfo>, stale: bool) -> Response {
+    let mut response = body.into_response();
+    if let Some(value) = userinfo.and_then(|userinfo| HeaderValue::from_str(&userinfo.to_string()).ok()) {
+        response.headers_mut().insert(SUBSCRIPTION_USERINFO_HEADER, value);
+    }
+    if stale {
+        response
+            .headers_mut()
+            .insert(CACHE_STATUS_HEADER, HeaderValue::from_static(STALE_CACHE_STATUS));
+    }
+    response
 }
@@ -1,5 +1,7 @@
 mod clash_service;
+mod node_health;
 mod surge_service;
 
 pub use clash_service::*;
+pub use node_health::*;
 pub use surge_service::*;
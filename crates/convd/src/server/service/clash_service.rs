@@ -1,38 +1,77 @@
 use crate::server::response::AppError;
+use crate::server::service::NodeHealth;
+use convertor::common::cache::{CacheEntryInfo, CacheSource};
 use convertor::config::Config;
+use convertor::core::diff::ProfileSnapshot;
 use convertor::core::profile::Profile;
 use convertor::core::profile::clash_profile::ClashProfile;
 use convertor::core::profile::policy::Policy;
 use convertor::core::renderer::Renderer;
 use convertor::core::renderer::clash_renderer::ClashRenderer;
+use convertor::provider::cache_admin::CachePurge;
+use convertor::provider::profile_history::ProfileHistory;
+use convertor::provider::subs_response::SubsResponse;
 use convertor::url::url_builder::UrlBuilder;
 use moka::future::Cache;
 use std::sync::Arc;
-use tracing::instrument;
+use tracing::{error, instrument, warn};
 
 type Result<T> = core::result::Result<T, AppError>;
 
 #[derive(Clone)]
 pub struct ClashService {
     pub config: Arc<Config>,
-    pub profile_cache: Cache<UrlBuilder, ClashProfile>,
+    /// 以订阅内容的哈希作为键的一部分, 订阅内容不变时无需重新解析
+    pub profile_cache: Cache<(UrlBuilder, String), ClashProfile>,
+    pub history: ProfileHistory,
+    pub node_health: NodeHealth,
 }
 
 impl ClashService {
-    pub fn new(config: Arc<Config>) -> Self {
-        let duration = std::time::Duration::from_secs(60 * 60);
-        let profile_cache = Cache::builder().max_capacity(100).time_to_live(duration).build();
-        Self { config, profile_cache }
+    pub fn new(config: Arc<Config>, history: ProfileHistory, node_health: NodeHealth) -> Self {
+        let profile_cache = Cache::builder()
+            .max_capacity(config.cache.profile.capacity)
+            .time_to_live(config.cache.profile.ttl())
+            .support_invalidation_closures()
+            .build();
+        Self {
+            config,
+            profile_cache,
+            history,
+            node_health,
+        }
+    }
+
+    pub fn cache_entries(&self) -> Vec<CacheEntryInfo> {
+        self.profile_cache
+            .iter()
+            .map(|(key, _)| CacheEntryInfo {
+                key: format!("{}:{}@{}", key.0.client, key.0.sub_url, &key.1[..key.1.len().min(8)]),
+                source: CacheSource::Memory,
+                age_secs: None,
+                size: None,
+            })
+            .collect()
+    }
+
+    pub fn invalidate(&self, purge: &CachePurge) {
+        let purge = purge.clone();
+        let result = self
+            .profile_cache
+            .invalidate_entries_if(move |(url_builder, _), _| purge.matches(&url_builder.sub_url, url_builder.client));
+        if let Err(e) = result {
+            error!("无法清理配置缓存: {}", e);
+        }
     }
 
     #[instrument(skip_all)]
-    pub async fn profile(&self, url_builder: UrlBuilder, raw_profile: String) -> Result<String> {
+    pub async fn profile(&self, url_builder: UrlBuilder, raw_profile: SubsResponse) -> Result<String> {
         let profile = self.try_get_profile(url_builder, raw_profile).await?;
         Ok(ClashRenderer::render_profile(&profile)?)
     }
 
     #[instrument(skip_all)]
-    pub async fn rule_provider(&self, url_builder: UrlBuilder, raw_profile: String, policy: Policy) -> Result<String> {
+    pub async fn rule_provider(&self, url_builder: UrlBuilder, raw_profile: SubsResponse, policy: Policy) -> Result<String> {
         let profile = self.try_get_profile(url_builder, raw_profile).await?;
         match profile.get_provider_rules_with_policy(&policy) {
             None => Ok(String::new()),
@@ -41,22 +80,43 @@ impl ClashService {
     }
 
     #[instrument(skip_all)]
-    pub async fn subscription(&self, url_builder: UrlBuilder, raw_profile: String) -> Result<String> {
+    pub async fn subscription(&self, url_builder: UrlBuilder, raw_profile: SubsResponse) -> Result<String> {
         let profile = self.try_get_profile(url_builder, raw_profile).await?;
 
         Ok(ClashRenderer::render_profile(&profile)?)
     }
 
-    pub async fn try_get_profile(&self, url_builder: UrlBuilder, raw_profile: String) -> Result<ClashProfile> {
-        self.profile_cache
-            .try_get_with(url_builder.clone(), async {
-                let profile = ClashProfile::parse(raw_profile)?;
+    pub async fn try_get_profile(&self, url_builder: UrlBuilder, raw_profile: SubsResponse) -> Result<ClashProfile> {
+        // 用户令牌不参与缓存, 不同用户使用同一个订阅时共享转换结果
+        let cache_builder = url_builder.without_credentials();
+        let cache_key = (cache_builder.clone(), raw_profile.content_hash.clone());
+        let mut template = self
+            .profile_cache
+            .try_get_with(cache_key, async {
+                let profile = ClashProfile::parse(raw_profile.content)?;
+                // 订阅内容变化时才会走到这里, 顺便记录快照用于比较变化
+                if !raw_profile.stale {
+                    let snapshot = ProfileSnapshot::new(&profile, &raw_profile.content_hash);
+                    if let Err(e) = self.history.record(&url_builder.sub_url, snapshot).await {
+                        warn!("无法记录订阅快照: {e}");
+                    }
+                }
                 let mut template = ClashProfile::template()?;
                 template.patch(profile)?;
-                template.convert(&url_builder)?;
+                template.convert(&cache_builder)?;
                 Ok::<_, AppError>(template)
             })
             .await
-            .map_err(AppError::CacheError)
+            .map_err(AppError::CacheError)?;
+        if url_builder.token.is_some() {
+            template.relink(&cache_builder, &url_builder)?;
+        }
+        // 节点状态与流量信息可能在订阅内容不变时更新, 因此不随配置一起缓存
+        self.node_health.track(&template);
+        self.node_health.apply(&mut template);
+        if let (true, Some(userinfo)) = (self.config.subscription.userinfo_node, &raw_profile.userinfo) {
+            template.append_userinfo_proxy(userinfo);
+        }
+        Ok(template)
     }
 }
@@ -0,0 +1,400 @@
+use convertor::config::health_check_config::HealthCheckConfig;
+use convertor::core::profile::{Profile, SUBSCRIPTION_INFO_GROUP};
+use convertor::core::profile::proxy::Proxy;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument};
+
+/// 超过这个时长没有出现在任何渲染的配置中的节点不再探测
+const STALE_AFTER: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// 节点的探测结果, 以 `server:port` 区分节点
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct NodeStatus {
+    /// 使用这个地址的节点名称
+    pub names: Vec<String>,
+    pub server: String,
+    pub port: u16,
+    /// 是否通过 TLS 握手探测, 否则只建立 TCP 连接
+    pub tls: bool,
+    /// 尚未探测时为空
+    pub alive: Option<bool>,
+    /// 最近一次成功探测的延迟
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: usize,
+    /// 按时间先后排列的探测记录
+    pub history: VecDeque<ProbeRecord>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ProbeRecord {
+    /// 探测时间的 Unix 时间戳(秒)
+    pub time: u64,
+    /// 探测失败时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 记录节点的可用性与延迟, 渲染配置时据此标注或移除不可用的节点
+#[derive(Clone)]
+pub struct NodeHealth {
+    config: HealthCheckConfig,
+    nodes: Arc<RwLock<HashMap<(String, u16), TrackedNode>>>,
+    connector: TlsConnector,
+}
+
+struct TrackedNode {
+    status: NodeStatus,
+    /// TLS 握手使用的 SNI
+    sni: Option<String>,
+    last_seen: Instant,
+}
+
+impl NodeStatus {
+    pub fn is_dead(&self) -> bool {
+        self.alive == Some(false)
+    }
+
+    /// 排序用的权重: 可用的节点按延迟排列, 其次是未探测的节点, 不可用的节点最后
+    fn rank(&self) -> (u8, u64) {
+        match (self.alive, self.latency_ms) {
+            (Some(true), Some(latency)) => (0, latency),
+            (Some(false), _) => (2, 0),
+            _ => (1, 0),
+        }
+    }
+}
+
+impl NodeHealth {
+    pub fn new(config: HealthCheckConfig) -> Self {
+        Self {
+            config,
+            nodes: Arc::new(RwLock::new(HashMap::new())),
+            connector: insecure_connector(),
+        }
+    }
+
+    /// 所有节点的当前状态, 按地址排序
+    pub fn statuses(&self) -> Vec<NodeStatus> {
+        let nodes = self.nodes.read().unwrap_or_else(|e| e.into_inner());
+        let mut statuses = nodes.values().map(|node| node.status.clone()).collect::<Vec<_>>();
+        statuses.sort_by(|a, b| (&a.server, a.port).cmp(&(&b.server, b.port)));
+        statuses
+    }
+
+    /// 记录配置中出现的节点, 之后的每一轮都会探测它们
+    pub fn track<P: Profile>(&self, profile: &P) {
+        if !self.config.enabled {
+            return;
+        }
+        let info_proxies = info_proxies(profile);
+        let mut nodes = self.nodes.write().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        for proxy in profile.proxies().iter().filter(|p| !info_proxies.contains(p.name.as_str())) {
+            let node = nodes
+                .entry((proxy.server.clone(), proxy.port))
+                .or_insert_with(|| TrackedNode {
+                    status: NodeStatus {
+                        names: vec![],
+                        server: proxy.server.clone(),
+                        port: proxy.port,
+                        tls: uses_tls(proxy),
+                        alive: None,
+                        latency_ms: None,
+                        consecutive_failures: 0,
+                        history: VecDeque::new(),
+                    },
+                    sni: proxy.sni.clone(),
+                    last_seen: now,
+                });
+            node.last_seen = now;
+            if !node.status.names.contains(&proxy.name) {
+                node.status.names.push(proxy.name.clone());
+            }
+        }
+    }
+
+    /// 按探测结果调整配置: 标注或移除不可用的节点, 并按延迟排列策略组成员
+    #[instrument(skip_all)]
+    pub fn apply<P: Profile>(&self, profile: &mut P) {
+        if !self.config.enabled {
+            return;
+        }
+        let info_proxies = info_proxies(profile);
+        let statuses = {
+            let nodes = self.nodes.read().unwrap_or_else(|e| e.into_inner());
+            profile
+                .proxies()
+                .iter()
+                .filter(|proxy| !info_proxies.contains(proxy.name.as_str()))
+                .filter_map(|proxy| {
+                    let node = nodes.get(&(proxy.server.clone(), proxy.port))?;
+                    Some((proxy.name.clone(), node.status.clone()))
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        if statuses.is_empty() {
+            return;
+        }
+
+        let dead = statuses
+            .iter()
+            .filter(|(_, status)| status.is_dead())
+            .map(|(name, _)| name.clone())
+            .collect::<HashSet<_>>();
+        if self.config.exclude_dead && !dead.is_empty() {
+            let mut kept = HashSet::new();
+            for group in profile.proxy_groups_mut() {
+                // 成员全部不可用的策略组保留原样, 避免出现空的策略组
+                if group.proxies.iter().filter(|m| !m.contains('=')).all(|m| dead.contains(m)) {
+                    kept.extend(group.proxies.iter().cloned());
+                    continue;
+                }
+                group.proxies.retain(|member| !dead.contains(member));
+            }
+            debug!("移除 {} 个不可用的节点", dead.difference(&kept).count());
+            profile
+                .proxies_mut()
+                .retain(|proxy| !dead.contains(&proxy.name) || kept.contains(&proxy.name));
+        }
+
+        for proxy in profile.proxies_mut() {
+            let Some(status) = statuses.get(&proxy.name).filter(|status| status.is_dead()) else {
+                continue;
+            };
+            let note = format!("# 节点不可用: 连续 {} 次探测失败", status.consecutive_failures);
+            proxy.comment = Some(match proxy.comment.take() {
+                Some(comment) => format!("{comment}\n{note}"),
+                None => note,
+            });
+        }
+
+        if self.config.sort_by_latency {
+            for group in profile.proxy_groups_mut() {
+                // 只调整节点之间的顺序, 策略组与参数保持原来的位置
+                let positions = group
+                    .proxies
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, member)| statuses.contains_key(*member))
+                    .map(|(position, _)| position)
+                    .collect::<Vec<_>>();
+                let mut members = positions.iter().map(|p| group.proxies[*p].clone()).collect::<Vec<_>>();
+                members.sort_by_key(|member| statuses[member].rank());
+                for (position, member) in positions.into_iter().zip(members) {
+                    group.proxies[position] = member;
+                }
+            }
+        }
+    }
+
+    /// 启动后台探测, 直到 `stop` 被取消
+    pub fn spawn_prober(&self, stop: CancellationToken) {
+        if !self.config.enabled {
+            return;
+        }
+        let health = self.clone();
+        tokio::spawn(async move {
+            info!("开始探测节点可用性, 间隔 {}s", health.config.interval().as_secs());
+            let mut interval = tokio::time::interval(health.config.interval());
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            while stop.run_until_cancelled(interval.tick()).await.is_some() {
+                stop.run_until_cancelled(health.probe_all()).await;
+            }
+        });
+    }
+
+    /// 探测一轮所有节点, 并清理长时间没有出现的节点
+    #[instrument(skip_all)]
+    pub async fn probe_all(&self) {
+        let targets = {
+            let mut nodes = self.nodes.write().unwrap_or_else(|e| e.into_inner());
+            nodes.retain(|_, node| node.last_seen.elapsed() < STALE_AFTER);
+            nodes
+                .iter()
+                .map(|(key, node)| {
+                    let sni = node.sni.clone().filter(|sni| !sni.is_empty()).unwrap_or_else(|| key.0.clone());
+                    (key.clone(), node.status.tls.then_some(sni))
+                })
+                .collect::<Vec<_>>()
+        };
+        if targets.is_empty() {
+            return;
+        }
+        let results = futures_util::stream::iter(targets)
+            .map(|((server, port), sni)| async move {
+                let result = self.probe(&server, port, sni.as_deref()).await;
+                ((server, port), result)
+            })
+            .buffer_unordered(self.config.concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut nodes = self.nodes.write().unwrap_or_else(|e| e.into_inner());
+        for (key, result) in results {
+            let Some(node) = nodes.get_mut(&key) else {
+                continue;
+            };
+            let result = match result {
+                Ok(Some(latency)) => Ok(latency),
+                Ok(None) => {
+                    debug!("跳过内网地址的节点: {}:{}", key.0, key.1);
+                    continue;
+                }
+                Err(error) => Err(error),
+            };
+            node.status.record(time, result, &self.config);
+        }
+        let dead = nodes.values().filter(|node| node.status.is_dead()).count();
+        info!("节点探测完成: 共 {} 个节点, {dead} 个不可用", nodes.len());
+    }
+
+    /// 建立 TCP 连接, TLS 节点还需以 `sni` 完成握手, 返回耗时
+    /// 未开启 `allow_private` 时只连接解析出的公网地址, 全部指向内网时跳过并返回 `None`
+    async fn probe(&self, server: &str, port: u16, sni: Option<&str>) -> Result<Option<Duration>, String> {
+        let start = Instant::now();
+        let handshake = async {
+            let addrs = tokio::net::lookup_host((server, port)).await.map_err(|e| e.to_string())?;
+            let addrs = addrs
+                .filter(|addr| self.config.allow_private || !is_internal(addr.ip()))
+                .collect::<Vec<SocketAddr>>();
+            if addrs.is_empty() {
+                return Ok(None);
+            }
+            let stream = TcpStream::connect(addrs.as_slice()).await.map_err(|e| e.to_string())?;
+            if let Some(sni) = sni {
+                let name = ServerName::try_from(sni.to_string()).map_err(|e| e.to_string())?;
+                self.connector.connect(name, stream).await.map_err(|e| e.to_string())?;
+            }
+            Ok(Some(start.elapsed()))
+        };
+        match tokio::time::timeout(self.config.timeout(), handshake).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("超时 {}ms", self.config.timeout().as_millis())),
+        }
+    }
+}
+
+impl NodeStatus {
+    fn record(&mut self, time: u64, result: Result<Duration, String>, config: &HealthCheckConfig) {
+        let record = match result {
+            Ok(latency) => {
+                let latency_ms = latency.as_millis() as u64;
+                self.latency_ms = Some(latency_ms);
+                self.consecutive_failures = 0;
+                self.alive = Some(true);
+                ProbeRecord {
+                    time,
+                    latency_ms: Some(latency_ms),
+                    error: None,
+                }
+            }
+            Err(error) => {
+                self.consecutive_failures += 1;
+                self.alive = Some(self.consecutive_failures < config.failure_threshold.max(1));
+                ProbeRecord {
+                    time,
+                    latency_ms: None,
+                    error: Some(error),
+                }
+            }
+        };
+        self.history.push_back(record);
+        while self.history.len() > config.history.max(1) {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// "Subscription Info" 组中是展示流量等信息的节点, 不是真实的代理
+fn info_proxies<P: Profile>(profile: &P) -> HashSet<&str> {
+    profile
+        .proxy_groups()
+        .iter()
+        .filter(|group| group.name == SUBSCRIPTION_INFO_GROUP)
+        .flat_map(|group| group.proxies.iter().map(String::as_str))
+        .collect()
+}
+
+/// 回环、内网与链路本地地址
+fn is_internal(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.is_unique_local() || ip.is_unicast_link_local() || ip.is_unspecified(),
+    }
+}
+
+fn uses_tls(proxy: &Proxy) -> bool {
+    proxy.sni.is_some() || matches!(proxy.r#type.as_str(), "trojan" | "https")
+}
+
+/// 只验证握手能否完成, 节点普遍使用自签名证书, 因此不校验证书
+fn insecure_connector() -> TlsConnector {
+    let provider = Arc::new(default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("默认的 TLS 协议版本不可用")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,173 @@
+use axum::serve::Listener;
+use color_eyre::Result;
+use color_eyre::eyre::{WrapErr, eyre};
+use convertor::config::tls_config::TlsConfig;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::crypto::ring::{default_provider, sign::any_supported_type};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::server::TlsStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 从 PEM 文件加载证书, 并在文件变化后自动替换, 已建立的连接不受影响
+pub struct TlsTerminator {
+    config: TlsConfig,
+    resolver: Arc<ReloadingCertResolver>,
+    acceptor: TlsAcceptor,
+    modified: RwLock<Option<(SystemTime, SystemTime)>>,
+}
+
+#[derive(Debug)]
+struct ReloadingCertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+impl TlsTerminator {
+    pub fn new(config: TlsConfig) -> Result<Self> {
+        let modified = modified_times(&config);
+        let resolver = Arc::new(ReloadingCertResolver {
+            current: RwLock::new(Arc::new(load_certified_key(&config)?)),
+        });
+        let mut server_config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver.clone());
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Self {
+            config,
+            resolver,
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            modified: RwLock::new(modified),
+        })
+    }
+
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.clone()
+    }
+
+    /// 证书或私钥的修改时间变化时重新加载, 加载失败时继续使用旧证书
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = modified_times(&self.config);
+        if modified.is_none() || modified == *self.modified.read().unwrap_or_else(|e| e.into_inner()) {
+            return Ok(false);
+        }
+        let certified_key = load_certified_key(&self.config)?;
+        *self.resolver.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(certified_key);
+        *self.modified.write().unwrap_or_else(|e| e.into_inner()) = modified;
+        Ok(true)
+    }
+
+    /// 按配置的间隔检查证书文件, 直到 `cancel_token` 被取消
+    pub fn spawn_reload(self: Arc<Self>, cancel_token: CancellationToken) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.reload_interval());
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                match self.reload_if_changed() {
+                    Ok(true) => info!("TLS 证书已重新加载: {}", self.config.cert.display()),
+                    Ok(false) => {}
+                    Err(e) => warn!("重新加载 TLS 证书失败, 继续使用旧证书: {e:?}"),
+                }
+            }
+        });
+    }
+}
+
+fn modified_times(config: &TlsConfig) -> Option<(SystemTime, SystemTime)> {
+    let cert = std::fs::metadata(&config.cert).and_then(|m| m.modified()).ok()?;
+    let key = std::fs::metadata(&config.key).and_then(|m| m.modified()).ok()?;
+    Some((cert, key))
+}
+
+fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(&config.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .wrap_err_with(|| format!("无法读取证书: {}", config.cert.display()))?;
+    if certs.is_empty() {
+        return Err(eyre!("证书文件中没有证书: {}", config.cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key)
+        .wrap_err_with(|| format!("无法读取私钥: {}", config.key.display()))?;
+    let signing_key = any_supported_type(&key).wrap_err("不支持的私钥类型")?;
+    let certified_key = CertifiedKey::new(certs, signing_key);
+    certified_key.keys_match().wrap_err("证书与私钥不匹配")?;
+    Ok(certified_key)
+}
+
+/// 在 TCP 之上完成 TLS 握手的监听器
+/// 握手在独立的任务中进行, 慢速客户端不会阻塞其他连接
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, acceptor: TlsAcceptor) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, incoming) = mpsc::channel(128);
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = tokio::select! {
+                    _ = tx.closed() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("接受连接失败: {e}");
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            continue;
+                        }
+                    },
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, peer)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS 握手失败: {peer}: {e}"),
+                        Err(_) => debug!("TLS 握手超时: {peer}"),
+                    }
+                });
+            }
+        });
+        Ok(Self { incoming, local_addr })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(accepted) => accepted,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
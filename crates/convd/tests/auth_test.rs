@@ -0,0 +1,135 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server_with};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::response::ApiResponse;
+use convertor::auth::IssuedToken;
+use convertor::config::auth_config::AuthBackend;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn send(app: &Router, method: &str, uri: String, secret: Option<&str>) -> color_eyre::Result<(StatusCode, Vec<u8>)> {
+    let mut request = Request::builder().method(method).uri(uri).header("host", "127.0.0.1");
+    if let Some(secret) = secret {
+        request = request.header("authorization", format!("Bearer {secret}"));
+    }
+    let response = app.clone().oneshot(request.body(Body::empty())?).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    Ok((status, body.to_vec()))
+}
+
+#[tokio::test]
+async fn test_profile_requires_user_token() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| config.auth.enabled = true).await?;
+    let secret = app_state.config.secret.clone();
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+
+    // 未携带令牌
+    let (status, _) = send(&app, "GET", url_builder.build_profile_url()?.to_string(), None).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = send(&app, "PUT", "/api/users/alice".to_string(), Some(&secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, "POST", "/api/users/alice/tokens".to_string(), Some(&secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    let issued = serde_json::from_slice::<ApiResponse<IssuedToken>>(&body)?.data.expect("缺少令牌");
+
+    let profile_url = url_builder.clone().with_token(Some(issued.token.clone())).build_profile_url()?;
+    assert!(profile_url.to_string().contains(&issued.token));
+    let (status, _) = send(&app, "GET", profile_url.to_string(), None).await?;
+    assert_eq!(status, StatusCode::OK);
+
+    // 用户被限制为其他订阅
+    let body = r#"{"allowed_subscriptions":["https://example.com/other"]}"#;
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/api/users/alice")
+        .header("host", "127.0.0.1")
+        .header("authorization", format!("Bearer {secret}"))
+        .header("content-type", "application/json")
+        .body(Body::from(body))?;
+    assert_eq!(app.clone().oneshot(request).await?.status(), StatusCode::OK);
+    let (status, _) = send(&app, "GET", profile_url.to_string(), None).await?;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send(&app, "PUT", "/api/users/alice".to_string(), Some(&secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, "DELETE", format!("/api/users/alice/tokens/{}", issued.id), Some(&secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, "GET", profile_url.to_string(), None).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 管理接口仍然需要管理密钥
+    let (status, _) = send(&app, "GET", "/api/users".to_string(), None).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    Ok(())
+}
+
+async fn issue_token(app: &Router, secret: &str, name: &str) -> color_eyre::Result<IssuedToken> {
+    let (status, _) = send(app, "PUT", format!("/api/users/{name}"), Some(secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(app, "POST", format!("/api/users/{name}/tokens"), Some(secret)).await?;
+    assert_eq!(status, StatusCode::OK);
+    Ok(serde_json::from_slice::<ApiResponse<IssuedToken>>(&body)?.data.expect("缺少令牌"))
+}
+
+#[tokio::test]
+async fn test_profile_cache_shared_between_tokens() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| config.auth.enabled = true).await?;
+    let secret = app_state.config.secret.clone();
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+    let alice = issue_token(&app, &secret, "alice").await?;
+    let bob = issue_token(&app, &secret, "bob").await?;
+
+    // 同一个订阅只转换一次, 每个用户拿到的链接中都是自己的令牌
+    for (own, other) in [(&alice, &bob), (&bob, &alice)] {
+        let profile_url = url_builder.clone().with_token(Some(own.token.clone())).build_profile_url()?;
+        let (status, body) = send(&app, "GET", profile_url.to_string(), None).await?;
+        assert_eq!(status, StatusCode::OK);
+        let profile = String::from_utf8(body)?;
+        assert!(profile.contains(&format!("token={}", own.token)));
+        assert!(!profile.contains(&other.token));
+    }
+    app_state.surge_service.profile_cache.run_pending_tasks().await;
+    assert_eq!(app_state.surge_service.profile_cache.entry_count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_users_file_loaded_once() -> color_eyre::Result<()> {
+    init_test!();
+    let file = std::env::temp_dir().join(format!("convd-users-{}.json", uuid::Uuid::new_v4()));
+    let ServerContext { app, app_state } = start_server_with(|config| {
+        config.auth.enabled = true;
+        config.auth.backend = AuthBackend::File;
+        config.auth.file = file.clone();
+    })
+    .await?;
+    let secret = app_state.config.secret.clone();
+    let issued = issue_token(&app, &secret, "alice").await?;
+    let profile_url = app_state
+        .config
+        .create_url_builder(ProxyClient::Surge)?
+        .with_token(Some(issued.token.clone()))
+        .build_profile_url()?;
+
+    // 鉴权不再重复读取文件, 手动修改的文件在重载后生效
+    std::fs::write(&file, "[]")?;
+    let (status, _) = send(&app, "GET", profile_url.to_string(), None).await?;
+    assert_eq!(status, StatusCode::OK);
+    app_state.users.invalidate();
+    let (status, _) = send(&app, "GET", profile_url.to_string(), None).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    std::fs::remove_file(&file)?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::response::ApiResponse;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use convertor::provider::cache_admin::CacheOverview;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn cache_api(server_context: &ServerContext, method: &str, uri: &str) -> color_eyre::Result<(StatusCode, String)> {
+    let ServerContext { app, app_state } = server_context;
+    let request = Request::builder()
+        .uri(uri)
+        .method(method)
+        .header("host", "127.0.0.1")
+        .header("authorization", format!("Bearer {}", app_state.config.secret))
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    let status = response.status();
+    let body = String::from_utf8_lossy(&response.into_body().collect().await?.to_bytes()).to_string();
+    Ok((status, body))
+}
+
+async fn fetch_profile(server_context: &ServerContext, client: ProxyClient) -> color_eyre::Result<()> {
+    let ServerContext { app, app_state } = server_context;
+    let profile_url = app_state.config.create_url_builder(client)?.build_profile_url()?;
+    let request = Request::builder()
+        .uri(profile_url.to_string())
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_list_and_purge() -> color_eyre::Result<()> {
+    init_test!();
+    let server_context = start_server().await?;
+    fetch_profile(&server_context, ProxyClient::Surge).await?;
+    fetch_profile(&server_context, ProxyClient::Clash).await?;
+
+    let (status, body) = cache_api(&server_context, "GET", "/api/cache").await?;
+    assert_eq!(status, StatusCode::OK);
+    let overview = serde_json::from_str::<ApiResponse<CacheOverview>>(&body)?.data.unwrap_or_default();
+    assert_eq!(overview.subscriptions.len(), 2);
+    assert_eq!(overview.profiles.len(), 2);
+
+    let (status, _) = cache_api(&server_context, "DELETE", "/api/cache?client=surge").await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = cache_api(&server_context, "GET", "/api/cache").await?;
+    let overview = serde_json::from_str::<ApiResponse<CacheOverview>>(&body)?.data.unwrap_or_default();
+    assert_eq!(overview.subscriptions.len(), 1);
+    assert!(overview.subscriptions[0].key.contains("flag=clash"));
+    assert_eq!(overview.profiles.len(), 1);
+
+    let (status, _) = cache_api(&server_context, "DELETE", "/api/cache").await?;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = cache_api(&server_context, "DELETE", "/api/cache?all=true").await?;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = cache_api(&server_context, "GET", "/api/cache").await?;
+    let overview = serde_json::from_str::<ApiResponse<CacheOverview>>(&body)?.data.unwrap_or_default();
+    assert!(overview.subscriptions.is_empty() && overview.profiles.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_api_unauthorized() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, .. } = start_server().await?;
+    let request = Request::builder()
+        .uri("/api/cache")
+        .header("host", "127.0.0.1")
+        .header("authorization", "Bearer wrong")
+        .body(Body::empty())?;
+    let response = app.oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refresh_requires_admin() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server().await?;
+    let profile_url = app_state.config.create_url_builder(ProxyClient::Surge)?.build_profile_url()?;
+    let refresh_url = format!("{profile_url}&refresh=true");
+    let request = |authorization: Option<String>| {
+        let mut builder = Request::builder().uri(&refresh_url).header("host", "127.0.0.1");
+        if let Some(authorization) = authorization {
+            builder = builder.header("authorization", authorization);
+        }
+        builder.body(Body::empty())
+    };
+
+    // 持有订阅链接不足以跳过缓存
+    let response = app.clone().oneshot(request(None)?).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = app.clone().oneshot(request(Some("Bearer wrong".to_string()))?).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let bearer = format!("Bearer {}", app_state.config.secret);
+    let response = app.clone().oneshot(request(Some(bearer))?).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    Ok(())
+}
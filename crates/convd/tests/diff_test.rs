@@ -0,0 +1,58 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use color_eyre::eyre::OptionExt;
+use convd::server::response::ApiResponse;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::diff::{ProfileDiff, ProfileSnapshot};
+use convertor::core::profile::Profile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::init_test;
+use convertor::provider::subs_response::content_hash;
+use convertor::testkit::mock_profile;
+use convertor::url::url_builder::HostPort;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn diff(server_context: &ServerContext) -> color_eyre::Result<ProfileDiff> {
+    let ServerContext { app, app_state } = server_context;
+    let diff_url = app_state.config.create_url_builder(ProxyClient::Surge)?.build_diff_url()?;
+    let request = Request::builder()
+        .uri(format!("{}?{}", diff_url.path(), diff_url.query().unwrap_or_default()))
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await?.to_bytes();
+    let response = serde_json::from_slice::<ApiResponse<ProfileDiff>>(&body)?;
+    response.data.ok_or_eyre("响应中没有 diff")
+}
+
+#[tokio::test]
+async fn test_subscription_diff() -> color_eyre::Result<()> {
+    init_test!();
+    let server_context = start_server().await?;
+    let sub_url = server_context.app_state.config.subscription.sub_url.clone();
+
+    // 模拟上一次获取到的订阅: 比当前多一个节点
+    let sub_host = sub_url.host_port().ok_or_eyre("无法从 sub_url 中提取 host port")?;
+    let previous = mock_profile(ProxyClient::Surge, &sub_host).replace(
+        "[Proxy]\n",
+        "[Proxy]\n🇯🇵 日本 99=ss,bppleman.com,8080,password=bppleman,encrypt-method=aes-128-gcm\n",
+    );
+    let snapshot = ProfileSnapshot::new(&SurgeProfile::parse(previous.clone())?, content_hash(&previous));
+    server_context.app_state.profile_history.record(&sub_url, snapshot).await?;
+
+    let diff = diff(&server_context).await?;
+    assert_eq!(diff.previous_hash, Some(content_hash(&previous)));
+    assert_eq!(diff.nodes_removed, vec!["🇯🇵 日本 99"]);
+    assert!(diff.nodes_added.is_empty() && diff.rules.is_empty());
+
+    // 订阅内容没有再次变化, 结果保持不变
+    assert_eq!(diff, self::diff(&server_context).await?);
+    Ok(())
+}
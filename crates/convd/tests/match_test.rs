@@ -0,0 +1,57 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::response::ApiResponse;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::rule_engine::MatchResult;
+use convertor::init_test;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn evaluate(server_context: &ServerContext, params: &str) -> color_eyre::Result<(StatusCode, ApiResponse<MatchResult>)> {
+    let ServerContext { app, app_state } = server_context;
+    let profile_url = app_state.config.create_url_builder(ProxyClient::Surge)?.build_profile_url()?;
+    let request = Request::builder()
+        .uri(format!("/api/match/surge?{}&{params}", profile_url.query.unwrap_or_default()))
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    Ok((status, serde_json::from_slice(&body)?))
+}
+
+#[tokio::test]
+async fn test_match_rule() -> color_eyre::Result<()> {
+    init_test!();
+    let server_context = start_server().await?;
+
+    let (status, response) = evaluate(&server_context, "domain=api.boslife.net").await?;
+    assert_eq!(status, StatusCode::OK);
+    let result = response.data.expect("响应中没有匹配结果");
+    assert_eq!(result.rule.as_deref(), Some("DOMAIN-SUFFIX,boslife.net"));
+    assert_eq!(result.policy.as_deref(), Some("BosLife"));
+
+    let (_, response) = evaluate(&server_context, "ip=192.168.1.1&port=443").await?;
+    let result = response.data.expect("响应中没有匹配结果");
+    assert_eq!(result.rule.as_deref(), Some("IP-CIDR,192.168.0.0/16"));
+    assert_eq!(result.chain, vec!["DIRECT"]);
+
+    let (status, _) = evaluate(&server_context, "unused=1").await?;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_match_refresh_requires_admin() -> color_eyre::Result<()> {
+    init_test!();
+    let server_context = start_server().await?;
+
+    let (status, _) = evaluate(&server_context, "domain=api.boslife.net&refresh=true").await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    Ok(())
+}
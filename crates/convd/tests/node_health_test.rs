@@ -0,0 +1,180 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server_with};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::http::header::AUTHORIZATION;
+use axum::routing::get;
+use convd::server::response::ApiResponse;
+use convd::server::service::{NodeHealth, NodeStatus};
+use convd::server::tls::{TlsListener, TlsTerminator};
+use convertor::config::health_check_config::HealthCheckConfig;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::config::tls_config::TlsConfig;
+use convertor::core::profile::Profile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::init_test;
+use http_body_util::BodyExt;
+use std::path::Path;
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+fn health_check_config() -> HealthCheckConfig {
+    HealthCheckConfig {
+        enabled: true,
+        timeout: 1000,
+        // 测试节点都在本机
+        allow_private: true,
+        ..Default::default()
+    }
+}
+
+fn profile(tcp_port: u16, tls_port: u16, dead_port: u16) -> String {
+    format!(
+        r#"[General]
+loglevel = notify
+
+[Proxy]
+🇭🇰 Dead = ss, 127.0.0.1, {dead_port}, password=convertor
+🇭🇰 TLS = trojan, 127.0.0.1, {tls_port}, password=convertor, sni=localhost
+🇭🇰 TCP = ss, 127.0.0.1, {tcp_port}, password=convertor
+
+[Proxy Group]
+Proxy = select, 🇭🇰 Dead, 🇭🇰 TLS, 🇭🇰 TCP, DIRECT
+Only Dead = select, 🇭🇰 Dead
+
+[Rule]
+FINAL,Proxy
+"#
+    )
+}
+
+#[tokio::test]
+async fn test_probe_and_apply_node_health() -> color_eyre::Result<()> {
+    init_test!();
+    let tcp = TcpListener::bind("127.0.0.1:0").await?;
+    let tcp_port = tcp.local_addr()?.port();
+
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls");
+    let tls = TlsTerminator::new(TlsConfig {
+        cert: fixtures.join("first.crt"),
+        key: fixtures.join("first.key"),
+        reload_interval: 30,
+    })?;
+    let tls_listener = TcpListener::bind("127.0.0.1:0").await?;
+    let tls_port = tls_listener.local_addr()?.port();
+    let tls_listener = TlsListener::new(tls_listener, tls.acceptor())?;
+    let app: Router = Router::new().route("/", get(|| async { "convd" }));
+    tokio::spawn(async move { axum::serve(tls_listener, app).await });
+
+    // 释放端口后连接会被拒绝
+    let dead_port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+
+    let health = NodeHealth::new(HealthCheckConfig {
+        exclude_dead: true,
+        ..health_check_config()
+    });
+    let profile = SurgeProfile::parse(profile(tcp_port, tls_port, dead_port))?;
+    health.track(&profile);
+    // 默认连续失败两次才视为不可用
+    health.probe_all().await;
+    assert!(health.statuses().iter().all(|status| status.alive == Some(true)));
+    health.probe_all().await;
+
+    let statuses = health.statuses();
+    assert_eq!(statuses.len(), 3);
+    let status = |port: u16| statuses.iter().find(|status| status.port == port).expect("缺少节点状态");
+    assert_eq!(status(dead_port).alive, Some(false));
+    assert_eq!(status(dead_port).consecutive_failures, 2);
+    assert!(status(dead_port).history.iter().all(|record| record.error.is_some()));
+    assert!(status(tls_port).tls);
+    assert_eq!(status(tls_port).alive, Some(true));
+    assert!(!status(tcp_port).tls);
+    assert_eq!(status(tcp_port).alive, Some(true));
+    assert_eq!(status(tcp_port).history.len(), 2);
+
+    let mut applied = profile.clone();
+    health.apply(&mut applied);
+    // 成员全部不可用的策略组保留原样
+    let names = applied.proxies().iter().map(|proxy| proxy.name.as_str()).collect::<Vec<_>>();
+    assert!(names.contains(&"🇭🇰 Dead"));
+    let proxy_group = &applied.proxy_groups()[0];
+    assert_eq!(proxy_group.proxies.len(), 3);
+    assert!(!proxy_group.proxies.contains(&"🇭🇰 Dead".to_string()));
+    assert_eq!(proxy_group.proxies.last().map(String::as_str), Some("DIRECT"));
+    assert_eq!(applied.proxy_groups()[1].proxies, vec!["🇭🇰 Dead".to_string()]);
+    let dead = applied.proxies().iter().find(|proxy| proxy.name == "🇭🇰 Dead");
+    assert!(dead.and_then(|proxy| proxy.comment.as_deref()).is_some_and(|c| c.contains("节点不可用")));
+
+    // 未开启 exclude_dead 时只标注并排到最后
+    let health = NodeHealth::new(health_check_config());
+    health.track(&profile);
+    health.probe_all().await;
+    health.probe_all().await;
+    let mut applied = profile.clone();
+    health.apply(&mut applied);
+    assert_eq!(applied.proxies().len(), 3);
+    assert_eq!(applied.proxy_groups()[0].proxies[2], "🇭🇰 Dead");
+    assert_eq!(applied.proxy_groups()[0].proxies[3], "DIRECT");
+    drop(tcp);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_probe_skips_private_nodes_by_default() -> color_eyre::Result<()> {
+    init_test!();
+    let tcp = TcpListener::bind("127.0.0.1:0").await?;
+    let tcp_port = tcp.local_addr()?.port();
+
+    let health = NodeHealth::new(HealthCheckConfig {
+        allow_private: false,
+        ..health_check_config()
+    });
+    let profile = SurgeProfile::parse(profile(tcp_port, tcp_port, tcp_port))?;
+    health.track(&profile);
+    health.probe_all().await;
+    // 只跳过探测, 节点仍保持未探测的状态
+    let statuses = health.statuses();
+    assert!(!statuses.is_empty());
+    assert!(statuses.iter().all(|status| status.alive.is_none() && status.history.is_empty()));
+    drop(tcp);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nodes_api_lists_tracked_nodes() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| config.health_check = health_check_config()).await?;
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+
+    let request = Request::builder()
+        .uri(url_builder.build_profile_url()?.to_string())
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/api/nodes")
+        .header("host", "127.0.0.1")
+        .header(AUTHORIZATION, format!("Bearer {}", app_state.config.secret))
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await?.to_bytes();
+    let nodes = serde_json::from_slice::<ApiResponse<Vec<NodeStatus>>>(&body)?.data.unwrap_or_default();
+    assert!(!nodes.is_empty());
+    // 尚未探测过
+    assert!(nodes.iter().all(|node| node.alive.is_none() && !node.names.is_empty()));
+
+    let request = Request::builder()
+        .uri("/api/nodes")
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    Ok(())
+}
@@ -7,6 +7,8 @@ use axum::extract::Request;
 use axum::response::Response;
 use convertor::config::proxy_client::ProxyClient;
 use convertor::init_test;
+use convertor::provider::subscription_userinfo::SUBSCRIPTION_USERINFO_HEADER;
+use convertor::testkit::MOCK_SUBSCRIPTION_USERINFO;
 use http_body_util::BodyExt;
 use tower::ServiceExt;
 
@@ -49,3 +51,25 @@ async fn test_profile_clash_boslife() -> color_eyre::Result<()> {
     insta::assert_snapshot!(actual);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_profile_forward_subscription_userinfo() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state, .. } = start_server().await?;
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+
+    let request = Request::builder()
+        .uri(url_builder.build_profile_url()?.to_string())
+        .method("GET")
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response: Response = app.oneshot(request).await?;
+
+    let userinfo = response
+        .headers()
+        .get(SUBSCRIPTION_USERINFO_HEADER)
+        .map(|value| value.to_str())
+        .transpose()?;
+    assert_eq!(userinfo, Some(MOCK_SUBSCRIPTION_USERINFO));
+    Ok(())
+}
@@ -0,0 +1,97 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server_with};
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{StatusCode, header};
+use convertor::config::Config;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::config::rate_limit_config::TokenBucketConfig;
+use convertor::init_test;
+use http_body_util::BodyExt;
+use std::net::SocketAddr;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_rate_limit_per_ip_and_subscription() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| {
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_ip = TokenBucketConfig { burst: 2, per_minute: 1 };
+        config.rate_limit.per_subscription = TokenBucketConfig { burst: 3, per_minute: 1 };
+        config.rate_limit.trusted_proxies = vec!["127.0.0.1".parse().expect("不合法的地址")];
+    })
+    .await?;
+    let profile_url = app_state.config.create_url_builder(ProxyClient::Surge)?.build_profile_url()?;
+
+    let send = |peer: &str, forwarded_for: Option<&str>| {
+        let mut request = Request::builder()
+            .uri(profile_url.to_string())
+            .header("host", "127.0.0.1");
+        if let Some(forwarded_for) = forwarded_for {
+            request = request.header("x-forwarded-for", forwarded_for);
+        }
+        let mut request = request.body(Body::empty()).expect("无法构造请求");
+        let peer: SocketAddr = format!("{peer}:40000").parse().expect("不合法的地址");
+        request.extensions_mut().insert(ConnectInfo(peer));
+        app.clone().oneshot(request)
+    };
+
+    assert_eq!(send("10.0.0.1", None).await?.status(), StatusCode::OK);
+    // 不可信的来源伪造 X-Forwarded-For 无效
+    assert_eq!(send("10.0.0.1", Some("198.51.100.1")).await?.status(), StatusCode::OK);
+    let response = send("10.0.0.1", None).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = response.headers().get(header::RETRY_AFTER).and_then(|v| v.to_str().ok());
+    assert_eq!(retry_after, Some("60"));
+
+    // 经由可信代理的请求按真实客户端地址计数, 但共享同一个订阅的令牌桶
+    assert_eq!(send("127.0.0.1", Some("203.0.113.7, 127.0.0.1")).await?.status(), StatusCode::OK);
+    let response = send("127.0.0.1", Some("203.0.113.8")).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = response.into_body().collect().await?.to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains("subscription"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rate_limit_rejection_keeps_other_tokens() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| {
+        config.rate_limit.enabled = true;
+        config.rate_limit.per_ip = TokenBucketConfig { burst: 2, per_minute: 1 };
+        config.rate_limit.per_subscription = TokenBucketConfig { burst: 1, per_minute: 1 };
+    })
+    .await?;
+    let profile_url = app_state.config.create_url_builder(ProxyClient::Surge)?.build_profile_url()?;
+
+    let send = |uri: String| {
+        let mut request = Request::builder()
+            .uri(uri)
+            .header("host", "127.0.0.1")
+            .body(Body::empty())
+            .expect("无法构造请求");
+        let peer: SocketAddr = "10.0.0.1:40000".parse().expect("不合法的地址");
+        request.extensions_mut().insert(ConnectInfo(peer));
+        app.clone().oneshot(request)
+    };
+
+    assert_eq!(send(profile_url.to_string()).await?.status(), StatusCode::OK);
+    // 被订阅的令牌桶拒绝时, IP 的令牌不被消耗
+    let response = send(profile_url.to_string()).await?;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = response.into_body().collect().await?.to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains("subscription"));
+    // 换一个订阅时 IP 仍有剩余的令牌
+    assert_eq!(send("/p/unknown".to_string()).await?.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[test]
+fn test_rate_limit_rejects_zero_burst() {
+    let mut config = Config::template();
+    assert!(config.validate().is_ok());
+    config.rate_limit.per_subscription = TokenBucketConfig { burst: 0, per_minute: 30 };
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,108 @@
+use axum::Router;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::reload::{ConfigSource, Reloader};
+use convertor::common::encrypt::encrypt;
+use convertor::config::Config;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use convertor::url::url_builder::UrlBuilder;
+use std::time::Duration;
+use tower::ServiceExt;
+
+async fn admin_status(app: &Router, secret: &str) -> color_eyre::Result<StatusCode> {
+    let request = Request::builder()
+        .uri("/api/cache")
+        .header("host", "127.0.0.1")
+        .header("authorization", format!("Bearer {secret}"))
+        .body(Body::empty())?;
+    Ok(app.clone().oneshot(request).await?.status())
+}
+
+async fn admin_status_by_link(app: &Router, secret: &str) -> color_eyre::Result<StatusCode> {
+    let enc_secret = encrypt(secret.as_bytes(), secret)?;
+    let request = Request::builder()
+        .uri(format!("/api/cache?secret={enc_secret}"))
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    Ok(app.clone().oneshot(request).await?.status())
+}
+
+#[tokio::test]
+async fn test_reload_swaps_state_and_rejects_invalid_config() -> color_eyre::Result<()> {
+    init_test!();
+    let config = Config::template();
+    let old_secret = config.secret.clone();
+    let mut reloader = Reloader::new(config.clone(), ConfigSource::new(".", None), None, None).await?;
+    let app = reloader.router();
+    assert_eq!(admin_status(&app, &old_secret).await?, StatusCode::OK);
+
+    let url_builder = UrlBuilder::new(
+        &old_secret,
+        None,
+        ProxyClient::Surge,
+        config.server.clone(),
+        config.subscription.sub_url.clone(),
+        None,
+        86400,
+        true,
+    )?;
+    let link = reloader.state().short_links.create(&url_builder, None).await?;
+
+    // 配置没有变化时不重建
+    assert!(!reloader.reload(config.clone()).await?);
+
+    let mut rotated = config.clone();
+    rotated.secret = "rotated".to_string();
+    rotated.previous_secrets = vec![old_secret.clone()];
+    assert!(reloader.reload(rotated.clone()).await?);
+    assert_eq!(admin_status(&app, "rotated").await?, StatusCode::OK);
+    assert_eq!(admin_status(&app, &old_secret).await?, StatusCode::UNAUTHORIZED);
+    // 轮换前生成的管理链接在旧密钥移除前仍然有效
+    assert_eq!(admin_status_by_link(&app, &old_secret).await?, StatusCode::OK);
+    assert_eq!(admin_status_by_link(&app, "unknown").await?, StatusCode::UNAUTHORIZED);
+    // 运行时数据在重载后保留
+    assert!(reloader.state().short_links.get(&link.id).await?.is_some());
+
+    // 不合法的配置被拒绝, 继续使用当前配置
+    let mut invalid = rotated.clone();
+    invalid.secret = String::new();
+    assert!(reloader.reload(invalid).await.is_err());
+    assert_eq!(reloader.state().config.secret, "rotated");
+    assert_eq!(admin_status(&app, "rotated").await?, StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reload_on_config_file_change() -> color_eyre::Result<()> {
+    init_test!();
+    let dir = std::env::temp_dir().join(format!("convd-reload-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let file = dir.join("convertor.toml");
+    let mut config = Config::template();
+    std::fs::write(&file, config.to_string())?;
+
+    let source = ConfigSource::new(&dir, None);
+    let reloader = Reloader::new(source.load()?, source, None, None).await?;
+    let app = reloader.router();
+    let mut processed = reloader.processed();
+    let stop = tokio_util::sync::CancellationToken::new();
+    let handle = tokio::spawn(reloader.run(stop.clone()));
+
+    // 写入不合法的配置, 应当被拒绝
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    std::fs::write(&file, "secret = 1")?;
+    tokio::time::timeout(Duration::from_secs(10), processed.changed()).await??;
+    assert_eq!(admin_status(&app, &config.secret).await?, StatusCode::OK);
+
+    config.secret = "from-file".to_string();
+    std::fs::write(&file, config.to_string())?;
+    tokio::time::timeout(Duration::from_secs(10), processed.changed()).await??;
+    assert_eq!(admin_status(&app, "from-file").await?, StatusCode::OK, "配置文件变化后未重新加载");
+
+    stop.cancel();
+    handle.await?;
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use axum::Router;
-use axum::routing::get;
+use axum::routing::{delete, get, post, put};
 use convd::server::app_state::AppState;
+use convd::server::layer::rate_limit::RateLimitLayer;
 use convd::server::router::{api, profile};
 use convertor::config::Config;
 use convertor::testkit::start_mock_provider_server;
@@ -11,16 +12,38 @@ pub struct ServerContext {
     pub app_state: Arc<AppState>,
 }
 
+#[allow(unused)]
 pub async fn start_server() -> color_eyre::Result<ServerContext> {
+    start_server_with(|_| {}).await
+}
+
+pub async fn start_server_with(configure: impl FnOnce(&mut Config)) -> color_eyre::Result<ServerContext> {
     let mut config = Config::template();
     start_mock_provider_server(&mut config).await?;
+    configure(&mut config);
 
-    let app_state = Arc::new(AppState::new(config, None, None));
-    let app: Router = Router::new()
+    let app_state = Arc::new(AppState::new(config, None, None)?);
+    let public = Router::new()
         .route("/raw-profile/{client}", get(profile::raw_profile))
         .route("/profile/{client}", get(profile::profile))
         .route("/rule-provider/{client}", get(profile::rule_provider))
+        .route("/p/{id}", get(profile::short_link))
         .route("/api/subscription/{client}", get(api::subscription::subscription))
+        .route("/api/subscription/{client}/diff", get(api::subscription::diff))
+        .route("/api/subscription/{client}/qr", get(api::subscription::qr))
+        .route("/api/subscription/{client}/short-link", post(api::short_link::create))
+        .route("/api/match/{client}", get(api::rule_match::evaluate))
+        .route_layer(RateLimitLayer::new(app_state.rate_limiter.clone()));
+    let app: Router = Router::new()
+        .merge(public)
+        .route("/api/cache", get(api::cache::list).delete(api::cache::purge))
+        .route("/api/sub-logs", get(api::sub_logs::list))
+        .route("/api/nodes", get(api::nodes::list))
+        .route("/api/short-links/{id}", delete(api::short_link::remove))
+        .route("/api/users", get(api::users::list))
+        .route("/api/users/{name}", put(api::users::upsert).delete(api::users::remove))
+        .route("/api/users/{name}/tokens", post(api::users::issue_token))
+        .route("/api/users/{name}/tokens/{id}", delete(api::users::revoke_token))
         .with_state(app_state.clone());
 
     Ok(ServerContext { app, app_state })
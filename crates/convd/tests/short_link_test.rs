@@ -0,0 +1,67 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::response::ApiResponse;
+use convd::server::router::api::short_link::ShortLinkResult;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+async fn send(app: &Router, method: &str, uri: String) -> color_eyre::Result<(StatusCode, Vec<u8>)> {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes();
+    Ok((status, body.to_vec()))
+}
+
+#[tokio::test]
+async fn test_short_link_serves_profile() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server().await?;
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+    let profile_url = url_builder.build_profile_url()?;
+    let query = profile_url.query.clone().unwrap_or_default();
+
+    let (status, body) = send(&app, "POST", format!("/api/subscription/surge/short-link?{query}")).await?;
+    assert_eq!(status, StatusCode::OK);
+    let link = serde_json::from_slice::<ApiResponse<ShortLinkResult>>(&body)?.data.expect("缺少短链接");
+    assert_eq!(link.url.path(), format!("/p/{}", link.id));
+    assert_eq!(link.hits, 0);
+
+    // 同一份配置重新生成密文后仍得到相同的短链接
+    let query = app_state.config.create_url_builder(ProxyClient::Surge)?.build_profile_url()?.query.unwrap_or_default();
+    let (_, body) = send(&app, "POST", format!("/api/subscription/surge/short-link?{query}")).await?;
+    let again = serde_json::from_slice::<ApiResponse<ShortLinkResult>>(&body)?.data.expect("缺少短链接");
+    assert_eq!(again.id, link.id);
+
+    let (status, expected) = send(&app, "GET", profile_url.to_string()).await?;
+    assert_eq!(status, StatusCode::OK);
+    let expected = String::from_utf8(expected)?;
+    for _ in 0..2 {
+        let (status, body) = send(&app, "GET", link.url.path().to_string()).await?;
+        assert_eq!(status, StatusCode::OK);
+        // 托管配置头指向短链接, 其余内容与完整链接返回的配置相同
+        let body = String::from_utf8(body)?;
+        let (header, rest) = body.split_once('\n').expect("缺少配置头");
+        assert!(header.starts_with(&format!("#!MANAGED-CONFIG {} ", link.url)));
+        assert_eq!(Some(rest), expected.split_once('\n').map(|(_, rest)| rest));
+    }
+    let stored = app_state.short_links.get(&link.id).await?.expect("短链接丢失");
+    assert_eq!(stored.hits, 2);
+    assert!(stored.url_builder.secret.is_empty());
+
+    let (status, _) = send(&app, "GET", "/p/unknown".to_string()).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
@@ -0,0 +1,77 @@
+#[path = "./server.rs"]
+mod server;
+
+use crate::server::{ServerContext, start_server, start_server_with};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::StatusCode;
+use convd::server::response::ApiResponse;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::init_test;
+use convertor::provider::sub_log::SubLog;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_sub_logs_record_upstream_fetch() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server_with(|config| config.cache.sub_logs.analyze = true).await?;
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+
+    // 第二次请求命中缓存, 不应产生新的日志
+    for _ in 0..2 {
+        let request = Request::builder()
+            .uri(url_builder.build_profile_url()?.to_string())
+            .header("host", "127.0.0.1")
+            .header("user-agent", "Surge Mac/8310")
+            .body(Body::empty())?;
+        let response = app.clone().oneshot(request).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // 使用加密后的密钥访问
+    let sub_logs_url = url_builder.build_sub_logs_url()?;
+    let request = Request::builder()
+        .uri(format!("{}?{}", sub_logs_url.path(), sub_logs_url.query().unwrap_or_default()))
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await?.to_bytes();
+    let logs = serde_json::from_slice::<ApiResponse<Vec<SubLog>>>(&body)?.data.unwrap_or_default();
+    assert_eq!(logs.len(), 1);
+    let log = &logs[0];
+    assert_eq!(log.status, Some(200));
+    assert_eq!(log.user_agent.as_deref(), Some("Surge Mac/8310"));
+    assert!(log.bytes > 0);
+    assert!(log.node_count.is_some_and(|count| count > 0));
+    assert!(log.diagnostics.is_empty());
+
+    let request = Request::builder()
+        .uri("/api/sub-logs")
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sub_logs_skip_analysis_by_default() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server().await?;
+    let url_builder = app_state.config.create_url_builder(ProxyClient::Surge)?;
+    let request = Request::builder()
+        .uri(url_builder.build_profile_url()?.to_string())
+        .header("host", "127.0.0.1")
+        .body(Body::empty())?;
+    let response = app.clone().oneshot(request).await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // 未开启 analyze 时不再额外解析订阅内容
+    let logs = app_state.sub_logs.list(0, 10).await?;
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].status, Some(200));
+    assert!(logs[0].node_count.is_none());
+    Ok(())
+}
@@ -57,3 +57,38 @@ async fn test_subscription_surge_boslife() -> color_eyre::Result<()> {
     insta::assert_json_snapshot!(url_result.data.unwrap());
     Ok(())
 }
+
+#[tokio::test]
+async fn test_subscription_qr() -> color_eyre::Result<()> {
+    init_test!();
+    let ServerContext { app, app_state } = start_server().await?;
+    let secret = app_state.config.secret.clone();
+    let enc_secret = encrypt(secret.as_bytes(), secret.as_str())?;
+    let enc_sub_url = encrypt(secret.as_bytes(), app_state.config.subscription.sub_url.as_str())?;
+
+    let qr = |query: &str| {
+        Request::builder()
+            .uri(format!(
+                "/api/subscription/clash/qr?secret={enc_secret}&interval=43200&strict=true&sub_url={enc_sub_url}&{query}"
+            ))
+            .method("GET")
+            .header("host", "127.0.0.1")
+            .body(Body::empty())
+    };
+
+    let response = app.clone().oneshot(qr("entry=profile")?).await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    let body = response.into_body().collect().await?.to_bytes();
+    assert!(String::from_utf8_lossy(&body).contains("<svg"));
+
+    let response = app.clone().oneshot(qr("entry=rule_provider&index=0&format=png")?).await?;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let body = response.into_body().collect().await?.to_bytes();
+    assert!(body.starts_with(b"\x89PNG"));
+
+    let response = app.clone().oneshot(qr("entry=rule_provider&index=999")?).await?;
+    assert_eq!(response.status(), 404);
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use axum::Router;
+use axum::extract::ConnectInfo;
+use axum::routing::get;
+use convd::server::listen::ListenAddr;
+use convd::server::serve;
+use convd::server::tls::{TlsListener, TlsTerminator};
+use convertor::config::tls_config::TlsConfig;
+use convertor::init_test;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_util::sync::CancellationToken;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+}
+
+/// 信任 `cert` 并请求一次, 返回服务端出示的证书与响应内容
+async fn fetch(addr: SocketAddr, cert: &Path) -> color_eyre::Result<(CertificateDer<'static>, String)> {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(cert)?)?;
+    let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let stream = TcpStream::connect(addr).await?;
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost")?, stream)
+        .await?;
+    let peer_cert = stream.get_ref().1.peer_certificates().expect("缺少服务端证书")[0].clone().into_owned();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok((peer_cert, response))
+}
+
+#[tokio::test]
+async fn test_tls_listener_reloads_certificate() -> color_eyre::Result<()> {
+    init_test!();
+    let dir = std::env::temp_dir().join(format!("convd-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let config = TlsConfig {
+        cert: dir.join("convd.crt"),
+        key: dir.join("convd.key"),
+        reload_interval: 1,
+    };
+    std::fs::copy(fixture("first.crt"), &config.cert)?;
+    std::fs::copy(fixture("first.key"), &config.key)?;
+
+    let tls = TlsTerminator::new(config.clone())?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let listener = TlsListener::new(listener, tls.acceptor())?;
+    let app: Router = Router::new().route("/", get(|| async { "convd" }));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let (cert, response) = fetch(addr, &fixture("first.crt")).await?;
+    assert_eq!(cert, CertificateDer::from_pem_file(fixture("first.crt"))?);
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("convd"));
+    assert!(!tls.reload_if_changed()?);
+
+    // 替换证书文件后, 新连接使用新证书
+    std::fs::copy(fixture("second.crt"), &config.cert)?;
+    std::fs::copy(fixture("second.key"), &config.key)?;
+    assert!(tls.reload_if_changed()?);
+    let (cert, _) = fetch(addr, &fixture("second.crt")).await?;
+    assert_eq!(cert, CertificateDer::from_pem_file(fixture("second.crt"))?);
+
+    // 损坏的证书不会替换正在使用的证书
+    std::fs::write(&config.cert, "not a certificate")?;
+    assert!(tls.reload_if_changed().is_err());
+    let (cert, _) = fetch(addr, &fixture("second.crt")).await?;
+    assert_eq!(cert, CertificateDer::from_pem_file(fixture("second.crt"))?);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_serve_tls_with_connect_info() -> color_eyre::Result<()> {
+    init_test!();
+    let tls = TlsTerminator::new(TlsConfig {
+        cert: fixture("first.crt"),
+        key: fixture("first.key"),
+        reload_interval: 1,
+    })?;
+    let addr = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+    let app = Router::new().route(
+        "/",
+        get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }),
+    );
+    let stop = CancellationToken::new();
+    let handle = serve(&ListenAddr::Tcp(addr), app, Some(&tls), stop.clone())?;
+
+    let (_, response) = fetch(addr, &fixture("first.crt")).await?;
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("127.0.0.1"));
+
+    stop.cancel();
+    handle.await??;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_serve_unix_refuses_to_remove_regular_file() -> color_eyre::Result<()> {
+    init_test!();
+    let path = std::env::temp_dir().join(format!("convd-{}.sock", uuid::Uuid::new_v4()));
+    std::fs::write(&path, "not a socket")?;
+    let listen_addr = ListenAddr::Unix(path.clone());
+    assert!(serve(&listen_addr, Router::new(), None, CancellationToken::new()).is_err());
+    assert_eq!(std::fs::read_to_string(&path)?, "not a socket");
+    std::fs::remove_file(&path)?;
+
+    // 遗留的套接字文件会被清理后重新绑定
+    drop(std::os::unix::net::UnixListener::bind(&path)?);
+    let stop = CancellationToken::new();
+    let handle = serve(&listen_addr, Router::new(), None, stop.clone())?;
+    stop.cancel();
+    handle.await??;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_listen_addr() -> color_eyre::Result<()> {
+    assert_eq!("127.0.0.1:8080".parse::<ListenAddr>()?, ListenAddr::Tcp("127.0.0.1:8080".parse()?));
+    assert_eq!("[::]:8080".parse::<ListenAddr>()?, ListenAddr::Tcp("[::]:8080".parse()?));
+    let unix = "unix:/run/convd.sock".parse::<ListenAddr>()?;
+    assert_eq!(unix, ListenAddr::Unix(PathBuf::from("/run/convd.sock")));
+    assert_eq!(unix.to_string(), "unix:/run/convd.sock");
+    assert!("localhost".parse::<ListenAddr>().is_err());
+    Ok(())
+}
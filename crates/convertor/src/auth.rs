@@ -0,0 +1,109 @@
+use crate::error::AuthError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64URL;
+use rand_core::{OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+pub mod user_store;
+
+const TOKEN_PREFIX: &str = "cvt_";
+
+/// convd 的用户, 通过令牌访问允许使用的订阅
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    /// 允许使用的订阅地址, 为空时允许使用全部订阅
+    #[serde(default)]
+    pub allowed_subscriptions: Vec<Url>,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: String,
+    /// 令牌的 sha256, 不保存令牌明文
+    pub hash: String,
+    /// Unix 时间戳(秒)
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+}
+
+/// 新签发的令牌, 明文只会在签发时返回一次
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub user: String,
+    pub id: String,
+    pub token: String,
+}
+
+impl User {
+    pub fn new(name: impl Into<String>, allowed_subscriptions: Vec<Url>) -> Self {
+        Self {
+            name: name.into(),
+            allowed_subscriptions,
+            tokens: vec![],
+        }
+    }
+
+    pub fn allows(&self, sub_url: &Url) -> bool {
+        self.allowed_subscriptions.is_empty() || self.allowed_subscriptions.contains(sub_url)
+    }
+
+    pub fn find_token(&self, token: &str) -> Option<&ApiToken> {
+        let hash = hash_token(token);
+        self.tokens.iter().find(|t| t.hash == hash)
+    }
+
+    /// 签发新令牌并返回明文
+    pub fn issue_token(&mut self) -> Result<IssuedToken, AuthError> {
+        let mut bytes = [0u8; 32];
+        OsRng.try_fill_bytes(&mut bytes)?;
+        let token = format!("{TOKEN_PREFIX}{}", B64URL.encode(bytes));
+        let hash = hash_token(&token);
+        let id = hash[..12].to_string();
+        self.tokens.push(ApiToken {
+            id: id.clone(),
+            hash,
+            created_at: now_secs(),
+            revoked_at: None,
+        });
+        Ok(IssuedToken {
+            user: self.name.clone(),
+            id,
+            token,
+        })
+    }
+
+    /// 吊销令牌, 令牌不存在或已被吊销时返回 false
+    pub fn revoke_token(&mut self, id: &str) -> bool {
+        match self.tokens.iter_mut().find(|t| t.id == id && t.revoked_at.is_none()) {
+            Some(token) => {
+                token.revoked_at = Some(now_secs());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl ApiToken {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
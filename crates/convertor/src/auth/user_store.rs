@@ -0,0 +1,224 @@
+use crate::auth::{IssuedToken, User};
+use crate::common::cache::CACHED_AUTH_TOKEN_KEY;
+use crate::config::auth_config::{AuthBackend, AuthConfig};
+use crate::error::{AuthError, CacheStoreError};
+use redis::aio::ConnectionManager;
+use redis::{AsyncTypedCommands, Script};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+use tokio::sync::Mutex;
+use tracing::warn;
+use url::Url;
+
+type Result<T> = core::result::Result<T, AuthError>;
+
+// 仅当内容仍是读取时的值才写入, 多个实例并发修改时由调用方重新读取后重试
+// KEYS: 用户 json 的键
+// ARGV: 读取时的内容(不存在时为空串), 新内容
+static COMPARE_AND_SET_SCRIPT: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r#"
+local current = redis.call('GET', KEYS[1]) or ''
+if current ~= ARGV[1] then
+  return 0
+end
+redis.call('SET', KEYS[1], ARGV[2])
+return 1
+"#,
+    )
+});
+
+/// 用户与令牌的存储, 所有用户整体保存为一份 json
+#[derive(Clone)]
+pub struct UserStore {
+    backend: UserStoreBackend,
+    // 串行化本进程内的读-改-写, Redis 后端另外通过比较后写入防止多个实例之间丢失更新
+    lock: Arc<Mutex<()>>,
+}
+
+#[derive(Clone)]
+enum UserStoreBackend {
+    Redis { redis: ConnectionManager, key: String },
+    /// 文件只在首次使用或重载后读取一次, 之后的修改经由 `save` 同步更新
+    File { path: PathBuf, cached: Arc<RwLock<Option<Vec<User>>>> },
+    Memory(Arc<Mutex<Vec<User>>>),
+}
+
+impl UserStore {
+    pub fn new(config: &AuthConfig, redis: Option<ConnectionManager>, prefix: impl AsRef<str>) -> Self {
+        let backend = match (config.backend, redis) {
+            (AuthBackend::Redis, Some(redis)) => UserStoreBackend::Redis {
+                redis,
+                key: format!("{CACHED_AUTH_TOKEN_KEY}:{}", prefix.as_ref()),
+            },
+            (AuthBackend::Redis, None) => {
+                if config.enabled {
+                    warn!("未配置 Redis, 用户与令牌仅保存在内存中, 重启后将丢失");
+                }
+                UserStoreBackend::Memory(Arc::new(Mutex::new(vec![])))
+            }
+            (AuthBackend::File, _) => UserStoreBackend::File {
+                path: config.file.clone(),
+                cached: Arc::new(RwLock::new(None)),
+            },
+        };
+        Self {
+            backend,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub async fn users(&self) -> Result<Vec<User>> {
+        self.load().await
+    }
+
+    /// 丢弃已读取的用户文件, 下次使用时重新读取, 用于配置重载后同步手动修改的文件
+    pub fn invalidate(&self) {
+        if let UserStoreBackend::File { cached, .. } = &self.backend {
+            *cached.write().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    /// 创建用户或更新已有用户允许使用的订阅, 已签发的令牌保持不变
+    pub async fn upsert_user(&self, name: &str, allowed_subscriptions: Vec<Url>) -> Result<User> {
+        self.update(|users| match users.iter_mut().find(|u| u.name == name) {
+            Some(user) => {
+                user.allowed_subscriptions = allowed_subscriptions.clone();
+                Ok(user.clone())
+            }
+            None => {
+                let user = User::new(name, allowed_subscriptions.clone());
+                users.push(user.clone());
+                Ok(user)
+            }
+        })
+        .await
+    }
+
+    pub async fn remove_user(&self, name: &str) -> Result<bool> {
+        self.update(|users| {
+            let len = users.len();
+            users.retain(|u| u.name != name);
+            Ok(users.len() != len)
+        })
+        .await
+    }
+
+    pub async fn issue_token(&self, name: &str) -> Result<IssuedToken> {
+        self.update(|users| {
+            users
+                .iter_mut()
+                .find(|u| u.name == name)
+                .ok_or_else(|| AuthError::UserNotFound(name.to_string()))?
+                .issue_token()
+        })
+        .await
+    }
+
+    pub async fn revoke_token(&self, name: &str, id: &str) -> Result<bool> {
+        self.update(|users| {
+            let user = users
+                .iter_mut()
+                .find(|u| u.name == name)
+                .ok_or_else(|| AuthError::UserNotFound(name.to_string()))?;
+            Ok(user.revoke_token(id))
+        })
+        .await
+    }
+
+    /// 校验令牌是否有效, 以及令牌所属用户能否使用 `sub_url`
+    pub async fn authenticate(&self, token: Option<&str>, sub_url: &Url) -> Result<User> {
+        let token = token.filter(|t| !t.is_empty()).ok_or(AuthError::MissingToken)?;
+        let users = self.load().await?;
+        let (user, api_token) = users
+            .iter()
+            .find_map(|user| user.find_token(token).map(|api_token| (user, api_token)))
+            .ok_or(AuthError::InvalidToken)?;
+        if api_token.is_revoked() {
+            return Err(AuthError::RevokedToken);
+        }
+        if !user.allows(sub_url) {
+            return Err(AuthError::SubscriptionNotAllowed {
+                user: user.name.clone(),
+                sub_url: sub_url.clone(),
+            });
+        }
+        Ok(user.clone())
+    }
+
+    /// 读取后修改再写回, Redis 后端的内容在读取后被其他实例修改时会重新执行 `f`
+    async fn update<R>(&self, mut f: impl FnMut(&mut Vec<User>) -> Result<R>) -> Result<R> {
+        let _guard = self.lock.lock().await;
+        if let UserStoreBackend::Redis { redis, key } = &self.backend {
+            loop {
+                let raw = redis.clone().get(key).await.map_err(CacheStoreError::from)?;
+                let mut users = match &raw {
+                    Some(raw) => serde_json::from_str(raw).map_err(CacheStoreError::from)?,
+                    None => vec![],
+                };
+                let result = f(&mut users)?;
+                let updated = serde_json::to_string(&users).map_err(CacheStoreError::from)?;
+                let written: u8 = COMPARE_AND_SET_SCRIPT
+                    .key(key)
+                    .arg(raw.unwrap_or_default())
+                    .arg(updated)
+                    .invoke_async(&mut redis.clone())
+                    .await
+                    .map_err(CacheStoreError::from)?;
+                if written == 1 {
+                    return Ok(result);
+                }
+            }
+        }
+        let mut users = self.load().await?;
+        let result = f(&mut users)?;
+        self.save(users).await?;
+        Ok(result)
+    }
+
+    async fn load(&self) -> Result<Vec<User>> {
+        let raw = match &self.backend {
+            UserStoreBackend::Redis { redis, key } => redis.clone().get(key).await.map_err(CacheStoreError::from)?,
+            UserStoreBackend::File { path, cached } => {
+                if let Some(users) = cached.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+                    return Ok(users.clone());
+                }
+                let users = match tokio::fs::read_to_string(path).await {
+                    Ok(raw) => serde_json::from_str(&raw).map_err(CacheStoreError::from)?,
+                    Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+                    Err(e) => return Err(CacheStoreError::from(e).into()),
+                };
+                *cached.write().unwrap_or_else(|e| e.into_inner()) = Some(users.clone());
+                return Ok(users);
+            }
+            UserStoreBackend::Memory(users) => return Ok(users.lock().await.clone()),
+        };
+        match raw {
+            Some(raw) => Ok(serde_json::from_str(&raw).map_err(CacheStoreError::from)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn save(&self, users: Vec<User>) -> Result<()> {
+        match &self.backend {
+            UserStoreBackend::Redis { redis, key } => {
+                let raw = serde_json::to_string(&users).map_err(CacheStoreError::from)?;
+                redis.clone().set(key, raw).await.map_err(CacheStoreError::from)?;
+            }
+            UserStoreBackend::File { path, cached } => {
+                let raw = serde_json::to_vec_pretty(&users).map_err(CacheStoreError::from)?;
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await.map_err(CacheStoreError::from)?;
+                }
+                // 先写临时文件再重命名, 避免进程中断时留下不完整的文件
+                let tmp = path.with_extension("json.tmp");
+                tokio::fs::write(&tmp, raw).await.map_err(CacheStoreError::from)?;
+                tokio::fs::rename(&tmp, path).await.map_err(CacheStoreError::from)?;
+                *cached.write().unwrap_or_else(|e| e.into_inner()) = Some(users);
+            }
+            UserStoreBackend::Memory(memory) => *memory.lock().await = users,
+        }
+        Ok(())
+    }
+}
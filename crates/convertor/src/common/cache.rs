@@ -1,18 +1,106 @@
+use crate::common::cache::cache_store::CacheStore;
 use crate::config::proxy_client::ProxyClient;
+use crate::error::CacheStoreError;
 use moka::future::Cache as MokaCache;
-use redis::AsyncTypedCommands;
-use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt::{Debug, Display, Formatter};
 use std::future::Future;
 use std::hash::Hash;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::{debug, error};
+use tokio::time::Instant;
+use tracing::{debug, error, warn};
+
+pub mod cache_store;
 
 pub const CACHED_AUTH_TOKEN_KEY: &str = "cached:auth_token";
 pub const CACHED_PROFILE_KEY: &str = "cached:profile";
 pub const CACHED_SUB_URL_KEY: &str = "cached:sub_url";
 pub const CACHED_SUB_LOGS_KEY: &str = "cached:sub_logs";
+pub const CACHED_SHORT_LINK_KEY: &str = "cached:short_link";
+pub const CACHED_PROFILE_HISTORY_KEY: &str = "cached:profile_history";
+/// 多个实例共享 Redis 时, 通过该频道广播缓存失效消息
+pub const CACHE_INVALIDATION_CHANNEL: &str = "convertor:cache:invalidation";
+/// 通知 convd 重新加载配置, 消息内容为空时重新搜索配置文件, 否则按 toml 解析为完整配置
+pub const CONFIG_RELOAD_CHANNEL: &str = "convertor:config:reload";
+
+/// 上游失败时, 最后一次成功的结果在持久化缓存中保留的时长
+const LAST_GOOD_TTL: Duration = Duration::from_secs(60 * 60 * 24 * 7);
+const LAST_GOOD_SUFFIX: &str = ":last_good";
+
+/// 缓存读取结果, 区分是新鲜数据还是上游失败时回退的旧数据
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Cached<V> {
+    Fresh(V),
+    Stale(V),
+}
+
+impl<V> Cached<V> {
+    pub fn is_stale(&self) -> bool {
+        matches!(self, Cached::Stale(_))
+    }
+
+    pub fn into_inner(self) -> V {
+        match self {
+            Cached::Fresh(value) | Cached::Stale(value) => value,
+        }
+    }
+}
+
+/// 缓存条目的概要信息, 用于管理接口展示
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CacheEntryInfo {
+    pub key: String,
+    pub source: CacheSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheSource {
+    Memory,
+    Redis,
+    File,
+}
+
+impl Display for CacheEntryInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let source = match self.source {
+            CacheSource::Memory => "memory",
+            CacheSource::Redis => "redis",
+            CacheSource::File => "file",
+        };
+        write!(f, "[{source}] {}", self.key)?;
+        if let Some(age) = self.age_secs {
+            write!(f, " | age: {age}s")?;
+        }
+        if let Some(size) = self.size {
+            write!(f, " | size: {size}B")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct CacheEntry<V> {
+    value: V,
+    fetched_at: Instant,
+}
+
+impl<V> CacheEntry<V> {
+    fn new(value: V) -> Self {
+        Self {
+            value,
+            fetched_at: Instant::now(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Cache<K, V>
@@ -20,9 +108,13 @@ where
     K: Hash + Eq + Clone + Debug + Display + Send + Sync + 'static,
     V: Clone + From<String> + ToString + Send + Sync + 'static,
 {
-    memory: MokaCache<CacheKey<K>, V>,
-    redis: Option<ConnectionManager>,
-    redis_tty: Duration,
+    memory: MokaCache<CacheKey<K>, CacheEntry<V>>,
+    // 不设置过期时间, 仅在上游失败时兜底使用
+    last_good: MokaCache<CacheKey<K>, V>,
+    refreshing: Arc<Mutex<HashSet<CacheKey<K>>>>,
+    store: Option<CacheStore>,
+    mem_tty: Duration,
+    store_tty: Duration,
 }
 
 impl<K, V> Cache<K, V>
@@ -30,53 +122,265 @@ where
     K: Hash + Eq + Clone + Debug + Display + Send + Sync + 'static,
     V: Clone + From<String> + ToString + Send + Sync + 'static,
 {
-    pub fn new(redis: Option<ConnectionManager>, capacity: u64, mem_tty: Duration, redis_tty: Duration) -> Self {
+    pub fn new(store: Option<CacheStore>, capacity: u64, mem_tty: Duration, store_tty: Duration) -> Self {
+        // 过期时间以 tokio 的时钟判断, 不使用 moka 的 TTL, 过期条目由容量上限回收
         let memory = moka::future::Cache::builder()
             .max_capacity(capacity)
-            .time_to_live(mem_tty)
+            .support_invalidation_closures()
+            .build();
+        let last_good = moka::future::Cache::builder()
+            .max_capacity(capacity)
+            .support_invalidation_closures()
             .build();
         Self {
             memory,
-            redis,
-            redis_tty,
+            last_good,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            store,
+            mem_tty,
+            store_tty,
         }
     }
 
-    pub async fn try_get_with<F, E>(&self, key: CacheKey<K>, init: F) -> Result<V, Arc<E>>
+    /// 读取缓存, 未命中时调用 `init` 获取
+    /// - `init` 的参数为最后一次成功的结果, 可用于向上游发起条件请求
+    /// - 临近过期时在后台提前刷新, 请求无需等待上游
+    /// - 上游失败时回退到最后一次成功的结果, 并返回 [`Cached::Stale`]
+    pub async fn try_get_with<F, Fut, E>(&self, key: CacheKey<K>, init: F) -> Result<Cached<V>, Arc<E>>
     where
-        F: Future<Output = Result<V, E>>,
+        F: Fn(Option<V>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
         E: Display + Send + Sync + 'static,
     {
-        futures_util::pin_mut!(init);
-        self.memory
-            .try_get_with(key.clone(), async { self.try_get_from_redis(key, init).await })
-            .await
+        if let Some(entry) = self.memory.get(&key).await {
+            let age = entry.fetched_at.elapsed();
+            if age < self.mem_tty {
+                if age >= self.refresh_ahead_after() {
+                    self.spawn_refresh(key, init, entry.value.clone());
+                }
+                return Ok(Cached::Fresh(entry.value));
+            }
+            self.memory.invalidate(&key).await;
+        }
+
+        let result = self
+            .memory
+            .try_get_with(key.clone(), async {
+                self.try_get_from_store(&key, init).await.map(CacheEntry::new)
+            })
+            .await;
+        match result {
+            Ok(entry) => {
+                self.last_good.insert(key, entry.value.clone()).await;
+                Ok(Cached::Fresh(entry.value))
+            }
+            Err(e) => self.fallback_to_last_good(&key, e).await,
+        }
+    }
+
+    /// 跳过缓存直接调用 `init`, 成功后更新缓存, 失败时同样回退到最后一次成功的结果
+    pub async fn refresh<F, Fut, E>(&self, key: CacheKey<K>, init: F) -> Result<Cached<V>, E>
+    where
+        F: FnOnce(Option<V>) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+        E: Display,
+    {
+        let previous = self.get_last_good(&key).await;
+        match init(previous).await {
+            Ok(value) => {
+                self.store(&key, value.clone()).await;
+                Ok(Cached::Fresh(value))
+            }
+            Err(e) => self.fallback_to_last_good(&key, e).await,
+        }
+    }
+
+    /// 列出当前实例内存中的缓存条目, 以及持久化缓存中 `prefix` 下的缓存条目
+    pub async fn entries(&self, prefix: &str) -> Vec<CacheEntryInfo> {
+        let mut entries = self
+            .memory
+            .iter()
+            .filter(|(_, entry)| entry.fetched_at.elapsed() < self.mem_tty)
+            .map(|(key, entry)| CacheEntryInfo {
+                key: key.to_string(),
+                source: CacheSource::Memory,
+                age_secs: Some(entry.fetched_at.elapsed().as_secs()),
+                size: Some(entry.value.to_string().len()),
+            })
+            .collect::<Vec<_>>();
+        let Some(store) = self.store.as_ref() else {
+            return entries;
+        };
+        let keys = match store.keys(&format!("convertor:{prefix}:")).await {
+            Ok(keys) => keys,
+            Err(e) => {
+                error!("无法列出持久化缓存: {}", e);
+                return entries;
+            }
+        };
+        for key in keys.into_iter().filter(|key| !key.ends_with(LAST_GOOD_SUFFIX)) {
+            let (ttl, size) = store.ttl_and_size(&key).await.unwrap_or_default();
+            entries.push(CacheEntryInfo {
+                key,
+                source: store.source(),
+                age_secs: ttl.map(|ttl| self.store_tty.saturating_sub(ttl).as_secs()),
+                size,
+            });
+        }
+        entries
+    }
+
+    /// 仅清理当前实例内存中的缓存, `predicate` 的参数为 [`CacheKey::hash`] 的字符串形式
+    pub fn invalidate_memory_if<P>(&self, predicate: P)
+    where
+        P: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        let predicate = Arc::new(predicate);
+        let memory_predicate = predicate.clone();
+        if let Err(e) = self
+            .memory
+            .invalidate_entries_if(move |key, _| memory_predicate(&key.hash.to_string()))
+        {
+            error!("无法清理内存缓存: {}", e);
+        }
+        if let Err(e) = self.last_good.invalidate_entries_if(move |key, _| predicate(&key.hash.to_string())) {
+            error!("无法清理兜底缓存: {}", e);
+        }
     }
 
-    async fn try_get_from_redis<F, E>(&self, key: CacheKey<K>, init: F) -> Result<V, E>
+    /// 清理内存以及持久化缓存中 `prefix` 下的缓存, 返回删除的持久化缓存数量
+    pub async fn purge_if<P>(&self, prefix: &str, predicate: P) -> Result<usize, CacheStoreError>
     where
-        F: Future<Output = Result<V, E>>,
+        P: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        let predicate = Arc::new(predicate);
+        let memory_predicate = predicate.clone();
+        self.invalidate_memory_if(move |hash| memory_predicate(hash));
+        let Some(store) = self.store.as_ref() else {
+            return Ok(0);
+        };
+        let key_prefix = format!("convertor:{prefix}:");
+        let keys = store
+            .keys(&key_prefix)
+            .await?
+            .into_iter()
+            .filter(|key| {
+                let hash = key.strip_prefix(&key_prefix).unwrap_or(key);
+                predicate(hash.strip_suffix(LAST_GOOD_SUFFIX).unwrap_or(hash))
+            })
+            .collect::<Vec<_>>();
+        store.del(keys).await
+    }
+
+    async fn fallback_to_last_good<E: Display>(&self, key: &CacheKey<K>, error: E) -> Result<Cached<V>, E> {
+        match self.get_last_good(key).await {
+            Some(value) => {
+                warn!("上游获取失败, 使用最后一次成功的缓存: {}: {}", key, error);
+                metrics::counter!("convertor_cache_stale_served_total", "prefix" => key.prefix.clone()).increment(1);
+                Ok(Cached::Stale(value))
+            }
+            None => Err(error),
+        }
+    }
+
+    async fn store(&self, key: &CacheKey<K>, value: V) {
+        self.store_to_persistent(key, &value).await;
+        self.last_good.insert(key.clone(), value.clone()).await;
+        self.memory.insert(key.clone(), CacheEntry::new(value)).await;
+    }
+
+    async fn try_get_from_store<F, Fut, E>(&self, key: &CacheKey<K>, init: F) -> Result<V, E>
+    where
+        F: Fn(Option<V>) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
         E: Display + Send + Sync + 'static,
     {
-        let Some(redis) = self.redis.as_ref() else {
-            return init.await;
+        let Some(store) = self.store.as_ref() else {
+            return init(self.get_last_good(key).await).await;
         };
-        let mut redis = redis.clone();
-        let redis_key = key.as_redis_key();
-        if let Ok(Some(raw)) = redis.get(&redis_key).await {
-            debug!("命中 Redis 缓存: {}", redis_key);
+        let store_key = key.as_redis_key();
+        if let Ok(Some(raw)) = store.get(&store_key).await {
+            debug!("命中持久化缓存: {}", store_key);
             return Ok(V::from(raw));
         }
 
-        let value = init.await?;
+        let value = init(self.get_last_good(key).await).await?;
+        self.store_to_persistent(key, &value).await;
+        Ok(value)
+    }
+
+    async fn store_to_persistent(&self, key: &CacheKey<K>, value: &V) {
+        let Some(store) = self.store.as_ref() else {
+            return;
+        };
         let raw = value.to_string();
+        if let Err(e) = store.set_ex(&key.as_redis_key(), &raw, self.store_tty).await {
+            error!("无法写入持久化缓存: {}", e);
+        }
+        if let Err(e) = store.set_ex(&key.as_last_good_key(), &raw, LAST_GOOD_TTL).await {
+            error!("无法写入兜底缓存: {}", e);
+        }
+    }
 
-        // 将结果存入 Redis
-        if let Err(e) = redis.set_ex(redis_key, raw, self.redis_tty.as_secs()).await {
-            error!("无法将缓存写入 Redis: {}", e);
+    async fn get_last_good(&self, key: &CacheKey<K>) -> Option<V> {
+        if let Some(value) = self.last_good.get(key).await {
+            return Some(value);
+        }
+        match self.store.as_ref()?.get(&key.as_last_good_key()).await {
+            Ok(raw) => raw.map(V::from),
+            Err(e) => {
+                error!("无法读取兜底缓存: {}", e);
+                None
+            }
         }
+    }
 
-        Ok(value)
+    /// 存活超过 TTL 的 80% 后开始后台刷新
+    fn refresh_ahead_after(&self) -> Duration {
+        self.mem_tty.mul_f64(0.8)
+    }
+
+    fn spawn_refresh<F, Fut, E>(&self, key: CacheKey<K>, init: F, previous: V)
+    where
+        F: Fn(Option<V>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
+        E: Display + Send + Sync + 'static,
+    {
+        // 同一个 key 同时只允许一个后台刷新
+        if !self.refreshing.lock().unwrap_or_else(|e| e.into_inner()).insert(key.clone()) {
+            return;
+        }
+        let guard = RefreshGuard {
+            refreshing: self.refreshing.clone(),
+            key: key.clone(),
+        };
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+            match init(Some(previous)).await {
+                Ok(value) => {
+                    debug!("后台刷新缓存成功: {}", key);
+                    cache.store(&key, value).await;
+                    metrics::counter!("convertor_cache_refresh_total", "prefix" => key.prefix.clone(), "result" => "success").increment(1);
+                }
+                Err(e) => {
+                    warn!("后台刷新缓存失败: {}: {}", key, e);
+                    metrics::counter!("convertor_cache_refresh_total", "prefix" => key.prefix.clone(), "result" => "failure").increment(1);
+                }
+            }
+        });
+    }
+}
+
+/// 后台刷新结束时移除标记, 刷新的 future panic 时同样会移除, 避免该 key 之后再也无法刷新
+struct RefreshGuard<K: Hash + Eq + Clone + Display + Send + Sync + 'static> {
+    refreshing: Arc<Mutex<HashSet<CacheKey<K>>>>,
+    key: CacheKey<K>,
+}
+
+impl<K: Hash + Eq + Clone + Display + Send + Sync + 'static> Drop for RefreshGuard<K> {
+    fn drop(&mut self) {
+        self.refreshing.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.key);
     }
 }
 
@@ -117,6 +421,10 @@ where
             client,
         }
     }
+
+    pub fn as_last_good_key(&self) -> String {
+        format!("{}{LAST_GOOD_SUFFIX}", self.as_redis_key())
+    }
 }
 
 impl<H> Display for CacheKey<H>
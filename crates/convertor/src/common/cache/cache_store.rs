@@ -0,0 +1,221 @@
+use crate::common::cache::CacheSource;
+use crate::error::CacheStoreError;
+use redis::AsyncTypedCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Result<T> = core::result::Result<T, CacheStoreError>;
+
+/// 缓存的持久化层, 进程重启后依然可用
+#[derive(Clone)]
+pub enum CacheStore {
+    Redis(ConnectionManager),
+    File(FileStore),
+}
+
+impl CacheStore {
+    pub fn source(&self) -> CacheSource {
+        match self {
+            CacheStore::Redis(_) => CacheSource::Redis,
+            CacheStore::File(_) => CacheSource::File,
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>> {
+        match self {
+            CacheStore::Redis(redis) => Ok(redis.clone().get(key).await?),
+            CacheStore::File(file) => Ok(file.read(key).await?.map(|record| record.value)),
+        }
+    }
+
+    pub async fn set_ex(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        match self {
+            CacheStore::Redis(redis) => Ok(redis.clone().set_ex(key, value, ttl.as_secs()).await?),
+            CacheStore::File(file) => file.write(key, value, ttl).await,
+        }
+    }
+
+    /// 列出以 `prefix` 开头的全部 key
+    pub async fn keys(&self, prefix: &str) -> Result<Vec<String>> {
+        match self {
+            CacheStore::Redis(redis) => scan_keys(&mut redis.clone(), &format!("{prefix}*")).await,
+            CacheStore::File(file) => Ok(file
+                .records()
+                .await?
+                .into_iter()
+                .map(|record| record.key)
+                .filter(|key| key.starts_with(prefix))
+                .collect()),
+        }
+    }
+
+    /// 返回剩余的过期时间与值的大小
+    pub async fn ttl_and_size(&self, key: &str) -> Result<(Option<Duration>, Option<usize>)> {
+        match self {
+            CacheStore::Redis(redis) => {
+                let mut redis = redis.clone();
+                let ttl = redis.ttl(key).await?.raw();
+                let size = redis.strlen(key).await?;
+                Ok(((ttl >= 0).then(|| Duration::from_secs(ttl as u64)), Some(size)))
+            }
+            CacheStore::File(file) => Ok(file
+                .read(key)
+                .await?
+                .map(|record| (Some(record.remaining()), Some(record.value.len())))
+                .unwrap_or_default()),
+        }
+    }
+
+    pub async fn del(&self, keys: Vec<String>) -> Result<usize> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        match self {
+            CacheStore::Redis(redis) => Ok(redis.clone().del(keys).await?),
+            CacheStore::File(file) => {
+                let mut removed = 0;
+                for key in keys {
+                    if file.remove(&key).await? {
+                        removed += 1;
+                    }
+                }
+                Ok(removed)
+            }
+        }
+    }
+}
+
+/// 基于本地目录的缓存, 每个 key 对应一个 json 文件
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileRecord {
+    key: String,
+    value: String,
+    /// 过期时间的 Unix 时间戳(秒)
+    expire_at: u64,
+}
+
+impl FileRecord {
+    fn is_expired(&self) -> bool {
+        self.expire_at <= now_secs()
+    }
+
+    fn remaining(&self) -> Duration {
+        Duration::from_secs(self.expire_at.saturating_sub(now_secs()))
+    }
+}
+
+impl FileStore {
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    // key 中包含订阅链接, 不适合直接作为文件名
+    fn path(&self, key: &str) -> PathBuf {
+        let digest = Sha256::digest(key.as_bytes());
+        self.dir.join(format!("{digest:x}.json"))
+    }
+
+    async fn read(&self, key: &str) -> Result<Option<FileRecord>> {
+        let path = self.path(key);
+        let Some(record) = Self::read_path(&path).await? else {
+            return Ok(None);
+        };
+        if record.key != key {
+            return Ok(None);
+        }
+        if record.is_expired() {
+            remove_file(&path).await?;
+            return Ok(None);
+        }
+        Ok(Some(record))
+    }
+
+    async fn read_path(path: &Path) -> Result<Option<FileRecord>> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write(&self, key: &str, value: &str, ttl: Duration) -> Result<()> {
+        let record = FileRecord {
+            key: key.to_string(),
+            value: value.to_string(),
+            expire_at: now_secs() + ttl.as_secs(),
+        };
+        let path = self.path(key);
+        // 先写临时文件再重命名, 避免进程中断时留下不完整的缓存
+        let tmp = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(&record)?).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &str) -> Result<bool> {
+        remove_file(&self.path(key)).await
+    }
+
+    async fn records(&self) -> Result<Vec<FileRecord>> {
+        let mut records = vec![];
+        let mut entries = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            match Self::read_path(&path).await {
+                Ok(Some(record)) if !record.is_expired() => records.push(record),
+                Ok(Some(_)) => {
+                    remove_file(&path).await?;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("忽略无法读取的缓存文件 {}: {}", path.display(), e),
+            }
+        }
+        Ok(records)
+    }
+}
+
+async fn remove_file(path: &Path) -> Result<bool> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+async fn scan_keys(redis: &mut ConnectionManager, pattern: &str) -> Result<Vec<String>> {
+    let mut keys = vec![];
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(100)
+            .query_async(redis)
+            .await?;
+        keys.extend(batch);
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
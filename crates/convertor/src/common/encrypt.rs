@@ -4,7 +4,10 @@ use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64URL;
 use chacha20poly1305::aead::{Aead, KeyInit};
 use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
 use rand_core::OsRng;
+use hkdf::Hkdf;
+use sha2::Sha256;
 use std::cell::RefCell;
+use subtle::ConstantTimeEq;
 
 type Result<T> = core::result::Result<T, EncryptError>;
 
@@ -55,6 +58,14 @@ fn gen_nonce24() -> Result<[u8; 24]> {
 const NONCE_LEN: usize = 24;
 const NONCE_B64URL_LEN: usize = 32; // 24 bytes -> 32 chars (url-safe, no pad)
 
+// 带版本的密文格式: `v1.<key id>.<nonce><ciphertext>`
+// base64url 字符集不含 `.`, 不会与旧格式混淆
+const TOKEN_VERSION: &str = "v1";
+const KDF_SALT: &[u8] = b"convertor";
+const KDF_INFO_KEY: &[u8] = b"convertor/xchacha20poly1305/v1";
+const KDF_INFO_KEY_ID: &[u8] = b"convertor/key-id/v1";
+
+/// 旧格式使用的密钥: 直接补零或截断到 32 字节, 仅用于解密旧链接
 fn normalize_key(key: &[u8]) -> [u8; 32] {
     let mut normalized = [0u8; 32];
     let len = key.len().min(32);
@@ -62,10 +73,46 @@ fn normalize_key(key: &[u8]) -> [u8; 32] {
     normalized
 }
 
+/// HKDF-SHA256 (RFC 5869), 输出长度固定为一个哈希块
+fn hkdf_sha256(secret: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut okm = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(KDF_SALT), secret)
+        .expand(info, &mut okm)
+        .expect("32 字节不超过 HKDF-SHA256 的输出上限");
+    okm
+}
+
+fn derive_key(secret: &[u8]) -> [u8; 32] {
+    hkdf_sha256(secret, KDF_INFO_KEY)
+}
+
+/// 密钥的标识, 写入密文前缀, 解密时据此选择密钥
+pub fn key_id(secret: &[u8]) -> String {
+    B64URL.encode(&hkdf_sha256(secret, KDF_INFO_KEY_ID)[..6])
+}
+
+/// 密文是否由 `secret` 以当前格式加密
+pub fn is_current_key(secret: &[u8], token: &str) -> bool {
+    match split_versioned(token) {
+        Some((kid, _)) => kid == key_id(secret),
+        None => false,
+    }
+}
+
+/// 常量时间比较密钥, 避免通过响应耗时逐字节猜测
+pub fn secret_eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    a.as_ref().ct_eq(b.as_ref()).into()
+}
+
+fn split_versioned(token: &str) -> Option<(&str, &str)> {
+    let rest = token.strip_prefix(TOKEN_VERSION)?.strip_prefix('.')?;
+    rest.split_once('.')
+}
+
+/// 总是使用当前格式与由 `secret` 派生的密钥加密
 pub fn encrypt(secret: &[u8], plaintext: &str) -> Result<String> {
-    let norm_key = normalize_key(secret);
-    let key = Key::from_slice(&norm_key);
-    let cipher = XChaCha20Poly1305::new(key);
+    let key_bytes = derive_key(secret);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key_bytes));
 
     // 统一从线程局部/OsRng 取 nonce
     let nonce_bytes = gen_nonce24()?;
@@ -75,18 +122,46 @@ pub fn encrypt(secret: &[u8], plaintext: &str) -> Result<String> {
         .encrypt(nonce, plaintext.as_bytes())
         .map_err(|_| EncryptError::Encrypt)?;
 
-    // URL-safe, no padding；不加任何分隔符
-    let mut out = String::with_capacity(NONCE_B64URL_LEN + (ciphertext.len() * 4).div_ceil(3));
+    let kid = key_id(secret);
+    let mut out = String::with_capacity(TOKEN_VERSION.len() + kid.len() + 2 + NONCE_B64URL_LEN + (ciphertext.len() * 4).div_ceil(3));
+    out.push_str(TOKEN_VERSION);
+    out.push('.');
+    out.push_str(&kid);
+    out.push('.');
     out.push_str(&B64URL.encode(nonce));
     out.push_str(&B64URL.encode(ciphertext));
     Ok(out)
 }
 
 pub fn decrypt(secret: &[u8], token: &str) -> Result<String> {
-    if token.len() < NONCE_B64URL_LEN {
+    decrypt_with_any(&[secret], token)
+}
+
+/// 使用当前密钥或任一旧密钥解密
+/// 带版本的密文按 key id 选择密钥, 旧格式的密文依次尝试每个密钥
+pub fn decrypt_with_any<S: AsRef<[u8]>>(secrets: &[S], token: &str) -> Result<String> {
+    if let Some((kid, body)) = split_versioned(token) {
+        let secret = secrets
+            .iter()
+            .find(|s| key_id(s.as_ref()) == kid)
+            .ok_or_else(|| EncryptError::UnknownKeyId(kid.to_string()))?;
+        return decrypt_body(&derive_key(secret.as_ref()), body);
+    }
+    let mut last_error = EncryptError::Decrypt;
+    for secret in secrets {
+        match decrypt_body(&normalize_key(secret.as_ref()), token) {
+            Ok(plaintext) => return Ok(plaintext),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn decrypt_body(key: &[u8; 32], body: &str) -> Result<String> {
+    if body.len() < NONCE_B64URL_LEN {
         return Err(EncryptError::NonceLength);
     }
-    let (nonce_part, ct_part) = token.split_at(NONCE_B64URL_LEN);
+    let (nonce_part, ct_part) = body.split_at(NONCE_B64URL_LEN);
 
     // 先解 nonce
     let nonce_raw = B64URL.decode(nonce_part).map_err(EncryptError::DecodeError)?;
@@ -98,9 +173,7 @@ pub fn decrypt(secret: &[u8], token: &str) -> Result<String> {
     // 再解密文
     let ciphertext = B64URL.decode(ct_part).map_err(EncryptError::DecodeError)?;
 
-    let norm_key = normalize_key(secret);
-    let key = Key::from_slice(&norm_key);
-    let cipher = XChaCha20Poly1305::new(key);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
 
     let plaintext = cipher
         .decrypt(nonce, ciphertext.as_ref())
@@ -113,7 +113,7 @@ pub fn init_log(loki_url: Option<&str>, otlp_grpc: Option<&str>) -> Option<Backg
         #[cfg(not(debug_assertions))]
         let service = "convd";
 
-        println!("初始化日志系统, service: {service}, env: {}", Env::current().name());
+        eprintln!("初始化日志系统, service: {service}, env: {}", Env::current().name());
 
         // 3. loki 日志（可选）
         let loki_layer = loki_url.map(|loki_url| {
@@ -1,9 +1,15 @@
 use crate::common::encrypt::encrypt;
 use crate::common::once::HOME_CONFIG_DIR;
+use crate::config::auth_config::AuthConfig;
+use crate::config::cache_config::CacheConfig;
 use crate::config::config_error::ConfigError;
+use crate::config::health_check_config::HealthCheckConfig;
 use crate::config::proxy_client::ProxyClient;
+use crate::config::rate_limit_config::RateLimitConfig;
 use crate::config::redis_config::RedisConfig;
 use crate::config::subscription_config::SubscriptionConfig;
+use crate::config::tls_config::TlsConfig;
+use crate::config::upstream_proxy_config::UpstreamProxyConfig;
 use crate::url::url_builder::UrlBuilder;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
@@ -13,19 +19,46 @@ use std::str::FromStr;
 use tracing::debug;
 use url::Url;
 
+pub mod auth_config;
+pub mod cache_config;
 pub mod config_error;
+pub mod health_check_config;
 pub mod proxy_client;
+pub mod rate_limit_config;
 pub mod redis_config;
+pub mod retry_config;
 pub mod subscription_config;
+pub mod tls_config;
+pub mod upstream_proxy_config;
 
 type Result<T> = core::result::Result<T, ConfigError>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub secret: String,
+    /// 轮换前使用过的密钥, 仅用于解密已发放的旧链接
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_secrets: Vec<String>,
     pub server: Url,
     pub subscription: SubscriptionConfig,
     pub redis: Option<RedisConfig>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// 全局出站代理, 订阅未单独配置代理时使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<UpstreamProxyConfig>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    /// 配置后 convd 在 TCP 监听地址上直接提供 HTTPS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// 本地 MMDB 国家库, 规则匹配模拟时用于解析 GEOIP 规则
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geoip: Option<PathBuf>,
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
 }
 
 impl Config {
@@ -34,12 +67,21 @@ impl Config {
         let server = Url::parse("http://127.0.0.1:8080").expect("不合法的服务器地址");
         let subscription = SubscriptionConfig::template();
         let redis = Some(RedisConfig::template());
+        let cache = CacheConfig::template();
 
         Config {
             secret,
+            previous_secrets: vec![],
             server,
             subscription,
             redis,
+            cache,
+            proxy: None,
+            auth: AuthConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            tls: None,
+            geoip: None,
+            health_check: HealthCheckConfig::default(),
         }
     }
 
@@ -58,6 +100,26 @@ impl Config {
             vars.extend(redis_vars);
         }
 
+        let cache_vars = self.cache.env_template(format!("{prefix}__CACHE"));
+        vars.extend(cache_vars);
+
+        if let Some(proxy) = &self.proxy {
+            vars.extend(proxy.env_template(format!("{prefix}__PROXY")));
+        }
+
+        vars.extend(self.auth.env_template(format!("{prefix}__AUTH")));
+        vars.extend(self.rate_limit.env_template(format!("{prefix}__RATE_LIMIT")));
+
+        if let Some(tls) = &self.tls {
+            vars.extend(tls.env_template(format!("{prefix}__TLS")));
+        }
+
+        if let Some(geoip) = &self.geoip {
+            vars.push((format!("{prefix}__GEOIP"), geoip.display().to_string()));
+        }
+
+        vars.extend(self.health_check.env_template(format!("{prefix}__HEALTH_CHECK")));
+
         vars
     }
 }
@@ -66,11 +128,43 @@ impl Config {
     pub fn search<'de, T: Deserialize<'de>>(cwd: impl AsRef<Path>, config_path: Option<impl AsRef<Path>>) -> Result<T> {
         let mut builder = config::Config::builder();
 
+        for (path, format) in Self::search_sources(cwd, config_path) {
+            match format {
+                Some(format) => builder = builder.add_source(config::File::from(path).format(format)),
+                None => builder = builder.add_source(config::File::from(path)),
+            }
+        }
+
+        debug!("从环境变量加载配置, 前缀: CONVERTOR__");
+        let builder = builder.add_source(
+            config::Environment::with_prefix("CONVERTOR")
+                .prefix_separator("__")
+                .separator("__")
+                .try_parsing(true),
+        );
+
+        Ok(builder.build()?.try_deserialize()?)
+    }
+
+    /// `search` 会按顺序加载的配置文件, 后加载的覆盖先加载的
+    pub fn search_files(cwd: impl AsRef<Path>, config_path: Option<impl AsRef<Path>>) -> Vec<PathBuf> {
+        Self::search_sources(cwd, config_path)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    fn search_sources(
+        cwd: impl AsRef<Path>,
+        config_path: Option<impl AsRef<Path>>,
+    ) -> Vec<(PathBuf, Option<config::FileFormat>)> {
+        let mut sources = vec![];
+
         // home 目录
         if let Some(Some(files)) = std::env::home_dir().map(|hd| Self::search_dir(hd.join(HOME_CONFIG_DIR))) {
             for (path, format) in files {
                 debug!("从 $HOME 目录加载配置文件: {}, 格式: {:?}", path.display(), format);
-                builder = builder.add_source(config::File::from(path).format(format));
+                sources.push((path, Some(format)));
             }
         }
 
@@ -78,25 +172,17 @@ impl Config {
         if let Some(files) = Self::search_dir(&cwd) {
             for (path, format) in files {
                 debug!("从工作目录加载配置文件: {}, 格式: {:?}", path.display(), format);
-                builder = builder.add_source(config::File::from(path).format(format));
+                sources.push((path, Some(format)));
             }
         }
 
         // 命令行参数
         if let Some(path) = config_path.map(|p| p.as_ref().to_path_buf()) {
             debug!("从命令行参数加载配置文件: {}", path.display());
-            builder = builder.add_source(config::File::from(path));
+            sources.push((path, None));
         }
 
-        debug!("从环境变量加载配置, 前缀: CONVERTOR__");
-        let builder = builder.add_source(
-            config::Environment::with_prefix("CONVERTOR")
-                .prefix_separator("__")
-                .separator("__")
-                .try_parsing(true),
-        );
-
-        Ok(builder.build()?.try_deserialize()?)
+        sources
     }
 
     fn search_dir(dir: impl AsRef<Path>) -> Option<Vec<(PathBuf, config::FileFormat)>> {
@@ -145,6 +231,37 @@ impl Config {
         Ok(config)
     }
 
+    /// 检查配置能否用于启动服务, 热重载时校验失败的配置会被拒绝
+    pub fn validate(&self) -> Result<()> {
+        if self.secret.is_empty() {
+            return Err(ConfigError::Invalid("secret 不能为空".to_string()));
+        }
+        if self.previous_secrets.iter().any(String::is_empty) {
+            return Err(ConfigError::Invalid("previous_secrets 中不能包含空字符串".to_string()));
+        }
+        if !matches!(self.server.scheme(), "http" | "https") || self.server.host().is_none() {
+            return Err(ConfigError::Invalid(format!("server 不是合法的 http(s) 地址: {}", self.server)));
+        }
+        for (name, bucket) in [
+            ("per_ip", &self.rate_limit.per_ip),
+            ("per_subscription", &self.rate_limit.per_subscription),
+        ] {
+            // 容量为 0 的令牌桶永远拒绝请求, 不限流应将 per_minute 设为 0
+            if bucket.burst == 0 {
+                return Err(ConfigError::Invalid(format!("rate_limit.{name}.burst 不能为 0")));
+            }
+        }
+        if let Some(tls) = &self.tls {
+            for path in [&tls.cert, &tls.key] {
+                if !path.is_file() {
+                    return Err(ConfigError::NotFile(path.clone()));
+                }
+            }
+        }
+        self.enc_secret()?;
+        Ok(())
+    }
+
     pub fn enc_secret(&self) -> Result<String> {
         Ok(encrypt(self.secret.as_bytes(), &self.secret)?)
     }
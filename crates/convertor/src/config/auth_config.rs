@@ -0,0 +1,59 @@
+use crate::common::once::HOME_CONFIG_DIR;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// convd 的用户与令牌鉴权
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// 开启后订阅链接必须携带有效的用户令牌
+    #[serde(default)]
+    pub enabled: bool,
+    /// 用户数据的存储位置, 未配置 Redis 时 `redis` 仅保存在内存中
+    #[serde(default)]
+    pub backend: AuthBackend,
+    /// `file` 后端的用户数据文件
+    #[serde(default = "default_users_file")]
+    pub file: PathBuf,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthBackend {
+    #[default]
+    Redis,
+    File,
+}
+
+impl AuthConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        let backend = match self.backend {
+            AuthBackend::Redis => "redis",
+            AuthBackend::File => "file",
+        };
+        vec![
+            (format!("{prefix}__ENABLED"), self.enabled.to_string()),
+            (format!("{prefix}__BACKEND"), backend.to_string()),
+            (format!("{prefix}__FILE"), self.file.display().to_string()),
+        ]
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: AuthBackend::default(),
+            file: default_users_file(),
+        }
+    }
+}
+
+fn default_users_file() -> PathBuf {
+    std::env::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(HOME_CONFIG_DIR)
+        .join("users.json")
+}
@@ -0,0 +1,196 @@
+use crate::common::cache::cache_store::{CacheStore, FileStore};
+use crate::common::once::HOME_CONFIG_DIR;
+use crate::error::CacheStoreError;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// 持久化缓存的后端, 未配置 Redis 时 `redis` 等同于 `memory`
+    #[serde(default)]
+    pub backend: CacheBackend,
+    /// `file` 后端的缓存目录
+    #[serde(default = "default_cache_dir")]
+    pub dir: PathBuf,
+    /// 订阅商原始配置的缓存
+    #[serde(default)]
+    pub subscription: SubscriptionCacheConfig,
+    /// 转换后配置的缓存, 仅保存在内存中
+    #[serde(default)]
+    pub profile: ProfileCacheConfig,
+    /// 订阅请求日志, 有 Redis 时保存在 Redis 中, 否则仅保存在内存中
+    #[serde(default)]
+    pub sub_logs: SubLogsConfig,
+}
+
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    #[default]
+    Redis,
+    File,
+    Memory,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct SubscriptionCacheConfig {
+    #[serde(default = "default_subscription_capacity")]
+    pub capacity: u64,
+    /// 内存缓存的过期时间, 单位秒
+    #[serde(default = "default_memory_ttl")]
+    pub memory_ttl: u64,
+    /// Redis / 文件缓存的过期时间, 单位秒
+    #[serde(default = "default_persistent_ttl")]
+    pub persistent_ttl: u64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct ProfileCacheConfig {
+    #[serde(default = "default_profile_capacity")]
+    pub capacity: u64,
+    /// 单位秒
+    #[serde(default = "default_profile_ttl")]
+    pub ttl: u64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct SubLogsConfig {
+    /// 最多保留的日志条数, 超出后丢弃最早的日志
+    #[serde(default = "default_sub_logs_retention")]
+    pub retention: usize,
+    /// 是否解析订阅内容以记录节点数量与规则诊断, 会额外解析一次订阅, 默认关闭
+    #[serde(default)]
+    pub analyze: bool,
+}
+
+impl CacheConfig {
+    pub fn template() -> Self {
+        Self::default()
+    }
+
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        let backend = match self.backend {
+            CacheBackend::Redis => "redis",
+            CacheBackend::File => "file",
+            CacheBackend::Memory => "memory",
+        };
+        vec![
+            (format!("{prefix}__BACKEND"), backend.to_string()),
+            (format!("{prefix}__DIR"), self.dir.display().to_string()),
+            (format!("{prefix}__SUBSCRIPTION__CAPACITY"), self.subscription.capacity.to_string()),
+            (format!("{prefix}__SUBSCRIPTION__MEMORY_TTL"), self.subscription.memory_ttl.to_string()),
+            (format!("{prefix}__SUBSCRIPTION__PERSISTENT_TTL"), self.subscription.persistent_ttl.to_string()),
+            (format!("{prefix}__PROFILE__CAPACITY"), self.profile.capacity.to_string()),
+            (format!("{prefix}__PROFILE__TTL"), self.profile.ttl.to_string()),
+            (format!("{prefix}__SUB_LOGS__RETENTION"), self.sub_logs.retention.to_string()),
+            (format!("{prefix}__SUB_LOGS__ANALYZE"), self.sub_logs.analyze.to_string()),
+        ]
+    }
+
+    /// 按配置的后端构建持久化缓存, `memory` 后端或未提供 Redis 连接时返回 None
+    pub fn build_store(&self, redis: Option<ConnectionManager>) -> Result<Option<CacheStore>, CacheStoreError> {
+        match self.backend {
+            CacheBackend::Redis => Ok(redis.map(CacheStore::Redis)),
+            CacheBackend::File => Ok(Some(CacheStore::File(FileStore::new(&self.dir)?))),
+            CacheBackend::Memory => Ok(None),
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::default(),
+            dir: default_cache_dir(),
+            subscription: SubscriptionCacheConfig::default(),
+            profile: ProfileCacheConfig::default(),
+            sub_logs: SubLogsConfig::default(),
+        }
+    }
+}
+
+impl SubscriptionCacheConfig {
+    pub fn memory_ttl(&self) -> Duration {
+        Duration::from_secs(self.memory_ttl)
+    }
+
+    pub fn persistent_ttl(&self) -> Duration {
+        Duration::from_secs(self.persistent_ttl)
+    }
+}
+
+impl Default for SubscriptionCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_subscription_capacity(),
+            memory_ttl: default_memory_ttl(),
+            persistent_ttl: default_persistent_ttl(),
+        }
+    }
+}
+
+impl ProfileCacheConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl)
+    }
+}
+
+impl Default for ProfileCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_profile_capacity(),
+            ttl: default_profile_ttl(),
+        }
+    }
+}
+
+impl Default for SubLogsConfig {
+    fn default() -> Self {
+        Self {
+            retention: default_sub_logs_retention(),
+            analyze: false,
+        }
+    }
+}
+
+fn default_cache_dir() -> PathBuf {
+    std::env::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(HOME_CONFIG_DIR)
+        .join("cache")
+}
+
+fn default_subscription_capacity() -> u64 {
+    10
+}
+
+fn default_memory_ttl() -> u64 {
+    #[cfg(debug_assertions)]
+    return 60 * 60 * 24;
+    #[cfg(not(debug_assertions))]
+    return 60 * 60;
+}
+
+fn default_persistent_ttl() -> u64 {
+    60 * 60 * 12
+}
+
+fn default_profile_capacity() -> u64 {
+    100
+}
+
+fn default_profile_ttl() -> u64 {
+    60 * 60
+}
+
+fn default_sub_logs_retention() -> usize {
+    500
+}
@@ -36,6 +36,9 @@ pub enum ConfigError {
     #[error("创建 UrlBuilder 时发生错误: {0}")]
     UrlBuilderError(#[from] UrlBuilderError),
 
+    #[error("配置不合法: {0}")]
+    Invalid(String),
+
     #[error("多段配置合并错误: {0}")]
     SearchConfigError(#[from] config::ConfigError),
 }
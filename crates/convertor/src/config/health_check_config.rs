@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// convd 后台探测节点可用性, 并据此调整渲染出的配置
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 两轮探测之间的间隔, 单位秒
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// 单个节点的连接超时, 单位毫秒
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// 同时探测的节点数
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// 每个节点保留的探测记录数
+    #[serde(default = "default_history")]
+    pub history: usize,
+    /// 连续失败多少次后视为不可用
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: usize,
+    /// 渲染配置时移除不可用的节点, 否则只在节点前加上注释
+    #[serde(default)]
+    pub exclude_dead: bool,
+    /// 渲染配置时策略组内的节点按延迟从低到高排列
+    #[serde(default = "default_sort_by_latency")]
+    pub sort_by_latency: bool,
+    /// 探测回环、内网与链路本地地址的节点, 默认跳过, 避免订阅借助探测扫描内网端口
+    #[serde(default)]
+    pub allow_private: bool,
+}
+
+impl HealthCheckConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        vec![
+            (format!("{prefix}__ENABLED"), self.enabled.to_string()),
+            (format!("{prefix}__INTERVAL"), self.interval.to_string()),
+            (format!("{prefix}__TIMEOUT"), self.timeout.to_string()),
+            (format!("{prefix}__CONCURRENCY"), self.concurrency.to_string()),
+            (format!("{prefix}__HISTORY"), self.history.to_string()),
+            (format!("{prefix}__FAILURE_THRESHOLD"), self.failure_threshold.to_string()),
+            (format!("{prefix}__EXCLUDE_DEAD"), self.exclude_dead.to_string()),
+            (format!("{prefix}__SORT_BY_LATENCY"), self.sort_by_latency.to_string()),
+            (format!("{prefix}__ALLOW_PRIVATE"), self.allow_private.to_string()),
+        ]
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.max(1))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout.max(1))
+    }
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_interval(),
+            timeout: default_timeout(),
+            concurrency: default_concurrency(),
+            history: default_history(),
+            failure_threshold: default_failure_threshold(),
+            exclude_dead: false,
+            sort_by_latency: default_sort_by_latency(),
+            allow_private: false,
+        }
+    }
+}
+
+fn default_interval() -> u64 {
+    60 * 5
+}
+
+fn default_timeout() -> u64 {
+    3000
+}
+
+fn default_concurrency() -> usize {
+    16
+}
+
+fn default_history() -> usize {
+    20
+}
+
+fn default_failure_threshold() -> usize {
+    2
+}
+
+fn default_sort_by_latency() -> bool {
+    true
+}
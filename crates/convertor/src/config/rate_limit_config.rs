@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// convd 公开接口的限流, 按客户端 IP 与订阅分别使用令牌桶
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_per_ip")]
+    pub per_ip: TokenBucketConfig,
+    #[serde(default = "default_per_subscription")]
+    pub per_subscription: TokenBucketConfig,
+    /// 可信的反向代理地址, 仅信任由这些地址转发的 `X-Forwarded-For`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct TokenBucketConfig {
+    /// 桶的容量, 即允许的突发请求数
+    pub burst: u32,
+    /// 每分钟补充的令牌数
+    pub per_minute: u32,
+}
+
+impl RateLimitConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        let mut vars = vec![(format!("{prefix}__ENABLED"), self.enabled.to_string())];
+        vars.extend(self.per_ip.env_template(format!("{prefix}__PER_IP")));
+        vars.extend(self.per_subscription.env_template(format!("{prefix}__PER_SUBSCRIPTION")));
+        vars
+    }
+}
+
+impl TokenBucketConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        vec![
+            (format!("{prefix}__BURST"), self.burst.to_string()),
+            (format!("{prefix}__PER_MINUTE"), self.per_minute.to_string()),
+        ]
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            per_ip: default_per_ip(),
+            per_subscription: default_per_subscription(),
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+fn default_per_ip() -> TokenBucketConfig {
+    TokenBucketConfig {
+        burst: 60,
+        per_minute: 60,
+    }
+}
+
+fn default_per_subscription() -> TokenBucketConfig {
+    TokenBucketConfig {
+        burst: 30,
+        per_minute: 30,
+    }
+}
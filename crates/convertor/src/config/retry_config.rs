@@ -0,0 +1,89 @@
+use rand_core::{OsRng, TryRngCore};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 请求订阅商时的重试策略
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// 每个订阅地址最多尝试的次数
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    /// 首次重试前的等待时间, 之后每次翻倍, 单位毫秒
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: u64,
+    /// 两次重试之间的最长等待时间, 单位毫秒
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+    /// 单次请求的超时时间, 单位秒
+    #[serde(default = "default_attempt_timeout")]
+    pub attempt_timeout: u64,
+    /// 包括所有镜像与重试在内的总时限, 单位秒
+    #[serde(default = "default_deadline")]
+    pub deadline: u64,
+}
+
+impl RetryConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        vec![
+            (format!("{prefix}__ATTEMPTS"), self.attempts.to_string()),
+            (format!("{prefix}__INITIAL_BACKOFF"), self.initial_backoff.to_string()),
+            (format!("{prefix}__MAX_BACKOFF"), self.max_backoff.to_string()),
+            (format!("{prefix}__ATTEMPT_TIMEOUT"), self.attempt_timeout.to_string()),
+            (format!("{prefix}__DEADLINE"), self.deadline.to_string()),
+        ]
+    }
+
+    /// 第 `attempt` 次失败后的等待时间 (从 1 开始), 在指数退避的基础上加入随机抖动
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .initial_backoff
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        // 保留一半的等待时间, 另一半随机, 避免多个实例同时重试
+        let half = exp / 2;
+        let jitter = OsRng.try_next_u64().map(|n| n % (half + 1)).unwrap_or(0);
+        Duration::from_millis(exp - half + jitter)
+    }
+
+    pub fn attempt_timeout(&self) -> Duration {
+        Duration::from_secs(self.attempt_timeout)
+    }
+
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline)
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_attempts(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+            attempt_timeout: default_attempt_timeout(),
+            deadline: default_deadline(),
+        }
+    }
+}
+
+fn default_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff() -> u64 {
+    300
+}
+
+fn default_max_backoff() -> u64 {
+    5000
+}
+
+fn default_attempt_timeout() -> u64 {
+    15
+}
+
+fn default_deadline() -> u64 {
+    60
+}
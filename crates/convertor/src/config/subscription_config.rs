@@ -1,3 +1,5 @@
+use crate::config::retry_config::RetryConfig;
+use crate::config::upstream_proxy_config::UpstreamProxyConfig;
 use headers::HeaderMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -16,6 +18,20 @@ pub struct SubscriptionConfig {
     pub strict: bool,
     #[serde(default = "Headers::default")]
     pub headers: Headers,
+    /// 是否在 "Subscription Info" 组中插入展示流量与到期信息的占位节点
+    #[serde(default)]
+    pub userinfo_node: bool,
+    /// 订阅商提供的镜像订阅地址, `sub_url` 获取失败时按顺序尝试
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<Url>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// 该订阅使用的出站代理, 优先于全局代理
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<UpstreamProxyConfig>,
+    /// 订阅内容变化时, convd 向该地址 POST 变化详情
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_webhook: Option<Url>,
 }
 
 impl SubscriptionConfig {
@@ -25,6 +41,11 @@ impl SubscriptionConfig {
             interval: 86400,
             strict: true,
             headers: Headers::default(),
+            userinfo_node: false,
+            mirrors: vec![],
+            retry: RetryConfig::default(),
+            proxy: None,
+            diff_webhook: None,
         }
     }
 
@@ -35,6 +56,14 @@ impl SubscriptionConfig {
         vars.push((format!("{prefix}__SUB_URL"), self.sub_url.to_string()));
         vars.push((format!("{prefix}__INTERVAL"), self.interval.to_string()));
         vars.push((format!("{prefix}__STRICT"), self.strict.to_string()));
+        vars.push((format!("{prefix}__USERINFO_NODE"), self.userinfo_node.to_string()));
+        vars.extend(self.retry.env_template(format!("{prefix}__RETRY")));
+        if let Some(proxy) = &self.proxy {
+            vars.extend(proxy.env_template(format!("{prefix}__PROXY")));
+        }
+        if let Some(diff_webhook) = &self.diff_webhook {
+            vars.push((format!("{prefix}__DIFF_WEBHOOK"), diff_webhook.to_string()));
+        }
 
         for (key, value) in self.headers.iter() {
             let env_key = format!("{prefix}__HEADERS__{}", key.replace("-", "_").to_uppercase());
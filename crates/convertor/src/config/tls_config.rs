@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// convd 自行终止 TLS 时使用的证书, 文件变化后会自动重新加载
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM 格式的证书链
+    pub cert: PathBuf,
+    /// PEM 格式的私钥
+    pub key: PathBuf,
+    /// 检查证书文件是否变化的间隔, 单位秒
+    #[serde(default = "default_reload_interval")]
+    pub reload_interval: u64,
+}
+
+impl TlsConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        vec![
+            (format!("{prefix}__CERT"), self.cert.display().to_string()),
+            (format!("{prefix}__KEY"), self.key.display().to_string()),
+            (format!("{prefix}__RELOAD_INTERVAL"), self.reload_interval.to_string()),
+        ]
+    }
+
+    pub fn reload_interval(&self) -> Duration {
+        Duration::from_secs(self.reload_interval.max(1))
+    }
+}
+
+fn default_reload_interval() -> u64 {
+    30
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// 请求订阅商等外部资源时使用的出站代理
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct UpstreamProxyConfig {
+    /// 代理地址, 支持 http / https / socks5 / socks5h
+    pub url: Url,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl UpstreamProxyConfig {
+    pub fn env_template(&self, prefix: impl AsRef<str>) -> Vec<(String, String)> {
+        let prefix = prefix.as_ref();
+        let mut vars = vec![(format!("{prefix}__URL"), self.url.to_string())];
+        if let Some(username) = &self.username {
+            vars.push((format!("{prefix}__USERNAME"), username.clone()));
+        }
+        if let Some(password) = &self.password {
+            vars.push((format!("{prefix}__PASSWORD"), password.clone()));
+        }
+        vars
+    }
+
+    /// 转换为 reqwest 的代理, 认证信息写入代理地址, http 与 socks5 代理均可识别
+    pub fn to_proxy(&self) -> Result<reqwest::Proxy, reqwest::Error> {
+        let mut url = self.url.clone();
+        if let Some(username) = &self.username {
+            // 只有无法作为 base 的地址才会设置失败, 这类地址本身也无法作为代理
+            let _ = url.set_username(username);
+            let _ = url.set_password(self.password.as_deref());
+        }
+        reqwest::Proxy::all(url.as_str())
+    }
+}
@@ -1,4 +1,9 @@
+pub mod diff;
+pub mod geoip;
+pub mod lint;
 pub mod parser;
 pub mod profile;
 pub mod region;
 pub mod renderer;
+pub mod rule_engine;
+pub mod rule_optimizer;
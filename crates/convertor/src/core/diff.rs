@@ -0,0 +1,270 @@
+use crate::config::proxy_client::ProxyClient;
+use crate::core::profile::Profile;
+use crate::core::profile::proxy::Proxy;
+use crate::core::profile::rule::Rule;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 订阅商原始配置中需要比较的部分, 节点的密码等敏感字段只保存摘要
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ProfileSnapshot {
+    pub client: ProxyClient,
+    pub content_hash: String,
+    /// Unix 时间戳(秒)
+    pub fetched_at: u64,
+    pub nodes: BTreeMap<String, NodeSnapshot>,
+    /// 策略组 -> 组内的节点或策略
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// 策略 -> 该策略下的规则
+    pub rules: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    pub r#type: String,
+    pub server: String,
+    pub port: u16,
+    /// 其余字段(含密码)的摘要, 只用于判断节点是否变化
+    pub fingerprint: String,
+}
+
+/// 两次获取之间订阅发生的变化
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ProfileDiff {
+    /// 上一次的订阅内容摘要, 为空表示还没有可比较的历史
+    pub previous_hash: Option<String>,
+    pub current_hash: Option<String>,
+    pub nodes_added: Vec<String>,
+    pub nodes_removed: Vec<String>,
+    pub nodes_changed: Vec<NodeChange>,
+    pub groups_added: Vec<String>,
+    pub groups_removed: Vec<String>,
+    pub groups_changed: Vec<GroupChange>,
+    pub rules: Vec<PolicyRulesChange>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct NodeChange {
+    pub name: String,
+    pub before: NodeSnapshot,
+    pub after: NodeSnapshot,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct GroupChange {
+    pub name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct PolicyRulesChange {
+    pub policy: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ProfileSnapshot {
+    /// 需要在 `convert` 之前调用, 转换后的策略组与规则已经不是订阅商的原始内容
+    pub fn new<P: Profile>(profile: &P, content_hash: impl Into<String>) -> Self {
+        let nodes = profile
+            .proxies()
+            .iter()
+            .map(|proxy| (proxy.name.clone(), NodeSnapshot::from(proxy)))
+            .collect();
+        let groups = profile
+            .proxy_groups()
+            .iter()
+            .map(|group| (group.name.clone(), group.proxies.clone()))
+            .collect();
+        let mut rules = BTreeMap::<String, BTreeSet<String>>::new();
+        for rule in profile.rules() {
+            rules.entry(rule.policy.name.clone()).or_default().insert(rule_key(rule));
+        }
+        Self {
+            client: P::client(),
+            content_hash: content_hash.into(),
+            fetched_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            nodes,
+            groups,
+            rules,
+        }
+    }
+}
+
+impl From<&Proxy> for NodeSnapshot {
+    fn from(proxy: &Proxy) -> Self {
+        let mut hasher = Sha256::new();
+        for field in [
+            Some(proxy.password.as_str()),
+            proxy.cipher.as_deref(),
+            proxy.sni.as_deref(),
+            proxy.udp.map(|v| if v { "udp" } else { "no-udp" }),
+            proxy.tfo.map(|v| if v { "tfo" } else { "no-tfo" }),
+            proxy.skip_cert_verify.map(|v| if v { "skip" } else { "verify" }),
+        ] {
+            hasher.update(field.unwrap_or_default().as_bytes());
+            hasher.update([0]);
+        }
+        Self {
+            r#type: proxy.r#type.clone(),
+            server: proxy.server.clone(),
+            port: proxy.port,
+            fingerprint: format!("{:x}", hasher.finalize())[..16].to_string(),
+        }
+    }
+}
+
+impl ProfileDiff {
+    pub fn between(previous: &ProfileSnapshot, current: &ProfileSnapshot) -> Self {
+        let (nodes_added, nodes_removed, nodes_common) = diff_keys(&previous.nodes, &current.nodes);
+        let nodes_changed = nodes_common
+            .into_iter()
+            .filter(|name| previous.nodes[name] != current.nodes[name])
+            .map(|name| NodeChange {
+                before: previous.nodes[&name].clone(),
+                after: current.nodes[&name].clone(),
+                name,
+            })
+            .collect();
+
+        let (groups_added, groups_removed, groups_common) = diff_keys(&previous.groups, &current.groups);
+        let groups_changed = groups_common
+            .into_iter()
+            .filter_map(|name| {
+                let before = previous.groups[&name].iter().collect::<BTreeSet<_>>();
+                let after = current.groups[&name].iter().collect::<BTreeSet<_>>();
+                let added = after.difference(&before).map(|s| s.to_string()).collect::<Vec<_>>();
+                let removed = before.difference(&after).map(|s| s.to_string()).collect::<Vec<_>>();
+                (!added.is_empty() || !removed.is_empty()).then_some(GroupChange { name, added, removed })
+            })
+            .collect();
+
+        let empty = BTreeSet::new();
+        let policies = previous.rules.keys().chain(current.rules.keys()).collect::<BTreeSet<_>>();
+        let rules = policies
+            .into_iter()
+            .filter_map(|policy| {
+                let before = previous.rules.get(policy).unwrap_or(&empty);
+                let after = current.rules.get(policy).unwrap_or(&empty);
+                let added = after.difference(before).cloned().collect::<Vec<_>>();
+                let removed = before.difference(after).cloned().collect::<Vec<_>>();
+                (!added.is_empty() || !removed.is_empty()).then(|| PolicyRulesChange {
+                    policy: policy.clone(),
+                    added,
+                    removed,
+                })
+            })
+            .collect();
+
+        Self {
+            previous_hash: Some(previous.content_hash.clone()),
+            current_hash: Some(current.content_hash.clone()),
+            nodes_added,
+            nodes_removed,
+            nodes_changed,
+            groups_added,
+            groups_removed,
+            groups_changed,
+            rules,
+        }
+    }
+
+    /// 只有当前快照, 没有可比较的历史
+    pub fn initial(current: &ProfileSnapshot) -> Self {
+        Self {
+            current_hash: Some(current.content_hash.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes_added.is_empty()
+            && self.nodes_removed.is_empty()
+            && self.nodes_changed.is_empty()
+            && self.groups_added.is_empty()
+            && self.groups_removed.is_empty()
+            && self.groups_changed.is_empty()
+            && self.rules.is_empty()
+    }
+}
+
+impl Display for ProfileDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.previous_hash.is_none() {
+            return write!(f, "还没有可比较的历史订阅");
+        }
+        if self.is_empty() {
+            return write!(f, "订阅内容没有变化");
+        }
+        for name in &self.nodes_added {
+            writeln!(f, "+ 节点 {name}")?;
+        }
+        for name in &self.nodes_removed {
+            writeln!(f, "- 节点 {name}")?;
+        }
+        for change in &self.nodes_changed {
+            let (before, after) = (&change.before, &change.after);
+            writeln!(
+                f,
+                "~ 节点 {}: {} {}:{} -> {} {}:{}",
+                change.name, before.r#type, before.server, before.port, after.r#type, after.server, after.port
+            )?;
+        }
+        for name in &self.groups_added {
+            writeln!(f, "+ 策略组 {name}")?;
+        }
+        for name in &self.groups_removed {
+            writeln!(f, "- 策略组 {name}")?;
+        }
+        for change in &self.groups_changed {
+            writeln!(f, "~ 策略组 {}", change.name)?;
+            for name in &change.added {
+                writeln!(f, "    + {name}")?;
+            }
+            for name in &change.removed {
+                writeln!(f, "    - {name}")?;
+            }
+        }
+        for change in &self.rules {
+            writeln!(f, "~ 策略 {} 的规则", change.policy)?;
+            for rule in &change.added {
+                writeln!(f, "    + {rule}")?;
+            }
+            for rule in &change.removed {
+                writeln!(f, "    - {rule}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rule_key(rule: &Rule) -> String {
+    let mut key = rule.rule_type.to_string();
+    if let Some(value) = &rule.value {
+        key.push(',');
+        key.push_str(value);
+    }
+    if let Some(option) = &rule.policy.option {
+        key.push(',');
+        key.push_str(option);
+    }
+    key
+}
+
+/// 返回 (新增的键, 删除的键, 共有的键)
+fn diff_keys<V>(previous: &BTreeMap<String, V>, current: &BTreeMap<String, V>) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let added = current.keys().filter(|k| !previous.contains_key(*k)).cloned().collect();
+    let removed = previous.keys().filter(|k| !current.contains_key(*k)).cloned().collect();
+    let common = current.keys().filter(|k| previous.contains_key(*k)).cloned().collect();
+    (added, removed, common)
+}
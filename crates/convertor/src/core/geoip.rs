@@ -0,0 +1,44 @@
+use crate::error::GeoIpError;
+use maxminddb::{MaxMindDBError, Reader, geoip2};
+use std::net::IpAddr;
+use std::path::Path;
+
+type Result<T> = core::result::Result<T, GeoIpError>;
+
+/// 只读的 MaxMind DB(MMDB) 国家库, 用于解析 GEOIP 规则
+///
+/// 支持 GeoLite2-Country 及兼容格式, IPv4/IPv6 库均可
+#[derive(Debug)]
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(buf: Vec<u8>) -> Result<Self> {
+        let reader = Reader::from_source(buf).map_err(|e| GeoIpError::Invalid(e.to_string()))?;
+        Ok(Self { reader })
+    }
+
+    /// 返回 IP 所属国家的 ISO 代码, 例如 `CN`
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        // IPv4-mapped 地址按 IPv4 查询
+        let ip = ip.to_canonical();
+        let record = match self.reader.lookup::<geoip2::Country>(ip) {
+            Ok(record) => record,
+            Err(MaxMindDBError::AddressNotFoundError(_)) => return None,
+            Err(e) => {
+                tracing::debug!("GeoIP 查询 {} 失败: {}", ip, e);
+                return None;
+            }
+        };
+        record
+            .country
+            .and_then(|country| country.iso_code)
+            .or_else(|| record.registered_country.and_then(|country| country.iso_code))
+            .map(str::to_string)
+    }
+}
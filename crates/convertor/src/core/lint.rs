@@ -0,0 +1,216 @@
+use crate::config::proxy_client::ProxyClient;
+use crate::core::profile::Profile;
+use crate::core::profile::clash_profile::ClashProfile;
+use crate::core::profile::rule::RuleType;
+use crate::core::profile::surge_profile::SurgeProfile;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Surge 与 Clash 内置的策略, 不需要对应的策略组
+const BUILT_IN_POLICIES: [&str; 8] = [
+    "DIRECT",
+    "REJECT",
+    "REJECT-TINYGIF",
+    "REJECT-DROP",
+    "REJECT-NO-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// 现有解析器无法解析
+    Parse,
+    /// 策略组成员既不是节点也不是策略组
+    MissingMember,
+    /// 规则中的策略没有对应的策略组或节点
+    MissingPolicy,
+    EmptyGroup,
+    DuplicateProxy,
+    /// 策略组之间互相引用形成环
+    GroupCycle,
+    /// 缺少兜底的 FINAL/MATCH 规则
+    MissingFinal,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LintIssue {
+    pub level: LintLevel,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+/// 配置检查的结果
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct LintReport {
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    /// 使用现有的解析器解析配置后检查, 解析失败同样作为一条错误
+    pub fn check_content(client: ProxyClient, content: impl Into<String>) -> Self {
+        let content = content.into();
+        let result = match client {
+            ProxyClient::Surge => SurgeProfile::parse(content).map(|profile| Self::check(&profile)),
+            ProxyClient::Clash => ClashProfile::parse(content).map(|profile| Self::check(&profile)),
+        };
+        result.unwrap_or_else(|e| {
+            let mut report = Self::default();
+            report.push(LintLevel::Error, LintKind::Parse, format!("无法解析配置: {e}"));
+            report
+        })
+    }
+
+    /// 检查策略组、节点与规则之间的引用是否完整
+    pub fn check<P: Profile>(profile: &P) -> Self {
+        let mut report = Self::default();
+
+        let mut proxies = HashSet::new();
+        for proxy in profile.proxies() {
+            if !proxies.insert(proxy.name.as_str()) {
+                report.push(LintLevel::Error, LintKind::DuplicateProxy, format!("节点名称重复: {}", proxy.name));
+            }
+        }
+
+        // 策略组中的 `key=value` 是组的参数, 不是成员
+        let groups = profile
+            .proxy_groups()
+            .iter()
+            .map(|group| {
+                let members = group.proxies.iter().filter(|m| !m.contains('=')).map(String::as_str);
+                (group.name.as_str(), members.collect::<Vec<_>>())
+            })
+            .collect::<HashMap<_, _>>();
+        let is_known = |name: &str| proxies.contains(name) || groups.contains_key(name) || is_built_in(name);
+
+        for group in profile.proxy_groups() {
+            let members = &groups[group.name.as_str()];
+            if members.is_empty() {
+                report.push(LintLevel::Error, LintKind::EmptyGroup, format!("策略组 {} 没有任何成员", group.name));
+            }
+            for member in members.iter().filter(|member| !is_known(member)) {
+                report.push(
+                    LintLevel::Error,
+                    LintKind::MissingMember,
+                    format!("策略组 {} 引用了不存在的节点或策略组: {member}", group.name),
+                );
+            }
+        }
+
+        let mut reported = HashSet::new();
+        for rule in profile.rules() {
+            let policy = rule.policy.name.as_str();
+            if !is_known(policy) && reported.insert(policy) {
+                let rule = match &rule.value {
+                    Some(value) => format!("{},{value}", rule.rule_type),
+                    None => rule.rule_type.to_string(),
+                };
+                report.push(
+                    LintLevel::Error,
+                    LintKind::MissingPolicy,
+                    format!("规则 {rule} 使用的策略 {policy} 没有对应的策略组或节点"),
+                );
+            }
+        }
+
+        for cycle in find_cycles(profile, &groups) {
+            report.push(LintLevel::Error, LintKind::GroupCycle, format!("策略组循环引用: {}", cycle.join(" -> ")));
+        }
+
+        if !profile
+            .rules()
+            .iter()
+            .any(|rule| matches!(rule.rule_type, RuleType::Final | RuleType::Match))
+        {
+            // Clash 没有兜底规则时直连, Surge 则拒绝加载配置
+            let level = match P::client() {
+                ProxyClient::Surge => LintLevel::Error,
+                ProxyClient::Clash => LintLevel::Warning,
+            };
+            report.push(level, LintKind::MissingFinal, "缺少 FINAL/MATCH 兜底规则".to_string());
+        }
+        report
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.level == LintLevel::Error)
+    }
+
+    fn push(&mut self, level: LintLevel, kind: LintKind, message: String) {
+        self.issues.push(LintIssue { level, kind, message });
+    }
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintLevel::Warning => write!(f, "警告"),
+            LintLevel::Error => write!(f, "错误"),
+        }
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.issues.is_empty() {
+            return writeln!(f, "配置检查通过");
+        }
+        for issue in &self.issues {
+            writeln!(f, "[{}] {}", issue.level, issue.message)?;
+        }
+        let errors = self.issues.iter().filter(|issue| issue.level == LintLevel::Error).count();
+        writeln!(f, "共 {} 个问题, 其中 {errors} 个错误", self.issues.len())
+    }
+}
+
+fn is_built_in(name: &str) -> bool {
+    BUILT_IN_POLICIES.iter().any(|p| p.eq_ignore_ascii_case(name))
+}
+
+/// 按策略组的定义顺序做深度优先搜索, 每个环只报告一次
+fn find_cycles<P: Profile>(profile: &P, groups: &HashMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        groups: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|n| *n == name) {
+            let mut cycle = path[start..].iter().map(|n| n.to_string()).collect::<Vec<_>>();
+            cycle.push(name.to_string());
+            cycles.push(cycle);
+            return;
+        }
+        if done.contains(name) {
+            return;
+        }
+        path.push(name);
+        for member in groups[name].iter().filter(|m| groups.contains_key(*m)) {
+            visit(member, groups, path, done, cycles);
+        }
+        path.pop();
+        done.insert(name);
+    }
+
+    let mut cycles = vec![];
+    let mut done = HashSet::new();
+    for group in profile.proxy_groups() {
+        visit(group.name.as_str(), groups, &mut vec![], &mut done, &mut cycles);
+    }
+    cycles
+}
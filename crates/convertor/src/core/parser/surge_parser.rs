@@ -2,6 +2,7 @@ use crate::core::profile::policy::Policy;
 use crate::core::profile::proxy::Proxy;
 use crate::core::profile::proxy_group::{ProxyGroup, ProxyGroupType};
 use crate::core::profile::rule::{Rule, RuleType};
+use crate::core::profile::surge_header::SurgeHeader;
 use crate::core::profile::surge_profile::SurgeProfile;
 use crate::error::ParseError;
 use std::collections::HashMap;
@@ -86,13 +87,10 @@ impl SurgeParser {
         sections
     }
 
+    /// 配置段之前的第一条 `#!MANAGED-CONFIG` 即为配置头, 其余内容忽略
     #[instrument(skip_all)]
-    pub fn parse_header(section: impl IntoIterator<Item = impl AsRef<str>>) -> Result<String> {
-        let mut output = String::new();
-        for line in section {
-            writeln!(output, "{}", line.as_ref())?;
-        }
-        Ok(output)
+    pub fn parse_header(section: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Option<SurgeHeader>> {
+        Ok(section.into_iter().find_map(SurgeHeader::parse))
     }
 
     #[instrument(skip_all)]
@@ -4,11 +4,13 @@ use crate::core::profile::proxy::Proxy;
 use crate::core::profile::proxy_group::{ProxyGroup, ProxyGroupType};
 use crate::core::profile::rule::{ProviderRule, Rule};
 use crate::core::region::Region;
+use crate::core::rule_optimizer;
 use crate::error::ParseError;
+use crate::provider::subscription_userinfo::SubscriptionUserinfo;
 use crate::url::url_builder::{HostPort, UrlBuilder};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
-use tracing::{instrument, span, warn};
+use tracing::{debug, info, instrument, span, warn};
 
 pub mod clash_profile;
 pub mod policy;
@@ -21,6 +23,8 @@ pub mod surge_profile;
 
 type Result<T> = core::result::Result<T, ParseError>;
 
+pub const SUBSCRIPTION_INFO_GROUP: &str = "Subscription Info";
+
 pub(super) fn group_by_region(proxies: &[Proxy]) -> (Vec<(&'static Region, Vec<&Proxy>)>, Vec<&Proxy>) {
     let match_number = Regex::new(r"^\d+$").unwrap();
     let mut infos = vec![];
@@ -132,7 +136,7 @@ pub trait Profile {
             })
             .collect::<Vec<_>>();
         let convertor_group = ProxyGroup::new(
-            "Subscription Info".to_string(),
+            SUBSCRIPTION_INFO_GROUP.to_string(),
             ProxyGroupType::Select,
             infos.into_iter().map(|p| p.name.to_string()).collect::<Vec<_>>(),
         );
@@ -168,6 +172,20 @@ pub trait Profile {
             .partition(|rule| rule.is_built_in() || rule.value.is_none());
         drop(_guard);
 
+        let inner_span = span!(tracing::Level::INFO, "优化其它规则");
+        let _guard = inner_span.entered();
+        let (other_rules, report) = rule_optimizer::optimize(other_rules);
+        if report.removed() > 0 {
+            info!("{}", report);
+        }
+        if !report.shadowed.is_empty() {
+            warn!("{} 条规则被更早的规则遮蔽", report.shadowed.len());
+            for shadowed in &report.shadowed {
+                debug!("{}", shadowed);
+            }
+        }
+        drop(_guard);
+
         let inner_span = span!(tracing::Level::INFO, "处理其它规则");
         let _guard = inner_span.entered();
         for mut rule in other_rules {
@@ -223,6 +241,26 @@ pub trait Profile {
 
     fn append_rule_provider(&mut self, url_builder: &UrlBuilder, policy: Policy) -> Result<()>;
 
+    /// 将订阅流量信息作为占位节点插入到 "Subscription Info" 组的最前面
+    #[instrument(skip_all)]
+    fn append_userinfo_proxy(&mut self, userinfo: &SubscriptionUserinfo) {
+        let proxy = Proxy::placeholder(userinfo.summary());
+        let name = proxy.name.clone();
+        self.proxies_mut().insert(0, proxy);
+        match self
+            .proxy_groups_mut()
+            .iter_mut()
+            .find(|group| group.name == SUBSCRIPTION_INFO_GROUP)
+        {
+            Some(group) => group.proxies.insert(0, name),
+            None => self.proxy_groups_mut().push(ProxyGroup::new(
+                SUBSCRIPTION_INFO_GROUP.to_string(),
+                ProxyGroupType::Select,
+                vec![name],
+            )),
+        }
+    }
+
     #[instrument(skip_all)]
     fn get_provider_rules_with_policy(&self, policy: &Policy) -> Option<&Vec<ProviderRule>> {
         self.policy_of_rules().get(policy)
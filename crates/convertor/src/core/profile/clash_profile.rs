@@ -10,7 +10,8 @@ use crate::core::renderer::Renderer;
 use crate::core::renderer::clash_renderer::ClashRenderer;
 use crate::error::ParseError;
 use crate::url::url_builder::UrlBuilder;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde_yaml::Value;
 use std::collections::HashMap;
 use tracing::instrument;
 
@@ -41,7 +42,7 @@ pub struct ClashProfile {
     pub proxy_groups: Vec<ProxyGroup>,
     #[serde(default)]
     pub rules: Vec<Rule>,
-    #[serde(rename = "rule-providers", default)]
+    #[serde(rename = "rule-providers", default, deserialize_with = "deserialize_rule_providers")]
     pub rule_providers: Vec<(String, RuleProvider)>,
     #[serde(default)]
     pub policy_of_rules: HashMap<Policy, Vec<ProviderRule>>,
@@ -49,6 +50,22 @@ pub struct ClashProfile {
     pub sorted_policy_list: Vec<Policy>,
 }
 
+/// mihomo 中的 rule-providers 是以名称为键的映射, 同时兼容 `[名称, 规则集]` 序列
+fn deserialize_rule_providers<'de, D>(deserializer: D) -> core::result::Result<Vec<(String, RuleProvider)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Mapping(providers) => providers
+            .into_iter()
+            .map(|(name, provider)| Ok((serde_yaml::from_value(name)?, serde_yaml::from_value(provider)?)))
+            .collect::<core::result::Result<Vec<_>, serde_yaml::Error>>()
+            .map_err(serde::de::Error::custom),
+        Value::Null => Ok(vec![]),
+        value => serde_yaml::from_value(value).map_err(serde::de::Error::custom),
+    }
+}
+
 impl Profile for ClashProfile {
     type PROFILE = ClashProfile;
 
@@ -119,6 +136,20 @@ impl Profile for ClashProfile {
 }
 
 impl ClashProfile {
+    /// 将由 `from` 生成的规则集链接替换为由 `to` 生成的链接
+    ///
+    /// 转换后的配置以不含用户令牌的 `UrlBuilder` 缓存, 返回给用户前据此补上令牌
+    pub fn relink(&mut self, from: &UrlBuilder, to: &UrlBuilder) -> Result<()> {
+        for policy in &self.sorted_policy_list {
+            let old = from.build_rule_provider_url(policy)?.to_string();
+            let new = to.build_rule_provider_url(policy)?.to_string();
+            for (_, provider) in self.rule_providers.iter_mut().filter(|(_, provider)| provider.url == old) {
+                provider.url = new.clone();
+            }
+        }
+        Ok(())
+    }
+
     #[instrument(skip_all)]
     pub fn parse(content: String) -> Result<Self> {
         ClashParser::parse(content)
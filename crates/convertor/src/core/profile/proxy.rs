@@ -19,6 +19,23 @@ pub struct Proxy {
 }
 
 impl Proxy {
+    /// 仅用于在客户端中展示信息的占位节点, 并不能用于实际代理
+    pub fn placeholder(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            r#type: "ss".to_string(),
+            server: "127.0.0.1".to_string(),
+            port: 1,
+            password: "convertor".to_string(),
+            udp: None,
+            tfo: None,
+            cipher: Some("aes-128-gcm".to_string()),
+            sni: None,
+            skip_cert_verify: None,
+            comment: None,
+        }
+    }
+
     pub fn set_comment(&mut self, comment: Option<String>) {
         self.comment = comment;
     }
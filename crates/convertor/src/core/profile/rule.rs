@@ -75,7 +75,7 @@ impl TryFrom<Rule> for ProviderRule {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
 pub enum RuleType {
     #[serde(rename = "DOMAIN")]
     Domain,
@@ -95,6 +95,8 @@ pub enum RuleType {
     IpCIDR,
     #[serde(rename = "IP-CIDR6")]
     IpCIDR6,
+    #[serde(rename = "DST-PORT")]
+    DstPort,
     #[serde(rename = "FINAL")]
     Final,
     #[serde(rename = "MATCH")]
@@ -113,6 +115,7 @@ impl RuleType {
             RuleType::GeoIP => "GEOIP",
             RuleType::IpCIDR => "IP-CIDR",
             RuleType::IpCIDR6 => "IP-CIDR6",
+            RuleType::DstPort => "DST-PORT",
             RuleType::Final => "FINAL",
             RuleType::Match => "MATCH",
         }
@@ -138,6 +141,7 @@ impl FromStr for RuleType {
             "RULE-SET" => Ok(RuleType::RuleSet),
             "IP-CIDR" => Ok(RuleType::IpCIDR),
             "IP-CIDR6" => Ok(RuleType::IpCIDR6),
+            "DST-PORT" => Ok(RuleType::DstPort),
             "GEOIP" => Ok(RuleType::GeoIP),
             "FINAL" => Ok(RuleType::Final),
             "MATCH" => Ok(RuleType::Match),
@@ -1,32 +1,100 @@
 use crate::url::convertor_url::ConvertorUrl;
 use std::fmt::{Display, Formatter};
+use url::Url;
 
+pub const SURGE_HEADER_SHEBANG: &str = "#!MANAGED-CONFIG";
+
+/// Surge 托管配置的第一行: `#!MANAGED-CONFIG <url> interval=<秒> strict=<bool>`
 #[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
 pub struct SurgeHeader {
     pub shebang: &'static str,
-    pub url: ConvertorUrl,
-    pub interval: u64,
-    pub strict: bool,
+    /// 订阅商提供的配置头中可能没有地址
+    pub url: Option<ConvertorUrl>,
+    pub interval: Option<u64>,
+    pub strict: Option<bool>,
+    /// 无法识别的选项, 按原样保留
+    pub options: Vec<String>,
 }
 
 impl SurgeHeader {
     pub fn new(url: ConvertorUrl, interval: u64, strict: bool) -> Self {
         Self {
-            shebang: "#!MANAGED-CONFIG",
-            url,
-            interval,
-            strict,
+            shebang: SURGE_HEADER_SHEBANG,
+            url: Some(url),
+            interval: Some(interval),
+            strict: Some(strict),
+            options: vec![],
+        }
+    }
+
+    /// 解析配置头, 不是以 `#!MANAGED-CONFIG` 开头时返回 None
+    pub fn parse(line: impl AsRef<str>) -> Option<Self> {
+        let rest = line.as_ref().trim().strip_prefix(SURGE_HEADER_SHEBANG)?;
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let mut header = Self {
+            shebang: SURGE_HEADER_SHEBANG,
+            url: None,
+            interval: None,
+            strict: None,
+            options: vec![],
+        };
+        for token in rest.split_whitespace() {
+            // 地址的查询参数中同样可能有 `=`, 因此先尝试解析为地址
+            if header.url.is_none()
+                && let Ok(url) = Url::parse(token)
+                && matches!(url.scheme(), "http" | "https")
+            {
+                header.url = Some(ConvertorUrl::raw(url));
+                continue;
+            }
+            match token.split_once('=') {
+                Some(("interval", value)) if value.parse::<u64>().is_ok() => header.interval = value.parse().ok(),
+                Some(("strict", value)) if value.parse::<bool>().is_ok() => header.strict = value.parse().ok(),
+                _ => header.options.push(token.to_string()),
+            }
+        }
+        Some(header)
+    }
+
+    /// 以当前的值为准, 补充 `base` 中缺少的字段与未识别的选项
+    ///
+    /// 用于让链接中的参数覆盖订阅商或本地文件中的配置头, 同时保留其余的选项
+    pub fn inherit(mut self, base: &SurgeHeader) -> Self {
+        self.url = self.url.or_else(|| base.url.clone());
+        self.interval = self.interval.or(base.interval);
+        self.strict = self.strict.or(base.strict);
+        for option in &base.options {
+            let key = option_key(option);
+            if !self.options.iter().any(|o| option_key(o) == key) {
+                self.options.push(option.clone());
+            }
         }
+        self
     }
 }
 
+fn option_key(option: &str) -> &str {
+    option.split_once('=').map(|(key, _)| key).unwrap_or(option)
+}
+
 impl Display for SurgeHeader {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} {} interval={} strict={}",
-            self.shebang, self.url, self.interval, self.strict
-        )
+        write!(f, "{}", self.shebang)?;
+        if let Some(url) = &self.url {
+            write!(f, " {url}")?;
+        }
+        if let Some(interval) = self.interval {
+            write!(f, " interval={interval}")?;
+        }
+        if let Some(strict) = self.strict {
+            write!(f, " strict={strict}")?;
+        }
+        for option in &self.options {
+            write!(f, " {option}")?;
+        }
+        Ok(())
     }
 }
@@ -5,6 +5,7 @@ use crate::core::profile::policy::Policy;
 use crate::core::profile::proxy::Proxy;
 use crate::core::profile::proxy_group::ProxyGroup;
 use crate::core::profile::rule::{ProviderRule, Rule};
+use crate::core::profile::surge_header::SurgeHeader;
 use crate::core::renderer::Renderer;
 use crate::core::renderer::surge_renderer::SurgeRenderer;
 use crate::error::ParseError;
@@ -17,7 +18,8 @@ type Result<T> = core::result::Result<T, ParseError>;
 
 #[derive(Debug, Clone)]
 pub struct SurgeProfile {
-    pub header: String,
+    /// 订阅商提供的托管配置头, 转换后替换为指向 convd 的配置头
+    pub header: Option<SurgeHeader>,
     pub general: Vec<String>,
     pub proxies: Vec<Proxy>,
     pub proxy_groups: Vec<ProxyGroup>,
@@ -100,9 +102,29 @@ impl Profile for SurgeProfile {
 }
 
 impl SurgeProfile {
+    /// 将由 `from` 生成的 convertor 链接替换为由 `to` 生成的链接
+    ///
+    /// 转换后的配置以不含用户令牌的 `UrlBuilder` 缓存, 返回给用户前据此补上令牌
+    pub fn relink(&mut self, from: &UrlBuilder, to: &UrlBuilder) -> Result<()> {
+        self.replace_header(to)?;
+        for policy in &self.sorted_policy_list {
+            let old = from.build_rule_provider_url(policy)?.to_string();
+            let new = to.build_rule_provider_url(policy)?.to_string();
+            for rule in self.rules.iter_mut().filter(|rule| rule.value.as_deref() == Some(old.as_str())) {
+                rule.value = Some(new.clone());
+            }
+        }
+        Ok(())
+    }
+
     #[instrument(skip_all)]
     fn replace_header(&mut self, url_builder: &UrlBuilder) -> Result<()> {
-        self.header = url_builder.build_surge_header(UrlType::Profile)?.to_string();
+        // 链接中的 interval 与 strict 优先, 订阅商配置头中的其它选项保留
+        let header = url_builder.build_surge_header(UrlType::Profile)?;
+        self.header = Some(match &self.header {
+            Some(base) => header.inherit(base),
+            None => header,
+        });
         Ok(())
     }
 }
@@ -12,6 +12,9 @@ use tracing::instrument;
 
 type Result<T> = core::result::Result<T, RenderError>;
 
+pub const CLASH_RULE_COMMENT_START: &str = "# Rules from convertor";
+pub const CLASH_RULE_COMMENT_END: &str = "# End of Rules";
+
 pub struct ClashRenderer;
 
 impl Renderer for ClashRenderer {
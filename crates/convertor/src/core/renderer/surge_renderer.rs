@@ -28,8 +28,10 @@ impl Renderer for SurgeRenderer {
         let mut output = String::new();
 
         let header = Self::render_header(profile)?;
-        writeln!(output, "{}", header.trim())?;
-        writeln!(output)?;
+        if !header.is_empty() {
+            writeln!(output, "{header}")?;
+            writeln!(output)?;
+        }
 
         let general = Self::render_general(profile)?;
         writeln!(output, "[General]")?;
@@ -164,7 +166,7 @@ impl Renderer for SurgeRenderer {
 impl SurgeRenderer {
     #[instrument(skip_all)]
     pub fn render_header(profile: &SurgeProfile) -> Result<String> {
-        Ok(profile.header.to_string())
+        Ok(profile.header.as_ref().map(ToString::to_string).unwrap_or_default())
     }
 
     #[instrument(skip_all)]
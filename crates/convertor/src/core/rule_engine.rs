@@ -0,0 +1,222 @@
+use crate::core::geoip::GeoIp;
+use crate::core::profile::Profile;
+use crate::core::profile::policy::Policy;
+use crate::core::profile::proxy_group::ProxyGroup;
+use crate::core::profile::rule::{ProviderRule, Rule, RuleType};
+use ipnet::IpNet;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+/// 一次连接的特征, 未提供的字段不会命中对应类型的规则
+///
+/// 只提供域名时不会进行 DNS 解析, IP 类规则与 GEOIP 规则不会命中
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MatchQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+}
+
+/// 模拟匹配的结果
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MatchResult {
+    /// 命中的规则, 为空表示没有任何规则命中
+    pub rule: Option<String>,
+    /// 命中的规则所在的规则集
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_set: Option<String>,
+    pub policy: Option<String>,
+    /// 策略 -> 策略组 -> 节点, 每一级取策略组的第一个成员作为默认选择
+    pub chain: Vec<String>,
+    /// 不是由 convd 提供的规则集, 无法展开, 匹配时被跳过
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// 在转换后的配置上按顺序模拟规则匹配, 包括 convd 提供的规则集中的规则
+pub struct RuleEngine<'a> {
+    rules: &'a [Rule],
+    policy_of_rules: &'a HashMap<Policy, Vec<ProviderRule>>,
+    proxy_groups: &'a [ProxyGroup],
+    geoip: Option<&'a GeoIp>,
+}
+
+impl MatchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.domain.is_none()
+            && self.ip.is_none()
+            && self.port.is_none()
+            && self.process.is_none()
+            && self.user_agent.is_none()
+    }
+}
+
+impl<'a> RuleEngine<'a> {
+    pub fn new<P: Profile>(profile: &'a P) -> Self {
+        Self {
+            rules: profile.rules(),
+            policy_of_rules: profile.policy_of_rules(),
+            proxy_groups: profile.proxy_groups(),
+            geoip: None,
+        }
+    }
+
+    /// 没有提供 MMDB 文件时 GEOIP 规则不会命中
+    pub fn with_geoip(mut self, geoip: Option<&'a GeoIp>) -> Self {
+        self.geoip = geoip;
+        self
+    }
+
+    pub fn evaluate(&self, query: &MatchQuery) -> MatchResult {
+        // 域名不区分大小写, 末尾的点表示根域
+        let mut query = query.clone();
+        query.domain = query.domain.map(|d| d.trim().trim_end_matches('.').to_ascii_lowercase());
+        let mut result = MatchResult::default();
+        for rule in self.rules {
+            let Some(value) = rule.value.as_deref() else {
+                // FINAL 与 MATCH 没有值, 总是命中
+                if matches!(rule.rule_type, RuleType::Final | RuleType::Match) {
+                    result.rule = Some(rule.rule_type.to_string());
+                    self.resolve(&mut result, &rule.policy);
+                    return result;
+                }
+                continue;
+            };
+            if rule.rule_type == RuleType::RuleSet {
+                let Some(provider_rules) = self.policy_of_rules.get(&rule.policy) else {
+                    result.skipped.push(value.to_string());
+                    continue;
+                };
+                let matched = provider_rules
+                    .iter()
+                    .find(|provider_rule| self.matches(&provider_rule.rule_type, &provider_rule.value, &query));
+                if let Some(provider_rule) = matched {
+                    result.rule = Some(format!("{},{}", provider_rule.rule_type, provider_rule.value));
+                    result.rule_set = Some(value.to_string());
+                    self.resolve(&mut result, &rule.policy);
+                    return result;
+                }
+            } else if self.matches(&rule.rule_type, value, &query) {
+                result.rule = Some(format!("{},{}", rule.rule_type, value));
+                self.resolve(&mut result, &rule.policy);
+                return result;
+            }
+        }
+        result
+    }
+
+    fn resolve(&self, result: &mut MatchResult, policy: &Policy) {
+        result.policy = Some(policy.name.clone());
+        result.chain = vec![policy.name.clone()];
+        let mut current = policy.name.as_str();
+        while let Some(group) = self.proxy_groups.iter().find(|group| group.name == current) {
+            let Some(next) = group.proxies.first() else {
+                break;
+            };
+            if result.chain.contains(next) {
+                break;
+            }
+            result.chain.push(next.clone());
+            current = next;
+        }
+    }
+
+    fn matches(&self, rule_type: &RuleType, value: &str, query: &MatchQuery) -> bool {
+        let value = value.trim();
+        match rule_type {
+            RuleType::Domain => query.domain.as_deref().is_some_and(|d| d.eq_ignore_ascii_case(value)),
+            RuleType::DomainSuffix => query.domain.as_deref().is_some_and(|d| {
+                let suffix = value.trim_start_matches('.').to_ascii_lowercase();
+                d == suffix || d.ends_with(&format!(".{suffix}"))
+            }),
+            RuleType::DomainKeyword => query
+                .domain
+                .as_deref()
+                .is_some_and(|d| d.contains(&value.to_ascii_lowercase())),
+            RuleType::IpCIDR | RuleType::IpCIDR6 => match (query.ip, value.parse::<IpNet>()) {
+                (Some(ip), Ok(net)) => net.contains(&ip),
+                _ => false,
+            },
+            RuleType::GeoIP => match (query.ip, self.geoip) {
+                (Some(ip), Some(geoip)) => geoip.country(ip).is_some_and(|c| c.eq_ignore_ascii_case(value)),
+                _ => false,
+            },
+            RuleType::DstPort => query.port.is_some_and(|port| match value.split_once('-') {
+                Some((start, end)) => match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+                    (Ok(start), Ok(end)) => (start..=end).contains(&port),
+                    _ => false,
+                },
+                None => value.parse::<u16>() == Ok(port),
+            }),
+            RuleType::ProcessName => query.process.as_deref().is_some_and(|process| {
+                // 规则可以写进程名或完整路径
+                let name = process.rsplit(['/', '\\']).next().unwrap_or(process);
+                process.eq_ignore_ascii_case(value) || name.eq_ignore_ascii_case(value)
+            }),
+            RuleType::UserAgent => query.user_agent.as_deref().is_some_and(|ua| {
+                let pattern = percent_decode_str(value).decode_utf8_lossy();
+                wildcard_match(&pattern, ua)
+            }),
+            RuleType::Final | RuleType::Match => true,
+            RuleType::RuleSet => false,
+        }
+    }
+}
+
+impl Display for MatchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.rule, &self.rule_set) {
+            (None, _) => writeln!(f, "没有命中任何规则")?,
+            (Some(rule), None) => writeln!(f, "命中规则: {rule}")?,
+            (Some(rule), Some(rule_set)) => writeln!(f, "命中规则: {rule}\n所在规则集: {rule_set}")?,
+        }
+        if !self.chain.is_empty() {
+            writeln!(f, "策略链: {}", self.chain.join(" -> "))?;
+        }
+        for rule_set in &self.skipped {
+            writeln!(f, "跳过无法展开的规则集: {rule_set}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 支持 `*` 与 `?` 的通配符匹配
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
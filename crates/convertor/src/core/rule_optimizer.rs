@@ -0,0 +1,284 @@
+use crate::core::profile::policy::Policy;
+use crate::core::profile::rule::{Rule, RuleType};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// 优化结果中最多列出的被遮蔽规则数量
+const MAX_SHADOWED_DIAGNOSTICS: usize = 10;
+
+/// 一次规则优化的统计
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RuleReport {
+    pub before: usize,
+    pub after: usize,
+    /// 与更早的规则完全相同
+    pub duplicates: usize,
+    /// 已被更早的同策略规则覆盖, 例如 DOMAIN-SUFFIX 覆盖了其下的 DOMAIN
+    pub covered: usize,
+    /// 相邻 CIDR 合并后减少的规则数
+    pub merged: usize,
+    /// 被更早的不同策略规则覆盖, 永远不会命中的规则
+    pub shadowed: Vec<ShadowedRule>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ShadowedRule {
+    pub rule: String,
+    pub by: String,
+}
+
+impl RuleReport {
+    pub fn removed(&self) -> usize {
+        self.before - self.after
+    }
+
+    /// 用于订阅日志的诊断信息, 没有可优化的内容时为空
+    pub fn diagnostics(&self) -> Vec<String> {
+        let mut diagnostics = vec![];
+        if self.removed() > 0 {
+            diagnostics.push(self.to_string());
+        }
+        for shadowed in self.shadowed.iter().take(MAX_SHADOWED_DIAGNOSTICS) {
+            diagnostics.push(shadowed.to_string());
+        }
+        if self.shadowed.len() > MAX_SHADOWED_DIAGNOSTICS {
+            diagnostics.push(format!(
+                "另有 {} 条规则被更早的规则遮蔽",
+                self.shadowed.len() - MAX_SHADOWED_DIAGNOSTICS
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl Display for RuleReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "规则优化: {} -> {}, 去重 {} 条, 覆盖 {} 条, 合并 CIDR {} 条",
+            self.before, self.after, self.duplicates, self.covered, self.merged
+        )
+    }
+}
+
+impl Display for ShadowedRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "规则 {} 被更早的规则 {} 遮蔽, 不会生效", self.rule, self.by)
+    }
+}
+
+/// 去除重复与被覆盖的规则, 合并同策略的相邻 CIDR, 并找出被不同策略遮蔽的规则
+///
+/// 规则按顺序匹配, 只有更早的规则能覆盖更晚的规则; 被不同策略遮蔽的规则只报告不删除
+pub fn optimize(rules: Vec<Rule>) -> (Vec<Rule>, RuleReport) {
+    let mut report = RuleReport {
+        before: rules.len(),
+        ..Default::default()
+    };
+    let mut index = CoverIndex::default();
+    let mut kept = Vec::with_capacity(rules.len());
+    for rule in rules {
+        let Some(matcher) = Matcher::new(&rule) else {
+            kept.push(rule);
+            continue;
+        };
+        match index.first_cover(&matcher) {
+            Some((position, exact)) if kept[position].policy == rule.policy => {
+                if exact {
+                    report.duplicates += 1;
+                } else {
+                    report.covered += 1;
+                }
+                continue;
+            }
+            Some((position, _)) if kept[position].policy.name != rule.policy.name => {
+                report.shadowed.push(ShadowedRule {
+                    rule: rule_key(&rule),
+                    by: rule_key(&kept[position]),
+                });
+            }
+            _ => {}
+        }
+        index.insert(matcher, kept.len());
+        kept.push(rule);
+    }
+
+    let kept = merge_cidrs(kept, &mut report);
+    report.after = kept.len();
+    (kept, report)
+}
+
+fn rule_key(rule: &Rule) -> String {
+    let mut key = rule.rule_type.to_string();
+    if let Some(value) = &rule.value {
+        key.push(',');
+        key.push_str(value);
+    }
+    key.push(',');
+    key.push_str(&rule.policy.name);
+    if let Some(option) = &rule.policy.option {
+        key.push(',');
+        key.push_str(option);
+    }
+    key
+}
+
+/// 参与覆盖判断的规则, 域名统一为小写
+enum Matcher {
+    Domain(String),
+    DomainSuffix(String),
+    DomainKeyword(String),
+    Cidr(RuleType, IpNet),
+    Other(RuleType, String),
+}
+
+impl Matcher {
+    fn new(rule: &Rule) -> Option<Self> {
+        let value = rule.value.as_deref()?.trim();
+        let matcher = match rule.rule_type {
+            RuleType::Domain => Matcher::Domain(value.to_ascii_lowercase()),
+            RuleType::DomainSuffix => Matcher::DomainSuffix(value.trim_start_matches('.').to_ascii_lowercase()),
+            RuleType::DomainKeyword => Matcher::DomainKeyword(value.to_ascii_lowercase()),
+            RuleType::IpCIDR | RuleType::IpCIDR6 => match value.parse::<IpNet>() {
+                Ok(net) => Matcher::Cidr(rule.rule_type.clone(), net.trunc()),
+                Err(_) => Matcher::Other(rule.rule_type.clone(), value.to_string()),
+            },
+            RuleType::GeoIP | RuleType::Final | RuleType::Match => return None,
+            _ => Matcher::Other(rule.rule_type.clone(), value.to_string()),
+        };
+        Some(matcher)
+    }
+
+    fn exact_key(&self) -> (RuleType, String) {
+        match self {
+            Matcher::Domain(domain) => (RuleType::Domain, domain.clone()),
+            Matcher::DomainSuffix(suffix) => (RuleType::DomainSuffix, suffix.clone()),
+            Matcher::DomainKeyword(keyword) => (RuleType::DomainKeyword, keyword.clone()),
+            Matcher::Cidr(rule_type, net) => (rule_type.clone(), net.to_string()),
+            Matcher::Other(rule_type, value) => (rule_type.clone(), value.clone()),
+        }
+    }
+}
+
+/// 已保留规则的索引, 用于找出最早覆盖某条规则的位置
+#[derive(Default)]
+struct CoverIndex {
+    exact: HashMap<(RuleType, String), usize>,
+    suffixes: HashMap<String, usize>,
+    keywords: Vec<(String, usize)>,
+    cidrs: Vec<(IpNet, usize)>,
+}
+
+impl CoverIndex {
+    /// 返回 (最早覆盖它的规则位置, 是否完全相同)
+    fn first_cover(&self, matcher: &Matcher) -> Option<(usize, bool)> {
+        let exact = self.exact.get(&matcher.exact_key()).copied();
+        let mut cover: Option<usize> = None;
+        let mut consider = |position: usize| {
+            cover = Some(cover.map_or(position, |c| c.min(position)));
+        };
+        match matcher {
+            Matcher::Domain(domain) | Matcher::DomainSuffix(domain) => {
+                for suffix in domain_suffixes(domain) {
+                    if let Some(position) = self.suffixes.get(suffix) {
+                        consider(*position);
+                    }
+                }
+                self.keywords
+                    .iter()
+                    .filter(|(keyword, _)| domain.contains(keyword.as_str()))
+                    .for_each(|(_, position)| consider(*position));
+            }
+            Matcher::DomainKeyword(keyword) => {
+                self.keywords
+                    .iter()
+                    .filter(|(other, _)| keyword.contains(other.as_str()))
+                    .for_each(|(_, position)| consider(*position));
+            }
+            Matcher::Cidr(_, net) => {
+                self.cidrs
+                    .iter()
+                    .filter(|(other, _)| other.contains(net))
+                    .for_each(|(_, position)| consider(*position));
+            }
+            Matcher::Other(..) => {}
+        }
+        match (exact, cover) {
+            (Some(exact), Some(cover)) if cover < exact => Some((cover, false)),
+            (Some(exact), _) => Some((exact, true)),
+            (None, Some(cover)) => Some((cover, false)),
+            (None, None) => None,
+        }
+    }
+
+    fn insert(&mut self, matcher: Matcher, position: usize) {
+        self.exact.entry(matcher.exact_key()).or_insert(position);
+        match matcher {
+            Matcher::DomainSuffix(suffix) => {
+                self.suffixes.entry(suffix).or_insert(position);
+            }
+            Matcher::DomainKeyword(keyword) => self.keywords.push((keyword, position)),
+            Matcher::Cidr(_, net) => self.cidrs.push((net, position)),
+            Matcher::Domain(_) | Matcher::Other(..) => {}
+        }
+    }
+}
+
+/// `a.b.example.com` 依次返回 `a.b.example.com`, `b.example.com`, `example.com`, `com`
+fn domain_suffixes(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |d| d.split_once('.').map(|(_, rest)| rest))
+}
+
+/// 合并同一策略下相邻的 CIDR, 合并后的网段不能与其它策略的 CIDR 重叠, 否则会改变匹配结果
+fn merge_cidrs(rules: Vec<Rule>, report: &mut RuleReport) -> Vec<Rule> {
+    let mut groups: HashMap<(RuleType, Policy), Vec<(usize, IpNet)>> = HashMap::new();
+    for (position, rule) in rules.iter().enumerate() {
+        if let Some(Matcher::Cidr(rule_type, net)) = Matcher::new(rule) {
+            groups.entry((rule_type, rule.policy.clone())).or_default().push((position, net));
+        }
+    }
+
+    let mut replaced: HashMap<usize, Vec<IpNet>> = HashMap::new();
+    let mut dropped: HashSet<usize> = HashSet::new();
+    for ((_, policy), members) in &groups {
+        if members.len() < 2 {
+            continue;
+        }
+        let nets = members.iter().map(|(_, net)| *net).collect::<Vec<_>>();
+        let aggregated = IpNet::aggregate(&nets);
+        if aggregated.len() == nets.len() {
+            continue;
+        }
+        let overlaps_other_policy = groups
+            .iter()
+            .filter(|((_, other), _)| other != policy)
+            .flat_map(|(_, members)| members.iter())
+            .any(|(_, other)| aggregated.iter().any(|net| net.contains(other) || other.contains(net)));
+        if overlaps_other_policy {
+            continue;
+        }
+        report.merged += nets.len() - aggregated.len();
+        // 合并结果放在这一组第一条规则的位置
+        replaced.insert(members[0].0, aggregated);
+        dropped.extend(members[1..].iter().map(|(position, _)| *position));
+    }
+
+    let mut optimized = Vec::with_capacity(rules.len());
+    for (position, rule) in rules.into_iter().enumerate() {
+        if dropped.contains(&position) {
+            continue;
+        }
+        match replaced.remove(&position) {
+            Some(nets) => optimized.extend(nets.into_iter().map(|net| Rule {
+                value: Some(net.to_string()),
+                ..rule.clone()
+            })),
+            None => optimized.push(rule),
+        }
+    }
+    optimized
+}
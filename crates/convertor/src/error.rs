@@ -1,11 +1,17 @@
+mod auth_error;
+mod cache_store_error;
 mod encrypt_error;
+mod geoip_error;
 mod parse_error;
 mod provider_error;
 mod query_error;
 mod render_error;
 mod url_error;
 
+pub use auth_error::*;
+pub use cache_store_error::*;
 pub use encrypt_error::*;
+pub use geoip_error::*;
 pub use parse_error::*;
 pub use provider_error::*;
 pub use query_error::*;
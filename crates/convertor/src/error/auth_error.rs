@@ -0,0 +1,27 @@
+use crate::error::CacheStoreError;
+use thiserror::Error;
+use url::Url;
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("缺少用户令牌")]
+    MissingToken,
+
+    #[error("无效的用户令牌")]
+    InvalidToken,
+
+    #[error("用户令牌已被吊销")]
+    RevokedToken,
+
+    #[error("用户 {user} 无权使用订阅: {sub_url}")]
+    SubscriptionNotAllowed { user: String, sub_url: Url },
+
+    #[error("用户不存在: {0}")]
+    UserNotFound(String),
+
+    #[error("无法生成令牌: {0}")]
+    Random(#[from] rand_core::OsError),
+
+    #[error(transparent)]
+    Store(#[from] CacheStoreError),
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CacheStoreError {
+    #[error("Redis 缓存错误: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("文件缓存读写失败: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("文件缓存格式错误: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -20,6 +20,9 @@ pub enum EncryptError {
     #[error("解密失败")]
     Decrypt,
 
+    #[error("未知的密钥: {0}, 该密钥可能已从 previous_secrets 中移除")]
+    UnknownKeyId(String),
+
     #[error("解码 base64 字符串失败")]
     DecodeError(#[source] base64::DecodeError),
 }
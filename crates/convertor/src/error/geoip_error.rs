@@ -0,0 +1,10 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GeoIpError {
+    #[error("无法读取 MMDB 文件: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("不合法的 MMDB 文件: {0}")]
+    Invalid(String),
+}
@@ -33,10 +33,36 @@ pub enum ProviderError {
     #[error(transparent)]
     InnerError(#[from] Arc<ProviderError>),
 
+    #[error("订阅获取失败, 共尝试 {} 次: {sub_url}{}", .attempts.len(), .attempts.iter().map(|a| format!("\n{a}")).collect::<String>())]
+    AttemptsExhausted { sub_url: Url, attempts: Vec<FetchAttempt> },
+
     #[error("{0}")]
     Other(String),
 }
 
+/// 出站代理配置无效, 无法构建客户端
+#[derive(Debug, Error)]
+#[error("无法使用出站代理 {url}: {source}")]
+pub struct UpstreamProxyError {
+    pub url: Url,
+    #[source]
+    pub source: reqwest::Error,
+}
+
+/// 一次失败的订阅请求, 记录请求的是主地址还是哪个镜像
+#[derive(Debug)]
+pub struct FetchAttempt {
+    pub url: Url,
+    pub attempt: u32,
+    pub error: ProviderError,
+}
+
+impl Display for FetchAttempt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}#{}] {}", self.url, self.attempt, self.error)
+    }
+}
+
 #[derive(Debug, Error)]
 pub struct ApiFailed {
     pub request: RequestInfo,
@@ -109,6 +135,15 @@ impl RequestInfo {
     }
 }
 
+impl ResponseInfo {
+    /// 按名称查找响应头, 忽略大小写
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find_map(|(k, v)| k.eq_ignore_ascii_case(name).then(|| v.clone()))
+    }
+}
+
 impl Display for RequestInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Request ID: {}", self.req_id)?;
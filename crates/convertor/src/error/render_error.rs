@@ -7,4 +7,8 @@ pub enum RenderError {
 
     #[error(transparent)]
     FmtError(#[from] std::fmt::Error),
+
+    #[cfg(feature = "qr")]
+    #[error("无法生成二维码: {0}")]
+    QrCode(#[from] qrcode::types::QrError),
 }
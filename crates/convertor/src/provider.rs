@@ -1,35 +1,55 @@
-use crate::common::cache::{Cache, CacheKey};
-use crate::config::subscription_config::Headers;
-use crate::error::{ApiFailed, ProviderError, RequestInfo, ResponseInfo};
-use redis::aio::ConnectionManager;
-use reqwest::Method;
+use crate::common::cache::cache_store::CacheStore;
+use crate::common::cache::{Cache, CacheEntryInfo, CacheKey, Cached};
+use crate::config::cache_config::SubscriptionCacheConfig;
+use crate::config::Config;
+use crate::config::retry_config::RetryConfig;
+use crate::config::subscription_config::{Headers, SubscriptionConfig};
+use crate::config::upstream_proxy_config::UpstreamProxyConfig;
+use crate::core::profile::Profile;
+use crate::core::profile::clash_profile::ClashProfile;
+use crate::core::profile::surge_profile::SurgeProfile;
+use crate::core::rule_optimizer::{self, RuleReport};
+use crate::error::{ApiFailed, CacheStoreError, FetchAttempt, ParseError, ProviderError, RequestInfo, ResponseInfo, UpstreamProxyError};
+use crate::provider::cache_admin::{CachePurge, without_flag};
+use crate::provider::sub_log::{SubLog, SubLogs};
+use crate::provider::subs_response::SubsResponse;
+use crate::provider::subscription_userinfo::{SUBSCRIPTION_USERINFO_HEADER, SubscriptionUserinfo};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Method, StatusCode};
+use std::collections::HashMap;
 use std::ops::Deref;
-use std::time::{Duration, Instant};
-use tracing::{debug, instrument};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{Span, debug, info, instrument, warn};
 use url::Url;
 
+pub mod cache_admin;
+pub mod profile_history;
+pub mod sub_log;
+pub mod subs_response;
+pub mod subscription_userinfo;
+
 #[derive(Clone)]
 pub struct SubsProvider {
     pub client: reqwest::Client,
-    pub cache: Cache<String, String>,
+    pub cache: Cache<String, SubsResponse>,
     pub cache_prefix: String,
+    /// 不含 `flag` 的订阅地址 -> 单独配置了出站代理的客户端
+    pub clients: Arc<HashMap<Url, reqwest::Client>>,
+    pub retry: RetryConfig,
+    /// 不含 `flag` 的订阅地址 -> 按顺序尝试的镜像地址
+    pub mirrors: Arc<HashMap<Url, Vec<Url>>>,
+    pub sub_logs: Option<SubLogs>,
 }
 
 impl SubsProvider {
-    pub fn new(redis: Option<ConnectionManager>, cache_prefix: Option<impl AsRef<str>>) -> Self {
-        let client = reqwest::Client::builder()
-            .connect_timeout(Duration::from_millis(5000))
-            // .connection_verbose(true)
-            .build()
-            .expect("构建 reqwest 客户端失败");
+    pub fn new(store: Option<CacheStore>, cache_prefix: Option<impl AsRef<str>>, cache_config: &SubscriptionCacheConfig) -> Self {
+        let client = build_client(None).expect("构建 reqwest 客户端失败");
         let cache = Cache::new(
-            redis,
-            10,
-            #[cfg(debug_assertions)]
-            Duration::from_secs(60 * 60 * 24),
-            #[cfg(not(debug_assertions))]
-            Duration::from_secs(60 * 60),
-            Duration::from_secs(60 * 60 * 12),
+            store,
+            cache_config.capacity,
+            cache_config.memory_ttl(),
+            cache_config.persistent_ttl(),
         );
         let cache_prefix = cache_prefix
             .as_ref()
@@ -39,27 +59,260 @@ impl SubsProvider {
             client,
             cache,
             cache_prefix,
+            clients: Arc::new(HashMap::new()),
+            retry: RetryConfig::default(),
+            mirrors: Arc::new(HashMap::new()),
+            sub_logs: None,
+        }
+    }
+
+    /// 使用全局出站代理与订阅配置
+    pub fn with_config(self, config: &Config) -> Result<Self, UpstreamProxyError> {
+        match &config.proxy {
+            Some(proxy) => self.with_proxy(proxy)?.with_subscription(&config.subscription),
+            None => self.with_subscription(&config.subscription),
         }
     }
 
+    /// 未单独配置代理的订阅均通过 `proxy` 请求
+    pub fn with_proxy(mut self, proxy: &UpstreamProxyConfig) -> Result<Self, UpstreamProxyError> {
+        self.client = build_proxy_client(proxy)?;
+        Ok(self)
+    }
+
+    /// 使用订阅配置中的重试策略, 并登记该订阅的镜像地址与出站代理
+    pub fn with_subscription(mut self, config: &SubscriptionConfig) -> Result<Self, UpstreamProxyError> {
+        self.retry = config.retry;
+        if !config.mirrors.is_empty() {
+            Arc::make_mut(&mut self.mirrors).insert(without_flag(&config.sub_url), config.mirrors.clone());
+        }
+        if let Some(proxy) = &config.proxy {
+            Arc::make_mut(&mut self.clients).insert(without_flag(&config.sub_url), build_proxy_client(proxy)?);
+        }
+        Ok(self)
+    }
+
+    /// 沿用已有的缓存, 按新配置重建出站代理、镜像地址与重试策略
+    pub fn reconfigure(&self, config: &Config) -> Result<Self, UpstreamProxyError> {
+        let provider = Self {
+            client: build_client(None).expect("构建 reqwest 客户端失败"),
+            clients: Arc::new(HashMap::new()),
+            retry: RetryConfig::default(),
+            mirrors: Arc::new(HashMap::new()),
+            ..self.clone()
+        };
+        provider.with_config(config)
+    }
+
+    /// 记录每一次向订阅商发起的请求
+    pub fn with_sub_logs(mut self, sub_logs: SubLogs) -> Self {
+        self.sub_logs = Some(sub_logs);
+        self
+    }
+
+    /// `user_agent` 为请求 convertor 的客户端, 仅用于订阅日志
     #[instrument(skip(self))]
-    pub async fn get_raw_profile(&self, sub_url: Url, headers: Headers) -> Result<String, ProviderError> {
+    pub async fn get_raw_profile(
+        &self,
+        sub_url: Url,
+        headers: Headers,
+        user_agent: Option<String>,
+    ) -> Result<SubsResponse, ProviderError> {
+        let cache_key = CacheKey::new(&self.cache_prefix, sub_url.to_string(), None);
+        let provider = self.clone();
+        let init = move |previous| {
+            let provider = provider.clone();
+            let sub_url = sub_url.clone();
+            let headers = headers.clone();
+            let user_agent = user_agent.clone();
+            async move { provider.fetch_with_validators(sub_url, headers, previous, user_agent).await }
+        };
+        let raw_profile = self.cache.try_get_with(cache_key, init).await?;
+        Ok(mark_stale(raw_profile))
+    }
+
+    /// 跳过缓存直接请求订阅商, 并用结果更新缓存
+    #[instrument(skip(self))]
+    pub async fn refresh_raw_profile(
+        &self,
+        sub_url: Url,
+        headers: Headers,
+        user_agent: Option<String>,
+    ) -> Result<SubsResponse, ProviderError> {
         let cache_key = CacheKey::new(&self.cache_prefix, sub_url.to_string(), None);
         let raw_profile = self
             .cache
-            .try_get_with(cache_key, async { self.fetch(sub_url, headers).await })
+            .refresh(cache_key, |previous| self.fetch_with_validators(sub_url, headers, previous, user_agent))
             .await?;
-        Ok(raw_profile)
+        Ok(mark_stale(raw_profile))
+    }
+
+    pub async fn cache_entries(&self) -> Vec<CacheEntryInfo> {
+        self.cache.entries(&self.cache_prefix).await
+    }
+
+    /// 清理内存与持久化缓存中匹配的缓存, 返回删除的持久化缓存数量
+    pub async fn purge_cache(&self, purge: &CachePurge) -> Result<usize, CacheStoreError> {
+        let purge = purge.clone();
+        self.cache
+            .purge_if(&self.cache_prefix, move |raw_url| purge.matches_raw_url(raw_url))
+            .await
+    }
+
+    /// 仅清理当前实例内存中匹配的缓存
+    pub fn invalidate_cache(&self, purge: &CachePurge) {
+        let purge = purge.clone();
+        self.cache.invalidate_memory_if(move |raw_url| purge.matches_raw_url(raw_url));
     }
 
     #[instrument(skip(self))]
-    pub async fn fetch(&self, sub_url: Url, headers: Headers) -> Result<String, ProviderError> {
+    pub async fn fetch(&self, sub_url: Url, headers: Headers) -> Result<SubsResponse, ProviderError> {
+        self.fetch_with_validators(sub_url, headers, None, None).await
+    }
+
+    /// 携带 `previous` 中的 `ETag` / `Last-Modified` 发起条件请求, 订阅商返回 304 时复用 `previous`
+    /// - 依次尝试 `sub_url` 及其镜像地址, 每个地址按重试策略指数退避重试
+    /// - 所有尝试共享同一个总时限
+    /// - 配置了订阅日志时记录本次请求, `user_agent` 为触发请求的客户端
+    #[instrument(skip(self, previous), fields(mirror))]
+    pub async fn fetch_with_validators(
+        &self,
+        sub_url: Url,
+        headers: Headers,
+        previous: Option<SubsResponse>,
+        user_agent: Option<String>,
+    ) -> Result<SubsResponse, ProviderError> {
+        let started = Instant::now();
+        let mut log = SubLog {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            sub_url: sub_url.clone(),
+            mirror: None,
+            status: None,
+            latency_ms: 0,
+            bytes: 0,
+            node_count: None,
+            user_agent,
+            diagnostics: vec![],
+        };
+        let result = self.fetch_with_retry(sub_url, &headers, previous, &mut log).await;
+        if let Some(sub_logs) = &self.sub_logs {
+            log.latency_ms = started.elapsed().as_millis() as u64;
+            if let Ok(response) = &result
+                && sub_logs.analyze()
+            {
+                match analyze_content(&response.content) {
+                    Ok((node_count, report)) => {
+                        log.node_count = Some(node_count);
+                        log.diagnostics.extend(report.diagnostics());
+                    }
+                    Err(e) => log.diagnostics.push(format!("无法解析订阅内容: {e}")),
+                }
+            }
+            if let Err(e) = sub_logs.push(log).await {
+                warn!("无法记录订阅请求日志: {}", e);
+            }
+        }
+        result
+    }
+
+    async fn fetch_with_retry(
+        &self,
+        sub_url: Url,
+        headers: &Headers,
+        previous: Option<SubsResponse>,
+        log: &mut SubLog,
+    ) -> Result<SubsResponse, ProviderError> {
+        let deadline = Instant::now() + self.retry.deadline();
+        // 请求地址带有 `flag`, 以不含 `flag` 的订阅地址查找; 镜像与主地址属于同一个订阅, 使用相同的出站代理
+        let key = without_flag(&sub_url);
+        let client = self.clients.get(&key).unwrap_or(&self.client);
+        let mirrors = self.mirrors.get(&key).into_iter().flatten();
+        let candidates = std::iter::once(sub_url.clone())
+            .chain(mirrors.map(|mirror| with_flag_of(mirror, &sub_url)))
+            .collect::<Vec<_>>();
+        let mut attempts = vec![];
+        'candidates: for url in &candidates {
+            for attempt in 1..=self.retry.attempts.max(1) {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                // 至少请求一次, 截止时间已耗尽时首次请求仍按单次超时进行
+                let timeout = match (remaining.is_zero(), attempts.is_empty()) {
+                    (true, true) => self.retry.attempt_timeout(),
+                    (true, false) => break 'candidates,
+                    (false, _) => remaining.min(self.retry.attempt_timeout()),
+                };
+                match self.fetch_once(client, url, headers, previous.clone(), timeout).await {
+                    Ok((status, response)) => {
+                        Span::current().record("mirror", url.as_str());
+                        if url != &sub_url {
+                            info!("订阅地址不可用, 已通过镜像获取: {}", url);
+                            log.mirror = Some(url.clone());
+                        }
+                        log.status = Some(status.as_u16());
+                        if status != StatusCode::NOT_MODIFIED {
+                            log.bytes = response.content.len() as u64;
+                        }
+                        return Ok(response);
+                    }
+                    Err(error) => {
+                        let retryable = is_retryable(&error);
+                        warn!("第 {} 次请求订阅失败: {}: {}", attempt, url, error);
+                        if let ProviderError::ApiFailed(failed) = &error {
+                            log.status = Some(failed.response.status.as_u16());
+                        }
+                        let failed = FetchAttempt {
+                            url: url.clone(),
+                            attempt,
+                            error,
+                        };
+                        // 日志中只保留错误的首行, 完整的请求信息仍在返回的错误中
+                        log.diagnostics.push(failed.to_string().lines().next().unwrap_or_default().to_string());
+                        attempts.push(failed);
+                        // 不可重试的错误直接换下一个镜像
+                        if !retryable || attempt == self.retry.attempts.max(1) {
+                            break;
+                        }
+                        let backoff = self.retry.backoff(attempt);
+                        if Instant::now() + backoff >= deadline {
+                            break 'candidates;
+                        }
+                        tokio::time::sleep(backoff).await;
+                    }
+                }
+            }
+        }
+        // 只请求了一次时保持原有的错误, 便于定位
+        if attempts.len() == 1 {
+            return Err(attempts.remove(0).error);
+        }
+        Err(ProviderError::AttemptsExhausted { sub_url, attempts })
+    }
+
+    async fn fetch_once(
+        &self,
+        client: &reqwest::Client,
+        sub_url: &Url,
+        headers: &Headers,
+        previous: Option<SubsResponse>,
+        timeout: Duration,
+    ) -> Result<(StatusCode, SubsResponse), ProviderError> {
         let mut request_info = RequestInfo::new(sub_url.clone(), Method::GET);
 
-        let mut rb = self.client.request(Method::GET, sub_url.clone());
+        let mut rb = client.request(Method::GET, sub_url.clone()).timeout(timeout);
         for (k, v) in headers.deref() {
+            // 条件请求头只由缓存中的校验信息决定, 不透传客户端的值
+            if k.eq_ignore_ascii_case(IF_NONE_MATCH.as_str()) || k.eq_ignore_ascii_case(IF_MODIFIED_SINCE.as_str()) {
+                continue;
+            }
             rb = rb.header(k, v);
         }
+        if let Some(previous) = previous.as_ref() {
+            if let Some(etag) = &previous.etag {
+                rb = rb.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &previous.last_modified {
+                rb = rb.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
         let req = rb.build().map_err(|e| ProviderError::RequestError {
             reason: "无法构建请求".to_string(),
             source: Box::new(e),
@@ -73,7 +326,7 @@ impl SubsProvider {
         let started = Instant::now();
 
         // —— 发出请求
-        let resp = self.client.execute(req).await.map_err(|e| ProviderError::RequestError {
+        let resp = client.execute(req).await.map_err(|e| ProviderError::RequestError {
             reason: "请求失败".to_string(),
             source: Box::new(e),
             request_info: request_info.clone(),
@@ -91,6 +344,10 @@ impl SubsProvider {
             body: None,
         };
 
+        let resp_userinfo = response_info.header(SUBSCRIPTION_USERINFO_HEADER);
+        let etag = response_info.header(ETAG.as_str());
+        let last_modified = response_info.header(LAST_MODIFIED.as_str());
+
         // 读取完整 body
         let response_body_text = resp.text().await.map_err(|e| ProviderError::ResponseError {
             reason: "读取响应体失败".to_string(),
@@ -118,8 +375,28 @@ impl SubsProvider {
             "HTTP request completed"
         );
 
-        if response_info.status.is_success() {
-            Ok(response_body_text)
+        let userinfo = resp_userinfo.and_then(|value| match value.parse::<SubscriptionUserinfo>() {
+            Ok(userinfo) => Some(userinfo),
+            Err(e) => {
+                warn!("忽略无法解析的 {SUBSCRIPTION_USERINFO_HEADER}: {e}");
+                None
+            }
+        });
+
+        if let (StatusCode::NOT_MODIFIED, Some(mut previous)) = (response_info.status, previous) {
+            debug!("订阅未变化, 复用缓存内容: {}", sub_url);
+            // 304 同样可能携带新的校验信息与流量信息
+            previous.etag = etag.or(previous.etag);
+            previous.last_modified = last_modified.or(previous.last_modified);
+            previous.userinfo = userinfo.or(previous.userinfo);
+            previous.stale = false;
+            Ok((response_info.status, previous))
+        } else if response_info.status.is_success() {
+            let mut response = SubsResponse::new(response_body_text);
+            response.userinfo = userinfo;
+            response.etag = etag;
+            response.last_modified = last_modified;
+            Ok((response_info.status, response))
         } else {
             // 与你原有错误结构对齐
             Err(ProviderError::ApiFailed(Box::new(ApiFailed {
@@ -129,3 +406,65 @@ impl SubsProvider {
         }
     }
 }
+
+fn build_client(proxy: Option<&UpstreamProxyConfig>) -> Result<reqwest::Client, reqwest::Error> {
+    let mut builder = reqwest::Client::builder().connect_timeout(Duration::from_millis(5000));
+    // .connection_verbose(true)
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(proxy.to_proxy()?);
+    }
+    builder.build()
+}
+
+fn build_proxy_client(proxy: &UpstreamProxyConfig) -> Result<reqwest::Client, UpstreamProxyError> {
+    build_client(Some(proxy)).map_err(|source| UpstreamProxyError {
+        url: proxy.url.clone(),
+        source,
+    })
+}
+
+/// 按订阅内容的格式解析, 统计节点数量并分析规则可优化的部分
+fn analyze_content(content: &str) -> Result<(usize, RuleReport), ParseError> {
+    let (node_count, rules) = if content.contains("[Proxy]") {
+        let profile = SurgeProfile::parse(content.to_string())?;
+        (profile.proxies.len(), profile.rules)
+    } else {
+        let profile = ClashProfile::parse(content.to_string())?;
+        (profile.proxies.len(), profile.rules)
+    };
+    let rules = rules.into_iter().filter(|rule| !rule.is_built_in()).collect();
+    Ok((node_count, rule_optimizer::optimize(rules).1))
+}
+
+/// 将请求地址中的 `flag` 补到镜像地址上, 镜像同样需要据此返回对应客户端的配置
+/// 镜像地址自带的 `flag` 会被替换
+fn with_flag_of(mirror: &Url, sub_url: &Url) -> Url {
+    let flags = sub_url.query_pairs().filter(|(k, _)| k == "flag").collect::<Vec<_>>();
+    if flags.is_empty() {
+        return mirror.clone();
+    }
+    let mut mirror_with_flag = without_flag(mirror);
+    mirror_with_flag.query_pairs_mut().extend_pairs(flags);
+    mirror_with_flag
+}
+
+fn is_retryable(error: &ProviderError) -> bool {
+    match error {
+        ProviderError::RequestError { .. } | ProviderError::ResponseError { .. } => true,
+        ProviderError::ApiFailed(failed) => {
+            let status = failed.response.status;
+            status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
+
+fn mark_stale(raw_profile: Cached<SubsResponse>) -> SubsResponse {
+    match raw_profile {
+        Cached::Fresh(raw_profile) => raw_profile,
+        Cached::Stale(mut raw_profile) => {
+            raw_profile.stale = true;
+            raw_profile
+        }
+    }
+}
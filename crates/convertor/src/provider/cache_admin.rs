@@ -0,0 +1,71 @@
+use crate::common::cache::CacheEntryInfo;
+use crate::config::proxy_client::ProxyClient;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// 缓存清理范围, 未指定的条件视为匹配全部
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CachePurge {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<ProxyClient>,
+}
+
+impl CachePurge {
+    pub fn is_all(&self) -> bool {
+        self.sub_url.is_none() && self.client.is_none()
+    }
+
+    pub fn matches(&self, sub_url: &Url, client: ProxyClient) -> bool {
+        let sub_url_matched = self
+            .sub_url
+            .as_ref()
+            .map(|expected| without_flag(expected) == without_flag(sub_url))
+            .unwrap_or(true);
+        let client_matched = self.client.map(|expected| expected == client).unwrap_or(true);
+        sub_url_matched && client_matched
+    }
+
+    /// 订阅商缓存以原始订阅链接为 key, 其中 `flag` 参数即为客户端类型
+    pub fn matches_raw_url(&self, raw_url: &str) -> bool {
+        let Ok(raw_url) = Url::parse(raw_url) else {
+            return self.is_all();
+        };
+        let client = raw_url
+            .query_pairs()
+            .find(|(k, _)| k == "flag")
+            .and_then(|(_, v)| v.parse::<ProxyClient>().ok());
+        match client {
+            Some(client) => self.matches(&raw_url, client),
+            None => self.client.is_none() && self.matches(&raw_url, ProxyClient::default()),
+        }
+    }
+}
+
+/// 缓存管理接口返回的缓存概览
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct CacheOverview {
+    /// 订阅商原始配置缓存
+    pub subscriptions: Vec<CacheEntryInfo>,
+    /// 转换后的配置缓存
+    pub profiles: Vec<CacheEntryInfo>,
+}
+
+/// 去掉客户端标识 `flag`, 同一个订阅的不同客户端请求得到相同的地址
+pub(crate) fn without_flag(url: &Url) -> Url {
+    let mut url = url.clone();
+    let pairs = url
+        .query_pairs()
+        .filter(|(k, _)| k != "flag")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect::<Vec<_>>();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url
+}
@@ -0,0 +1,156 @@
+use crate::common::cache::CACHED_PROFILE_HISTORY_KEY;
+use crate::config::proxy_client::ProxyClient;
+use crate::core::diff::{ProfileDiff, ProfileSnapshot};
+use crate::error::CacheStoreError;
+use crate::provider::subs_response::content_hash;
+use redis::AsyncTypedCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use url::Url;
+
+type Result<T> = core::result::Result<T, CacheStoreError>;
+
+/// 每个订阅最近两次不同内容的快照
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ProfileHistoryEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<ProfileSnapshot>,
+    pub current: ProfileSnapshot,
+}
+
+/// 订阅内容变化时发送给 webhook 的消息, 不包含完整的订阅地址
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ProfileDiffNotification {
+    pub client: ProxyClient,
+    pub sub_host: String,
+    pub diff: ProfileDiff,
+}
+
+/// 保存订阅的历史快照, 用于回答 "订阅变了什么"
+#[derive(Clone)]
+pub struct ProfileHistory {
+    backend: ProfileHistoryBackend,
+    prefix: String,
+    webhook: Option<(reqwest::Client, Url)>,
+}
+
+#[derive(Clone)]
+enum ProfileHistoryBackend {
+    Redis(ConnectionManager),
+    Memory(Arc<Mutex<HashMap<String, ProfileHistoryEntry>>>),
+}
+
+impl ProfileHistory {
+    pub fn new(redis: Option<ConnectionManager>, prefix: impl AsRef<str>) -> Self {
+        let backend = match redis {
+            Some(redis) => ProfileHistoryBackend::Redis(redis),
+            None => ProfileHistoryBackend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        };
+        // 与订阅日志一样不放在缓存 key 的前缀下, 避免被缓存清理误删
+        let prefix = format!("{CACHED_PROFILE_HISTORY_KEY}:{}", prefix.as_ref());
+        Self {
+            backend,
+            prefix,
+            webhook: None,
+        }
+    }
+
+    /// 订阅内容变化时向 `webhook` 发送 POST 请求
+    pub fn with_webhook(mut self, client: reqwest::Client, webhook: Option<Url>) -> Self {
+        self.webhook = webhook.map(|url| (client, url));
+        self
+    }
+
+    /// 记录最新快照, 内容与当前快照不同时返回两者的差异
+    pub async fn record(&self, sub_url: &Url, snapshot: ProfileSnapshot) -> Result<Option<ProfileDiff>> {
+        let key = self.key(snapshot.client, sub_url);
+        let entry = match self.load(&key).await? {
+            Some(entry) if entry.current.content_hash == snapshot.content_hash => return Ok(None),
+            Some(entry) => ProfileHistoryEntry {
+                previous: Some(entry.current),
+                current: snapshot,
+            },
+            None => ProfileHistoryEntry {
+                previous: None,
+                current: snapshot,
+            },
+        };
+        self.save(&key, &entry).await?;
+        let Some(previous) = &entry.previous else {
+            return Ok(None);
+        };
+        let diff = ProfileDiff::between(previous, &entry.current);
+        if !diff.is_empty() {
+            self.notify(entry.current.client, sub_url, &diff);
+        }
+        Ok(Some(diff))
+    }
+
+    /// 最近一次获取相对于上一次不同内容的差异, 从未记录过时返回 None
+    pub async fn diff(&self, client: ProxyClient, sub_url: &Url) -> Result<Option<ProfileDiff>> {
+        let entry = self.load(&self.key(client, sub_url)).await?;
+        Ok(entry.map(|entry| match &entry.previous {
+            Some(previous) => ProfileDiff::between(previous, &entry.current),
+            None => ProfileDiff::initial(&entry.current),
+        }))
+    }
+
+    async fn save(&self, key: &str, entry: &ProfileHistoryEntry) -> Result<()> {
+        match &self.backend {
+            ProfileHistoryBackend::Redis(redis) => {
+                redis.clone().set(key, serde_json::to_string(entry)?).await?;
+            }
+            ProfileHistoryBackend::Memory(entries) => {
+                let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+                entries.insert(key.to_string(), entry.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&self, client: ProxyClient, sub_url: &Url) -> String {
+        // 订阅地址中通常带有令牌, 只用它的摘要作为键
+        let hash = content_hash(sub_url.as_str());
+        format!("{}:{}:{}", self.prefix, client.as_str(), &hash[..16])
+    }
+
+    async fn load(&self, key: &str) -> Result<Option<ProfileHistoryEntry>> {
+        match &self.backend {
+            ProfileHistoryBackend::Redis(redis) => match redis.clone().get(key).await? {
+                Some(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+                None => Ok(None),
+            },
+            ProfileHistoryBackend::Memory(entries) => {
+                let entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(entries.get(key).cloned())
+            }
+        }
+    }
+
+    fn notify(&self, client: ProxyClient, sub_url: &Url, diff: &ProfileDiff) {
+        let Some((http, webhook)) = self.webhook.clone() else {
+            return;
+        };
+        let notification = ProfileDiffNotification {
+            client,
+            sub_host: sub_url.host_str().unwrap_or_default().to_string(),
+            diff: diff.clone(),
+        };
+        tokio::spawn(async move {
+            let result = http
+                .post(webhook.clone())
+                .json(&notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                warn!("发送订阅变化通知失败 {webhook}: {e}");
+            }
+        });
+    }
+}
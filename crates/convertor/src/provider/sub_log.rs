@@ -0,0 +1,143 @@
+use crate::common::cache::CACHED_SUB_LOGS_KEY;
+use crate::error::CacheStoreError;
+use redis::AsyncTypedCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// 一次向订阅商发起请求的记录
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct SubLog {
+    /// 请求时间的 Unix 时间戳(秒)
+    pub time: u64,
+    pub sub_url: Url,
+    /// 实际返回结果的镜像地址, 由主地址返回时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<Url>,
+    /// 最后一次请求的状态码, 请求未收到响应时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    pub bytes: u64,
+    /// 订阅中的节点数量, 无法解析时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_count: Option<usize>,
+    /// 触发这次请求的客户端 User-Agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// 失败的尝试与解析问题
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<String>,
+}
+
+impl SubLog {
+    pub fn is_success(&self) -> bool {
+        self.status.is_some_and(|status| (200..400).contains(&status))
+    }
+}
+
+impl Display for SubLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let time = chrono::DateTime::from_timestamp(self.time as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| self.time.to_string());
+        let status = self.status.map(|s| s.to_string()).unwrap_or_else(|| "-".to_string());
+        write!(
+            f,
+            "[{time}] {status} {} | {}ms | {}B",
+            self.sub_url.host_str().unwrap_or_default(),
+            self.latency_ms,
+            self.bytes
+        )?;
+        if let Some(node_count) = self.node_count {
+            write!(f, " | {node_count} nodes")?;
+        }
+        if let Some(user_agent) = &self.user_agent {
+            write!(f, " | {user_agent}")?;
+        }
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n    {diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+/// 订阅请求日志, 按时间倒序保存最近的 `retention` 条
+#[derive(Clone)]
+pub struct SubLogs {
+    backend: SubLogsBackend,
+    key: String,
+    retention: usize,
+    analyze: bool,
+}
+
+#[derive(Clone)]
+enum SubLogsBackend {
+    Redis(ConnectionManager),
+    Memory(Arc<Mutex<VecDeque<SubLog>>>),
+}
+
+impl SubLogs {
+    pub fn new(redis: Option<ConnectionManager>, prefix: impl AsRef<str>, retention: usize) -> Self {
+        let backend = match redis {
+            Some(redis) => SubLogsBackend::Redis(redis),
+            None => SubLogsBackend::Memory(Arc::new(Mutex::new(VecDeque::new()))),
+        };
+        // 不放在缓存 key 的前缀下, 避免被缓存清理误删
+        let key = format!("{CACHED_SUB_LOGS_KEY}:{}", prefix.as_ref());
+        Self {
+            backend,
+            key,
+            retention: retention.max(1),
+            analyze: false,
+        }
+    }
+
+    /// 记录日志时解析订阅内容, 统计节点数量与规则诊断
+    pub fn with_analyze(mut self, analyze: bool) -> Self {
+        self.analyze = analyze;
+        self
+    }
+
+    pub fn analyze(&self) -> bool {
+        self.analyze
+    }
+
+    pub async fn push(&self, log: SubLog) -> Result<(), CacheStoreError> {
+        match &self.backend {
+            SubLogsBackend::Redis(redis) => {
+                let mut redis = redis.clone();
+                redis.lpush(&self.key, serde_json::to_string(&log)?).await?;
+                redis.ltrim(&self.key, 0, self.retention as isize - 1).await?;
+            }
+            SubLogsBackend::Memory(logs) => {
+                let mut logs = logs.lock().unwrap_or_else(|e| e.into_inner());
+                logs.push_front(log);
+                logs.truncate(self.retention);
+            }
+        }
+        Ok(())
+    }
+
+    /// 按时间倒序返回日志
+    pub async fn list(&self, offset: usize, limit: usize) -> Result<Vec<SubLog>, CacheStoreError> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        match &self.backend {
+            SubLogsBackend::Redis(redis) => {
+                let end = (offset + limit - 1) as isize;
+                let raws = redis.clone().lrange(&self.key, offset as isize, end).await?;
+                Ok(raws.iter().filter_map(|raw| serde_json::from_str(raw).ok()).collect())
+            }
+            SubLogsBackend::Memory(logs) => {
+                let logs = logs.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(logs.iter().skip(offset).take(limit).cloned().collect())
+            }
+        }
+    }
+}
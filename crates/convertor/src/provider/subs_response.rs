@@ -0,0 +1,70 @@
+use crate::provider::subscription_userinfo::SubscriptionUserinfo;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+
+/// 订阅商响应中需要被缓存的部分: 响应体以及与之相关的响应头
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct SubsResponse {
+    pub content: String,
+    #[serde(default)]
+    pub userinfo: Option<SubscriptionUserinfo>,
+    /// 响应头中的 `ETag`, 刷新时作为 `If-None-Match` 发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// 响应头中的 `Last-Modified`, 刷新时作为 `If-Modified-Since` 发送
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// 响应体的 sha256, 用于判断内容是否真的发生了变化
+    #[serde(default)]
+    pub content_hash: String,
+    /// 上游获取失败, 当前内容来自最后一次成功的缓存
+    #[serde(skip)]
+    pub stale: bool,
+}
+
+impl SubsResponse {
+    pub fn new(content: impl Into<String>) -> Self {
+        let content = content.into();
+        let content_hash = content_hash(&content);
+        Self {
+            content,
+            userinfo: None,
+            etag: None,
+            last_modified: None,
+            content_hash,
+            stale: false,
+        }
+    }
+
+    /// 是否携带可用于条件请求的校验信息
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+impl From<String> for SubsResponse {
+    fn from(value: String) -> Self {
+        // 旧版本缓存中只保存了响应体, 无法反序列化时整体视为响应体
+        match serde_json::from_str::<SubsResponse>(&value) {
+            Ok(mut response) => {
+                if response.content_hash.is_empty() {
+                    response.content_hash = content_hash(&response.content);
+                }
+                response
+            }
+            Err(_) => SubsResponse::new(value),
+        }
+    }
+}
+
+impl Display for SubsResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).map_err(|_| std::fmt::Error)?)
+    }
+}
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
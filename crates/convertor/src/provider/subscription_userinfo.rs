@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+pub const SUBSCRIPTION_USERINFO_HEADER: &str = "subscription-userinfo";
+
+/// 订阅商通过 `subscription-userinfo` 响应头下发的流量与到期信息
+/// 形如: `upload=455727941; download=6174315083; total=1073741824000; expire=1671815872`
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct SubscriptionUserinfo {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    /// 到期时间的 Unix 时间戳(秒), 部分订阅商不下发或下发 0 表示不限期
+    #[serde(default)]
+    pub expire: Option<u64>,
+}
+
+impl SubscriptionUserinfo {
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.total.saturating_sub(self.used())
+    }
+
+    /// 用于展示的简短描述, 不包含 `,` 与 `=`, 可以直接作为节点名称
+    pub fn summary(&self) -> String {
+        let mut output = format!("流量: {} / {}", format_bytes(self.used()), format_bytes(self.total));
+        if let Some(expire) = self.expire.and_then(|e| chrono::DateTime::from_timestamp(e as i64, 0)) {
+            output.push_str(&format!(" | 到期: {}", expire.format("%Y-%m-%d")));
+        }
+        output
+    }
+}
+
+impl FromStr for SubscriptionUserinfo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut userinfo = SubscriptionUserinfo::default();
+        for pair in s.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("无法解析 subscription-userinfo 字段: {pair}"))?;
+            // 部分订阅商会下发浮点数, 这里统一截断为整数
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("无法解析 subscription-userinfo 字段 {key}: {e}"))? as u64;
+            match key.trim().to_lowercase().as_str() {
+                "upload" => userinfo.upload = value,
+                "download" => userinfo.download = value,
+                "total" => userinfo.total = value,
+                "expire" => userinfo.expire = (value > 0).then_some(value),
+                _ => {}
+            }
+        }
+        Ok(userinfo)
+    }
+}
+
+impl Display for SubscriptionUserinfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upload={}; download={}; total={}", self.upload, self.download, self.total)?;
+        if let Some(expire) = self.expire {
+            write!(f, "; expire={expire}")?;
+        }
+        Ok(())
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[unit])
+    } else {
+        format!("{value:.2} {}", UNITS[unit])
+    }
+}
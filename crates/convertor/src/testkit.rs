@@ -2,6 +2,7 @@ use crate::config::Config;
 use crate::config::proxy_client::ProxyClient;
 use crate::config::subscription_config::SubscriptionConfig;
 use crate::core::profile::policy::Policy;
+use crate::provider::subscription_userinfo::SUBSCRIPTION_USERINFO_HEADER;
 use crate::url::url_builder::HostPort;
 use color_eyre::Report;
 use color_eyre::eyre::OptionExt;
@@ -24,6 +25,8 @@ macro_rules! init_test {
     }};
 }
 
+pub const MOCK_SUBSCRIPTION_USERINFO: &str = "upload=1073741824; download=2147483648; total=107374182400; expire=1893456000";
+
 pub async fn start_mock_provider_server(config: &mut Config) -> Result<(), Report> {
     config.subscription.start_mock_provider_server().await?;
     Ok(())
@@ -56,7 +59,8 @@ impl MockServerExt for SubscriptionConfig {
                     let body = mock_profile(*client, &sub_host);
                     then.status(200)
                         .body(body)
-                        .header("Content-Type", "text/plain; charset=utf-8");
+                        .header("Content-Type", "text/plain; charset=utf-8")
+                        .header(SUBSCRIPTION_USERINFO_HEADER, MOCK_SUBSCRIPTION_USERINFO);
                 })
                 .await;
         }
@@ -73,7 +77,8 @@ impl MockServerExt for SubscriptionConfig {
                     let body = mock_profile(*client, &sub_host);
                     then.status(200)
                         .body(body)
-                        .header("Content-Type", "text/plain; charset=utf-8");
+                        .header("Content-Type", "text/plain; charset=utf-8")
+                        .header(SUBSCRIPTION_USERINFO_HEADER, MOCK_SUBSCRIPTION_USERINFO);
                 })
                 .await;
         }
@@ -1,4 +1,7 @@
 pub mod convertor_url;
+#[cfg(feature = "qr")]
+pub mod qr_code;
 pub mod query;
+pub mod short_link;
 pub mod url_builder;
 pub mod url_result;
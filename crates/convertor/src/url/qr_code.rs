@@ -0,0 +1,70 @@
+use crate::error::RenderError;
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use qrcode::render::{svg, unicode};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+type Result<T> = core::result::Result<T, RenderError>;
+
+/// 图片格式的二维码, 供 dashboard 展示
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrImageFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+impl QrImageFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            QrImageFormat::Svg => "image/svg+xml",
+            QrImageFormat::Png => "image/png",
+        }
+    }
+}
+
+/// 将链接编码为二维码, 订阅链接较长, 使用最低的纠错等级以减小尺寸
+pub struct QrCodeRenderer {
+    code: QrCode,
+}
+
+impl QrCodeRenderer {
+    pub fn new(data: impl AsRef<[u8]>) -> Result<Self> {
+        let code = QrCode::with_error_correction_level(data, qrcode::EcLevel::L)?;
+        Ok(Self { code })
+    }
+
+    /// 每个字符表示上下两个模块, `invert` 为 true 时颜色反转
+    pub fn render_terminal(&self, invert: bool) -> String {
+        let mut renderer = self.code.render::<unicode::Dense1x2>();
+        if invert {
+            renderer
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark);
+        }
+        renderer.build()
+    }
+
+    pub fn render_svg(&self) -> String {
+        self.code.render::<svg::Color>().min_dimensions(256, 256).build()
+    }
+
+    pub fn render_png(&self) -> Result<Vec<u8>> {
+        let image = self.code.render::<Luma<u8>>().min_dimensions(256, 256).build();
+        let mut output = Cursor::new(vec![]);
+        image
+            .write_to(&mut output, ImageFormat::Png)
+            .map_err(|e| RenderError::Render(e.to_string()))?;
+        Ok(output.into_inner())
+    }
+
+    pub fn render_image(&self, format: QrImageFormat) -> Result<Vec<u8>> {
+        match format {
+            QrImageFormat::Svg => Ok(self.render_svg().into_bytes()),
+            QrImageFormat::Png => self.render_png(),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::common::encrypt::decrypt;
+use crate::common::encrypt::decrypt_with_any;
 use crate::core::profile::policy::Policy;
 use crate::error::{EncodeUrlError, ParseUrlError, QueryError};
 use percent_encoding::{percent_decode_str, utf8_percent_encode};
@@ -24,16 +24,26 @@ pub struct ConvertorQuery {
     // sub logs
     pub secret: Option<String>,
     pub enc_secret: Option<String>,
+
+    // 用户令牌, 开启鉴权后 profile 与 rule provider 必须携带
+    pub token: Option<String>,
+
+    // 跳过缓存, 仅对当前请求生效, 不会编码进生成的链接
+    pub refresh: bool,
 }
 
 impl ConvertorQuery {
+    /// `previous_secrets` 为轮换前的密钥, 用于解密旧链接中的参数
     pub fn parse_from_query_string(
         query_string: impl AsRef<str>,
         secret: impl AsRef<str>,
+        previous_secrets: &[String],
         server: Url,
     ) -> Result<Self, QueryError> {
         let query_string = query_string.as_ref();
-        let secret = secret.as_ref();
+        let secrets = std::iter::once(secret.as_ref())
+            .chain(previous_secrets.iter().map(String::as_str))
+            .collect::<Vec<_>>();
         let query_map = Self::url_decode(query_string)?;
 
         // 解析 sub_url
@@ -41,7 +51,7 @@ impl ConvertorQuery {
             .get("sub_url")
             .ok_or(ParseUrlError::NotFoundParam("sub_url"))?
             .to_string();
-        let sub_url = decrypt(secret.as_bytes(), enc_sub_url.as_ref())?
+        let sub_url = decrypt_with_any(&secrets, enc_sub_url.as_ref())?
             .parse::<Url>()
             .map_err(ParseUrlError::from)?;
 
@@ -63,6 +73,14 @@ impl ConvertorQuery {
         // 解析 policy
         let policy = Self::parse_policy_from_query_pairs(&query_map)?;
 
+        // 解析 refresh
+        let refresh = query_map
+            .get("refresh")
+            .map(|s| s.parse::<bool>())
+            .transpose()
+            .map_err(ParseUrlError::from)?
+            .unwrap_or(false);
+
         // 解析 secret
         let enc_secret = query_map
             .get("secret")
@@ -75,9 +93,12 @@ impl ConvertorQuery {
             .transpose()?;
         let secret = enc_secret
             .as_ref()
-            .map(|es| decrypt(secret.as_bytes(), es.as_ref()))
+            .map(|es| decrypt_with_any(&secrets, es.as_ref()))
             .transpose()?;
 
+        // 解析 token
+        let token = query_map.get("token").map(|s| s.to_string());
+
         Ok(Self {
             server,
             sub_url,
@@ -87,6 +108,8 @@ impl ConvertorQuery {
             policy,
             secret,
             enc_secret,
+            token,
+            refresh,
         })
     }
 
@@ -117,11 +140,14 @@ impl ConvertorQuery {
             .strict
             .ok_or(EncodeUrlError::NotFoundParam("profile", "strict"))?
             .to_string();
-        let query_pairs = vec![
+        let mut query_pairs = vec![
             ("interval", Cow::Borrowed(interval_str.as_str())),
             ("strict", Cow::Borrowed(strict_str.as_str())),
             ("sub_url", Cow::Borrowed(self.enc_sub_url.as_str())),
         ];
+        if let Some(token) = &self.token {
+            query_pairs.push(("token", Cow::Borrowed(token.as_str())));
+        }
 
         Ok(Self::url_encode(query_pairs))
     }
@@ -134,6 +160,9 @@ impl ConvertorQuery {
         let mut query_pairs = vec![("interval", Cow::Owned(self.interval.to_string()))];
         Self::encode_policy_to_query_pairs(policy, &mut query_pairs);
         query_pairs.push(("sub_url", Cow::Borrowed(&self.enc_sub_url)));
+        if let Some(token) = &self.token {
+            query_pairs.push(("token", Cow::Borrowed(token)));
+        }
 
         Ok(Self::url_encode(query_pairs))
     }
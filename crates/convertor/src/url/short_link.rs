@@ -0,0 +1,225 @@
+use crate::common::cache::CACHED_SHORT_LINK_KEY;
+use crate::common::encrypt::is_current_key;
+use crate::error::{CacheStoreError, UrlBuilderError};
+use crate::url::url_builder::UrlBuilder;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as B64URL;
+use redis::AsyncTypedCommands;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 短链接, 通过 `/p/{id}` 访问保存的订阅配置
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ShortLink {
+    pub id: String,
+    /// 保存时会清空明文密钥, 读取后通过 [`ShortLink::url_builder`] 填回当前密钥
+    pub url_builder: UrlBuilder,
+    /// Unix 时间戳(秒)
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub hits: u64,
+}
+
+impl ShortLink {
+    /// 同一份订阅配置总是得到相同的 id, 便于去重
+    /// 只使用明文字段计算, 密文每次生成都不同; 不包含密钥, 轮换密钥后已导入的短链接依然有效
+    pub fn id_for(url_builder: &UrlBuilder) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            url_builder.client.as_str(),
+            url_builder.server.as_str(),
+            url_builder.sub_url.as_str(),
+            &url_builder.interval.to_string(),
+            &url_builder.strict.to_string(),
+            url_builder.token.as_deref().unwrap_or_default(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        B64URL.encode(&hasher.finalize()[..9])
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now_secs())
+    }
+
+    /// 填回当前密钥, 由旧密钥加密的链接会重新加密
+    /// 返回值的第二项表示是否重新加密过, 调用方应据此保存新的短链接
+    pub fn url_builder(&self, secret: impl AsRef<str>) -> Result<(UrlBuilder, bool), UrlBuilderError> {
+        let secret = secret.as_ref();
+        let stored = &self.url_builder;
+        if is_current_key(secret.as_bytes(), &stored.enc_sub_url) {
+            let mut url_builder = stored.clone();
+            url_builder.secret = secret.to_string();
+            return Ok((url_builder, false));
+        }
+        let url_builder = UrlBuilder::new(
+            secret,
+            None,
+            stored.client,
+            stored.server.clone(),
+            stored.sub_url.clone(),
+            None,
+            stored.interval,
+            stored.strict,
+        )?
+        .with_token(stored.token.clone());
+        Ok((url_builder, true))
+    }
+}
+
+/// 短链接的存储, 优先保存在 Redis 中, 否则仅保存在内存中
+#[derive(Clone)]
+pub struct ShortLinkStore {
+    backend: ShortLinkBackend,
+    key: String,
+}
+
+#[derive(Clone)]
+enum ShortLinkBackend {
+    Redis(ConnectionManager),
+    Memory(Arc<Mutex<HashMap<String, ShortLink>>>),
+}
+
+impl ShortLinkStore {
+    pub fn new(redis: Option<ConnectionManager>, prefix: impl AsRef<str>) -> Self {
+        let backend = match redis {
+            Some(redis) => ShortLinkBackend::Redis(redis),
+            None => ShortLinkBackend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+        };
+        let key = format!("{CACHED_SHORT_LINK_KEY}:{}", prefix.as_ref());
+        Self { backend, key }
+    }
+
+    /// 创建短链接, 已存在时仅更新过期时间
+    /// 保留原有的密文, 使短链接返回的配置保持稳定
+    pub async fn create(&self, url_builder: &UrlBuilder, ttl: Option<Duration>) -> Result<ShortLink, CacheStoreError> {
+        let id = ShortLink::id_for(url_builder);
+        let mut stored = url_builder.clone();
+        stored.secret = String::new();
+        let mut link = ShortLink {
+            id: id.clone(),
+            url_builder: stored,
+            created_at: now_secs(),
+            expires_at: ttl.map(|ttl| now_secs() + ttl.as_secs()),
+            hits: 0,
+        };
+        if let Some(existing) = self.get(&id).await? {
+            link.url_builder = existing.url_builder;
+            link.created_at = existing.created_at;
+            link.hits = existing.hits;
+        }
+        self.save(&link).await?;
+        Ok(link)
+    }
+
+    /// 读取短链接但不计入访问次数, 过期的短链接视为不存在
+    pub async fn get(&self, id: &str) -> Result<Option<ShortLink>, CacheStoreError> {
+        let link = match &self.backend {
+            ShortLinkBackend::Redis(redis) => {
+                let mut redis = redis.clone();
+                let Some(raw) = redis.get(self.link_key(id)).await? else {
+                    return Ok(None);
+                };
+                let mut link = serde_json::from_str::<ShortLink>(&raw)?;
+                link.hits = redis.get(self.hits_key(id)).await?.and_then(|h| h.parse().ok()).unwrap_or(0);
+                link
+            }
+            ShortLinkBackend::Memory(links) => {
+                let links = links.lock().unwrap_or_else(|e| e.into_inner());
+                let Some(link) = links.get(id) else {
+                    return Ok(None);
+                };
+                link.clone()
+            }
+        };
+        Ok(Some(link).filter(|link| !link.is_expired()))
+    }
+
+    /// 读取短链接并计入一次访问
+    pub async fn resolve(&self, id: &str) -> Result<Option<ShortLink>, CacheStoreError> {
+        let Some(mut link) = self.get(id).await? else {
+            return Ok(None);
+        };
+        match &self.backend {
+            ShortLinkBackend::Redis(redis) => {
+                let mut redis = redis.clone();
+                let hits_key = self.hits_key(id);
+                link.hits = redis.incr(&hits_key, 1).await? as u64;
+                // 首次计数时创建的键没有过期时间, 与短链接同时过期
+                if let Some(expires_at) = link.expires_at {
+                    redis.expire_at(&hits_key, expires_at as i64).await?;
+                }
+            }
+            ShortLinkBackend::Memory(links) => {
+                let mut links = links.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(stored) = links.get_mut(id) {
+                    stored.hits += 1;
+                    link.hits = stored.hits;
+                }
+            }
+        }
+        Ok(Some(link))
+    }
+
+    /// 保存短链接, 访问次数单独计数, 不会被覆盖, 计数的过期时间与短链接保持一致
+    pub async fn save(&self, link: &ShortLink) -> Result<(), CacheStoreError> {
+        match &self.backend {
+            ShortLinkBackend::Redis(redis) => {
+                let mut redis = redis.clone();
+                let raw = serde_json::to_string(link)?;
+                let hits_key = self.hits_key(&link.id);
+                match link.expires_at {
+                    Some(expires_at) => {
+                        let ttl = expires_at.saturating_sub(now_secs()).max(1);
+                        redis.set_ex(self.link_key(&link.id), raw, ttl).await?;
+                        redis.expire_at(&hits_key, expires_at as i64).await?;
+                    }
+                    None => {
+                        redis.set(self.link_key(&link.id), raw).await?;
+                        redis.persist(&hits_key).await?;
+                    }
+                }
+            }
+            ShortLinkBackend::Memory(links) => {
+                let mut links = links.lock().unwrap_or_else(|e| e.into_inner());
+                let hits = links.get(&link.id).map(|l| l.hits).unwrap_or(link.hits);
+                links.insert(link.id.clone(), ShortLink { hits, ..link.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: &str) -> Result<bool, CacheStoreError> {
+        match &self.backend {
+            ShortLinkBackend::Redis(redis) => {
+                let mut redis = redis.clone();
+                redis.del(self.hits_key(id)).await?;
+                Ok(redis.del(self.link_key(id)).await? > 0)
+            }
+            ShortLinkBackend::Memory(links) => {
+                let mut links = links.lock().unwrap_or_else(|e| e.into_inner());
+                Ok(links.remove(id).is_some())
+            }
+        }
+    }
+
+    fn link_key(&self, id: &str) -> String {
+        format!("{}:{id}", self.key)
+    }
+
+    fn hits_key(&self, id: &str) -> String {
+        format!("{}:{id}:hits", self.key)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
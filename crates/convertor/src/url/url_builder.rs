@@ -1,4 +1,4 @@
-use crate::common::encrypt::encrypt;
+use crate::common::encrypt::{encrypt, is_current_key};
 use crate::config::proxy_client::ProxyClient;
 use crate::core::profile::policy::Policy;
 use crate::core::profile::surge_header::SurgeHeader;
@@ -18,6 +18,9 @@ pub struct UrlBuilder {
     pub enc_sub_url: String,
     pub interval: u64,
     pub strict: bool,
+    /// 用户令牌, 会编码进生成的 profile 与 rule provider 链接
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 impl UrlBuilder {
@@ -49,10 +52,27 @@ impl UrlBuilder {
             enc_sub_url,
             interval,
             strict,
+            token: None,
         };
         Ok(builder)
     }
 
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// 去掉不影响生成的配置的用户令牌与加密后的密钥, 作为转换结果的缓存键
+    ///
+    /// 链接中没有 `secret` 参数时每次请求都会重新加密, 密文各不相同
+    pub fn without_credentials(&self) -> Self {
+        Self {
+            enc_secret: String::new(),
+            token: None,
+            ..self.clone()
+        }
+    }
+
     pub fn from_convertor_query(
         query: ConvertorQuery,
         secret: impl AsRef<str>,
@@ -66,20 +86,28 @@ impl UrlBuilder {
             strict,
             secret: secret_opt,
             enc_secret,
+            token,
             policy: _,
+            refresh: _,
         } = query;
-        let secret = secret_opt.unwrap_or(secret.as_ref().to_string());
+        // `secret` 为服务端配置的当前密钥, 链接中解出的可能是轮换前的旧密钥
+        let secret = secret.as_ref().to_string();
         let strict = strict.unwrap_or(true);
+        // 由旧密钥加密的参数重新用当前密钥加密, 新生成的链接总是使用当前密钥
+        let enc_secret = enc_secret
+            .filter(|es| secret_opt.as_deref() == Some(secret.as_str()) && is_current_key(secret.as_bytes(), es));
+        let enc_sub_url = Some(enc_sub_url).filter(|es| is_current_key(secret.as_bytes(), es));
         Self::new(
             secret,
             enc_secret,
             client,
             server,
             sub_url,
-            Some(enc_sub_url),
+            enc_sub_url,
             interval,
             strict,
         )
+        .map(|builder| builder.with_token(token))
     }
 
     pub fn build_raw_url(&self) -> ConvertorUrl {
@@ -115,6 +143,32 @@ impl UrlBuilder {
         Ok(url)
     }
 
+    /// 订阅请求日志的地址, 携带加密后的密钥用于鉴权
+    pub fn build_sub_logs_url(&self) -> Result<Url, UrlBuilderError> {
+        let query = self.as_sub_logs_query().encode_to_sub_logs_query()?;
+        let mut url = self.server.clone();
+        url.set_path("/api/sub-logs");
+        url.set_query(Some(&query));
+        Ok(url)
+    }
+
+    /// 订阅变化详情的地址, 与转换后配置使用相同的参数
+    pub fn build_diff_url(&self) -> Result<Url, UrlBuilderError> {
+        let query = self.as_profile_query().encode_to_profile_query()?;
+        let mut url = self.server.clone();
+        url.set_path(&format!("/api/subscription/{}/diff", self.client.as_str()));
+        url.set_query(Some(&query));
+        Ok(url)
+    }
+
+    /// 短链接地址, 由 convd 映射回保存的 UrlBuilder
+    pub fn build_short_link_url(&self, id: impl AsRef<str>) -> Url {
+        let mut url = self.server.clone();
+        url.set_path(&format!("/p/{}", id.as_ref()));
+        url.set_query(None);
+        url
+    }
+
     // 构造专属 Surge 的订阅头
     pub fn build_surge_header(&self, r#type: UrlType) -> Result<SurgeHeader, UrlBuilderError> {
         let url = match r#type {
@@ -138,6 +192,8 @@ impl UrlBuilder {
             policy: None,
             secret: None,
             enc_secret: None,
+            token: self.token.clone(),
+            refresh: false,
         }
     }
 
@@ -1,3 +1,4 @@
+use crate::provider::subscription_userinfo::SubscriptionUserinfo;
 use crate::url::convertor_url::ConvertorUrl;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -9,15 +10,39 @@ pub struct UrlResult {
     pub raw_profile_url: ConvertorUrl,
     pub profile_url: ConvertorUrl,
     pub rule_providers_url: Vec<ConvertorUrl>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userinfo: Option<SubscriptionUserinfo>,
+}
+
+/// UrlResult 中的一类链接
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlEntry {
+    Raw,
+    RawProfile,
+    Profile,
+    RuleProvider,
 }
 
 impl UrlResult {
+    /// 按类型取出链接, 规则集链接需要通过 index 指定第几个
+    pub fn entry(&self, entry: UrlEntry, index: usize) -> Option<&ConvertorUrl> {
+        match entry {
+            UrlEntry::Raw => Some(&self.raw_url),
+            UrlEntry::RawProfile => Some(&self.raw_profile_url),
+            UrlEntry::Profile => Some(&self.profile_url),
+            UrlEntry::RuleProvider => self.rule_providers_url.get(index),
+        }
+    }
+
     pub fn empty() -> Self {
         Self {
             raw_url: ConvertorUrl::empty(),
             raw_profile_url: ConvertorUrl::empty(),
             profile_url: ConvertorUrl::empty(),
             rule_providers_url: vec![],
+            userinfo: None,
         }
     }
 }
@@ -34,6 +59,10 @@ impl Display for UrlResult {
             writeln!(f, "{}", url.desc)?;
             writeln!(f, "{url}")?;
         }
+        if let Some(userinfo) = &self.userinfo {
+            writeln!(f, "订阅流量信息")?;
+            writeln!(f, "{}", userinfo.summary())?;
+        }
         Ok(())
     }
 }
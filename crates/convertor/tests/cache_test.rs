@@ -0,0 +1,158 @@
+use convertor::common::cache::cache_store::{CacheStore, FileStore};
+use convertor::common::cache::{Cache, CacheKey, Cached};
+use convertor::config::cache_config::SubscriptionCacheConfig;
+use convertor::config::subscription_config::Headers;
+use convertor::init_test;
+use convertor::provider::SubsProvider;
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use url::Url;
+
+type InitFuture = Pin<Box<dyn Future<Output = Result<String, String>> + Send>>;
+
+fn counting_init(counter: Arc<AtomicUsize>, fail: bool) -> impl Fn(Option<String>) -> InitFuture + Send + Sync {
+    move |_| {
+        let counter = counter.clone();
+        Box::pin(async move {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if fail { Err("upstream down".to_string()) } else { Ok(format!("v{n}")) }
+        })
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cache_fallback_to_last_good() -> color_eyre::Result<()> {
+    init_test!();
+    let cache: Cache<String, String> = Cache::new(None, 10, Duration::from_millis(100), Duration::from_secs(60));
+    let key = CacheKey::new("test", "sub_url".to_string(), None);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let value = cache.try_get_with(key.clone(), counting_init(counter.clone(), false)).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+
+    tokio::time::advance(Duration::from_millis(150)).await;
+    let value = cache.try_get_with(key.clone(), counting_init(counter.clone(), true)).await;
+    assert_eq!(value.ok(), Some(Cached::Stale("v0".to_string())));
+
+    let empty = CacheKey::new("test", "never_fetched".to_string(), None);
+    let value = cache.try_get_with(empty, counting_init(counter.clone(), true)).await;
+    assert!(value.is_err());
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cache_refresh_ahead() -> color_eyre::Result<()> {
+    init_test!();
+    let cache: Cache<String, String> = Cache::new(None, 10, Duration::from_secs(1), Duration::from_secs(60));
+    let key = CacheKey::new("test", "sub_url".to_string(), None);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    cache.try_get_with(key.clone(), counting_init(counter.clone(), false)).await.ok();
+
+    // 临近过期时仍然返回旧值, 同时在后台刷新
+    tokio::time::advance(Duration::from_millis(850)).await;
+    let value = cache.try_get_with(key.clone(), counting_init(counter.clone(), false)).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+
+    tokio::time::advance(Duration::from_millis(50)).await;
+    let value = cache.try_get_with(key.clone(), counting_init(counter.clone(), false)).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v1".to_string())));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_cache_refresh_ahead_recovers_from_panic() -> color_eyre::Result<()> {
+    init_test!();
+    let cache: Cache<String, String> = Cache::new(None, 10, Duration::from_secs(1), Duration::from_secs(60));
+    let key = CacheKey::new("test", "sub_url".to_string(), None);
+    let counter = Arc::new(AtomicUsize::new(0));
+    // 第一次后台刷新时 panic
+    let init = {
+        let counter = counter.clone();
+        move |_: Option<String>| -> InitFuture {
+            let counter = counter.clone();
+            Box::pin(async move {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                assert_ne!(n, 1, "refresh panicked");
+                Ok(format!("v{n}"))
+            })
+        }
+    };
+
+    cache.try_get_with(key.clone(), init.clone()).await.ok();
+    tokio::time::advance(Duration::from_millis(850)).await;
+    let value = cache.try_get_with(key.clone(), init.clone()).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+    tokio::time::advance(Duration::from_millis(10)).await;
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+    // panic 之后仍然可以再次发起后台刷新
+    let value = cache.try_get_with(key.clone(), init.clone()).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+    tokio::time::advance(Duration::from_millis(10)).await;
+    let value = cache.try_get_with(key.clone(), init).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v2".to_string())));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_file_store_survives_restart() -> color_eyre::Result<()> {
+    init_test!();
+    let dir = std::env::temp_dir().join(format!("convertor-cache-{}", std::process::id()));
+    let new_cache = || -> color_eyre::Result<Cache<String, String>> {
+        let store = CacheStore::File(FileStore::new(&dir)?);
+        Ok(Cache::new(Some(store), 10, Duration::from_secs(60), Duration::from_secs(60)))
+    };
+    let key = CacheKey::new("test", "sub_url".to_string(), None);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let value = new_cache()?.try_get_with(key.clone(), counting_init(counter.clone(), false)).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+
+    // 新的实例没有内存缓存, 应当直接从文件中读取而不请求上游
+    let value = new_cache()?.try_get_with(key.clone(), counting_init(counter.clone(), true)).await;
+    assert_eq!(value.ok(), Some(Cached::Fresh("v0".to_string())));
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_conditional_refresh() -> color_eyre::Result<()> {
+    init_test!();
+    let server = MockServer::start_async().await;
+    let not_modified = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/sub").header("if-none-match", "\"v1\"");
+            then.status(304).header("ETag", "\"v1\"");
+        })
+        .await;
+    let full = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/sub").header_missing("if-none-match");
+            then.status(200).header("ETag", "\"v1\"").body("proxies: []");
+        })
+        .await;
+
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default());
+    let sub_url = Url::parse(&server.url("/sub"))?;
+
+    let first = provider.get_raw_profile(sub_url.clone(), Headers::default(), None).await?;
+    assert_eq!(first.etag.as_deref(), Some("\"v1\""));
+
+    // 刷新时携带 ETag, 订阅商返回 304 后复用原有内容
+    let refreshed = provider.refresh_raw_profile(sub_url, Headers::default(), None).await?;
+    assert_eq!(refreshed.content, first.content);
+    assert_eq!(refreshed.content_hash, first.content_hash);
+    assert!(!refreshed.stale);
+    full.assert_calls_async(1).await;
+    not_modified.assert_calls_async(1).await;
+    Ok(())
+}
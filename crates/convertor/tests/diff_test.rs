@@ -0,0 +1,73 @@
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::diff::{ProfileDiff, ProfileSnapshot};
+use convertor::core::profile::Profile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::init_test;
+use convertor::provider::profile_history::ProfileHistory;
+use convertor::provider::subs_response::content_hash;
+use convertor::testkit::mock_profile;
+use url::Url;
+
+fn snapshot(content: String) -> color_eyre::Result<ProfileSnapshot> {
+    let hash = content_hash(&content);
+    let profile = SurgeProfile::parse(content)?;
+    Ok(ProfileSnapshot::new(&profile, hash))
+}
+
+fn updated_profile(content: &str) -> String {
+    content
+        .lines()
+        .filter(|line| !line.starts_with("🇦🇺 澳洲 01="))
+        .map(|line| match line {
+            l if l.starts_with("🇭🇰 香港 01=") => l.replace("8080", "8443"),
+            "BosLife = select, 订阅提供商信息" => "BosLife = select, 订阅提供商信息, 🇭🇰 香港 01".to_string(),
+            "DOMAIN-SUFFIX,boswiki.net,BosLife" => "DOMAIN-SUFFIX,boslife.org,BosLife".to_string(),
+            l => l.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_profile_diff() -> color_eyre::Result<()> {
+    init_test!();
+    let content = mock_profile(ProxyClient::Surge, "127.0.0.1:8080");
+    let previous = snapshot(content.clone())?;
+    let current = snapshot(updated_profile(&content))?;
+
+    assert!(ProfileDiff::between(&previous, &previous).is_empty());
+    let diff = ProfileDiff::between(&previous, &current);
+    assert_eq!(diff.nodes_removed, vec!["🇦🇺 澳洲 01"]);
+    assert_eq!(diff.nodes_changed.len(), 1);
+    assert_eq!(diff.nodes_changed[0].name, "🇭🇰 香港 01");
+    assert_eq!(diff.nodes_changed[0].after.port, 8443);
+    assert_eq!(diff.groups_changed.len(), 1);
+    assert_eq!(diff.groups_changed[0].added, vec!["🇭🇰 香港 01"]);
+    assert_eq!(diff.rules.len(), 1);
+    assert_eq!(diff.rules[0].policy, "BosLife");
+    assert_eq!(diff.rules[0].added, vec!["DOMAIN-SUFFIX,boslife.org"]);
+    assert_eq!(diff.rules[0].removed, vec!["DOMAIN-SUFFIX,boswiki.net"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_profile_history_record() -> color_eyre::Result<()> {
+    init_test!();
+    let content = mock_profile(ProxyClient::Surge, "127.0.0.1:8080");
+    let sub_url = Url::parse("https://example.com/subscription?token=bppleman")?;
+    let history = ProfileHistory::new(None, "convertor:");
+
+    assert!(history.diff(ProxyClient::Surge, &sub_url).await?.is_none());
+    assert!(history.record(&sub_url, snapshot(content.clone())?).await?.is_none());
+    let diff = history.diff(ProxyClient::Surge, &sub_url).await?.expect("已记录快照");
+    assert!(diff.previous_hash.is_none() && diff.is_empty());
+
+    // 内容不变时不替换快照
+    assert!(history.record(&sub_url, snapshot(content.clone())?).await?.is_none());
+    let diff = history.record(&sub_url, snapshot(updated_profile(&content))?).await?;
+    assert!(diff.is_some_and(|diff| !diff.is_empty()));
+    let diff = history.diff(ProxyClient::Surge, &sub_url).await?.expect("已记录快照");
+    assert_eq!(diff.previous_hash, Some(content_hash(&content)));
+    assert!(history.diff(ProxyClient::Clash, &sub_url).await?.is_none());
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use convertor::common::encrypt::{decrypt, encrypt};
+use convertor::common::encrypt::{decrypt, decrypt_with_any, encrypt, is_current_key, key_id};
+use convertor::error::EncryptError;
 use convertor::init_test;
 
 #[test]
@@ -10,7 +11,7 @@ fn test_encrypt_and_decrypt() -> color_eyre::Result<()> {
 
     // 加密
     let encrypted = encrypt(secret, message)?;
-    insta::assert_snapshot!(encrypted, @"drjgraDxPZBAXWrlU4a9KL3SGbigje0acGn9tTwYzGmZBvRxAJCqF8bbX4IvnkC7n6rWzLVMOk8ndUZ85yvQXKY");
+    insta::assert_snapshot!(encrypted, @"v1.PV4hVUST.drjgraDxPZBAXWrlU4a9KL3SGbigje0aPmSG9u-v-duSn6U-N7aZ6d9B8zu6dXnjpuwEgRVbPecd5uUlGRNb-_w");
 
     // 解密
     let decrypted = decrypt(secret, &encrypted)?;
@@ -39,3 +40,29 @@ fn test_decrypt() -> color_eyre::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_decrypt_with_previous_secret() -> color_eyre::Result<()> {
+    init_test!();
+
+    let old_secret = "bppleman";
+    let new_secret = "rotated";
+    assert_eq!(key_id(old_secret.as_bytes()), "KP-aFRia");
+
+    let encrypted = encrypt(old_secret.as_bytes(), "http://127.0.0.1/subscription")?;
+    assert!(encrypted.starts_with("v1.KP-aFRia."));
+    assert!(is_current_key(old_secret.as_bytes(), &encrypted));
+    assert!(!is_current_key(new_secret.as_bytes(), &encrypted));
+
+    // 轮换后旧密钥仍可解密, 移除旧密钥后无法解密
+    let dec = decrypt_with_any(&[new_secret, old_secret], &encrypted)?;
+    assert_eq!(dec, "http://127.0.0.1/subscription");
+    assert!(matches!(decrypt(new_secret.as_bytes(), &encrypted), Err(EncryptError::UnknownKeyId(_))));
+
+    // 无版本的旧格式依次尝试每个密钥
+    let legacy = "qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3UN2hjcQ3B_IH9FI51WQX94QusyP4URwR4naCdMYFGV6aljrLzyNRhsJg9Cj55JszewkvSRXW5zMgUJCkai79FKZ4";
+    let dec = decrypt_with_any(&[new_secret, old_secret], legacy)?;
+    assert_eq!(dec, "http://127.0.0.1:64287/subscription?token=bppleman");
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::lint::{LintKind, LintLevel, LintReport};
+use convertor::core::profile::Profile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::init_test;
+use convertor::testkit::mock_profile;
+use convertor::url::url_builder::UrlBuilder;
+use url::Url;
+
+const BROKEN_PROFILE: &str = r#"#!MANAGED-CONFIG interval=259200 strict=true
+
+[General]
+loglevel = notify
+
+[Proxy]
+HK=ss,bppleman.com,8080,password=bppleman,encrypt-method=aes-128-gcm
+HK=ss,bppleman.com,8081,password=bppleman,encrypt-method=aes-128-gcm
+
+[Proxy Group]
+Proxy = select, HK, Auto, Missing
+Auto = url-test, Proxy, url=http://www.gstatic.com/generate_204
+Empty = select
+
+[Rule]
+DOMAIN-SUFFIX,example.com,Proxy
+DOMAIN-SUFFIX,example.net,Streaming
+IP-CIDR,10.0.0.0/8,DIRECT
+"#;
+
+#[test]
+fn test_lint_converted_profile() -> color_eyre::Result<()> {
+    init_test!();
+    let server = Url::parse("http://127.0.0.1:8080")?;
+    let sub_url = Url::parse("https://localhost/subscription?token=bppleman")?;
+    let url_builder = UrlBuilder::new("bppleman_secret", None, ProxyClient::Surge, server, sub_url, None, 86400, true)?;
+    let mut profile = SurgeProfile::parse(mock_profile(ProxyClient::Surge, "localhost"))?;
+    profile.convert(&url_builder)?;
+    let report = LintReport::check(&profile);
+    assert!(report.issues.is_empty(), "{report}");
+    Ok(())
+}
+
+#[test]
+fn test_lint_broken_profile() -> color_eyre::Result<()> {
+    init_test!();
+    let report = LintReport::check_content(ProxyClient::Surge, BROKEN_PROFILE);
+    assert!(report.has_errors());
+    let kinds = report.issues.iter().map(|issue| issue.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            LintKind::DuplicateProxy,
+            LintKind::MissingMember,
+            LintKind::EmptyGroup,
+            LintKind::MissingPolicy,
+            LintKind::GroupCycle,
+            LintKind::MissingFinal,
+        ]
+    );
+    assert!(report.issues.iter().all(|issue| issue.level == LintLevel::Error));
+    assert!(report.issues[4].message.contains("Proxy -> Auto -> Proxy"));
+
+    let report = LintReport::check_content(ProxyClient::Clash, "proxies: [");
+    assert_eq!(report.issues[0].kind, LintKind::Parse);
+    Ok(())
+}
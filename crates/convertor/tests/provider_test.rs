@@ -0,0 +1,189 @@
+use convertor::config::cache_config::SubscriptionCacheConfig;
+use convertor::config::proxy_client::ProxyClient;
+use convertor::config::retry_config::RetryConfig;
+use convertor::config::subscription_config::{Headers, SubscriptionConfig};
+use convertor::config::upstream_proxy_config::UpstreamProxyConfig;
+use convertor::error::ProviderError;
+use convertor::init_test;
+use convertor::provider::SubsProvider;
+use convertor::url::url_builder::UrlBuilder;
+use httpmock::Method::GET;
+use httpmock::MockServer;
+use url::Url;
+
+/// 与 convd / confly 一致, 经由 UrlBuilder 构造带有 `flag` 的原始订阅地址
+fn raw_url(sub_url: &Url) -> color_eyre::Result<Url> {
+    let url_builder = UrlBuilder::new(
+        "secret",
+        None,
+        ProxyClient::Surge,
+        Url::parse("http://127.0.0.1:8080/")?,
+        sub_url.clone(),
+        None,
+        86400,
+        true,
+    )?;
+    Ok(url_builder.build_raw_url().into())
+}
+
+fn retry() -> RetryConfig {
+    RetryConfig {
+        attempts: 2,
+        initial_backoff: 10,
+        max_backoff: 50,
+        ..RetryConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_provider_retry_then_mirror() -> color_eyre::Result<()> {
+    init_test!();
+    let primary = MockServer::start_async().await;
+    let mirror = MockServer::start_async().await;
+    let primary_mock = primary
+        .mock_async(|when, then| {
+            when.method(GET).path("/sub");
+            then.status(503);
+        })
+        .await;
+    let mirror_mock = mirror
+        .mock_async(|when, then| {
+            when.method(GET).path("/sub").query_param("flag", "surge");
+            then.status(200).body("from mirror");
+        })
+        .await;
+
+    let mut config = SubscriptionConfig::template();
+    config.sub_url = Url::parse(&primary.url("/sub"))?;
+    config.mirrors = vec![Url::parse(&mirror.url("/sub"))?];
+    config.retry = retry();
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default()).with_subscription(&config)?;
+
+    let response = provider.fetch(raw_url(&config.sub_url)?, Headers::default()).await?;
+    assert_eq!(response.content, "from mirror");
+    primary_mock.assert_calls_async(2).await;
+    mirror_mock.assert_calls_async(1).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_attempts_exhausted() -> color_eyre::Result<()> {
+    init_test!();
+    let server = MockServer::start_async().await;
+    let retryable = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/down");
+            then.status(502);
+        })
+        .await;
+    let not_found = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/missing");
+            then.status(404);
+        })
+        .await;
+
+    let mut config = SubscriptionConfig::template();
+    config.sub_url = Url::parse(&server.url("/down"))?;
+    config.mirrors = vec![Url::parse(&server.url("/missing"))?];
+    config.retry = retry();
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default()).with_subscription(&config)?;
+
+    let error = provider.fetch(config.sub_url.clone(), Headers::default()).await.unwrap_err();
+    let ProviderError::AttemptsExhausted { attempts, .. } = error else {
+        panic!("应当返回 AttemptsExhausted");
+    };
+    // 5xx 会重试, 4xx 直接切换到下一个镜像
+    let urls = attempts.iter().map(|a| a.url.path()).collect::<Vec<_>>();
+    assert_eq!(urls, vec!["/down", "/down", "/missing"]);
+    retryable.assert_calls_async(2).await;
+    not_found.assert_calls_async(1).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_mirror_flag_replaced() -> color_eyre::Result<()> {
+    init_test!();
+    let server = MockServer::start_async().await;
+    server
+        .mock_async(|when, then| {
+            when.method(GET).path("/sub");
+            then.status(404);
+        })
+        .await;
+    let mirror_mock = server
+        .mock_async(|when, then| {
+            when.method(GET)
+                .path("/mirror")
+                .query_param("flag", "surge")
+                .query_param_count("^flag$", ".*", 1)
+                .query_param("token", "abc");
+            then.status(200).body("from mirror");
+        })
+        .await;
+
+    let mut config = SubscriptionConfig::template();
+    config.sub_url = Url::parse(&server.url("/sub"))?;
+    config.mirrors = vec![Url::parse(&server.url("/mirror?flag=clash&token=abc"))?];
+    config.retry = retry();
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default()).with_subscription(&config)?;
+
+    let response = provider.fetch(raw_url(&config.sub_url)?, Headers::default()).await?;
+    assert_eq!(response.content, "from mirror");
+    mirror_mock.assert_calls_async(1).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_attempts_once_when_deadline_exhausted() -> color_eyre::Result<()> {
+    init_test!();
+    let server = MockServer::start_async().await;
+    let mock = server
+        .mock_async(|when, then| {
+            when.method(GET).path("/down");
+            then.status(502);
+        })
+        .await;
+
+    let mut config = SubscriptionConfig::template();
+    config.sub_url = Url::parse(&server.url("/down"))?;
+    config.retry = RetryConfig { deadline: 0, ..retry() };
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default()).with_subscription(&config)?;
+
+    let error = provider.fetch(config.sub_url.clone(), Headers::default()).await.unwrap_err();
+    assert!(matches!(error, ProviderError::ApiFailed(_)), "{error}");
+    mock.assert_calls_async(1).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_through_upstream_proxy() -> color_eyre::Result<()> {
+    init_test!();
+    // httpmock 作为本地 http 代理, 订阅域名本身无法解析, 只能经由代理访问
+    let proxy = MockServer::start_async().await;
+    let proxied = proxy
+        .mock_async(|when, then| {
+            when.method(GET)
+                .host("blocked.invalid")
+                .path("/sub")
+                .query_param("flag", "surge")
+                .header("proxy-authorization", "Basic dXNlcjpwYXNz");
+            then.status(200).body("via proxy");
+        })
+        .await;
+
+    let mut config = SubscriptionConfig::template();
+    config.sub_url = Url::parse("http://blocked.invalid/sub")?;
+    config.retry = retry();
+    config.proxy = Some(UpstreamProxyConfig {
+        url: Url::parse(&proxy.base_url())?,
+        username: Some("user".to_string()),
+        password: Some("pass".to_string()),
+    });
+    let provider = SubsProvider::new(None, Some("test"), &SubscriptionCacheConfig::default()).with_subscription(&config)?;
+
+    let response = provider.fetch(raw_url(&config.sub_url)?, Headers::default()).await?;
+    assert_eq!(response.content, "via proxy");
+    proxied.assert_calls_async(1).await;
+    Ok(())
+}
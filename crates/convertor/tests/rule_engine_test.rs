@@ -0,0 +1,84 @@
+use convertor::config::proxy_client::ProxyClient;
+use convertor::core::geoip::GeoIp;
+use convertor::core::profile::Profile;
+use convertor::core::profile::surge_profile::SurgeProfile;
+use convertor::core::rule_engine::{MatchQuery, RuleEngine};
+use convertor::init_test;
+use convertor::testkit::mock_profile;
+use convertor::url::url_builder::UrlBuilder;
+use url::Url;
+
+/// GeoLite2-Country 格式的 IPv6 测试库, IPv4 位于 ::/96 之下
+///
+/// 1.0.0.0/8 -> CN, 81.2.69.0/24 -> GB, 89.160.20.112/28 仅有 registered_country DE,
+/// 2001:218::/32 -> JP, 240e::/20 -> CN, 2600::/12 -> US
+fn test_geoip_db() -> color_eyre::Result<GeoIp> {
+    Ok(GeoIp::from_bytes(include_bytes!("fixtures/GeoLite2-Country-Test.mmdb").to_vec())?)
+}
+
+fn converted_profile() -> color_eyre::Result<SurgeProfile> {
+    let server = Url::parse("http://127.0.0.1:8080")?;
+    let sub_url = Url::parse("https://localhost/subscription?token=bppleman")?;
+    let url_builder = UrlBuilder::new("bppleman_secret", None, ProxyClient::Surge, server, sub_url, None, 86400, true)?;
+    let mut profile = SurgeProfile::parse(mock_profile(ProxyClient::Surge, "localhost"))?;
+    profile.convert(&url_builder)?;
+    Ok(profile)
+}
+
+#[test]
+fn test_geoip() -> color_eyre::Result<()> {
+    init_test!();
+    let geoip = test_geoip_db()?;
+    let country = |ip: &str| geoip.country(ip.parse().unwrap());
+    assert_eq!(country("1.2.3.4"), Some("CN".to_string()));
+    assert_eq!(country("81.2.69.160"), Some("GB".to_string()));
+    assert_eq!(country("89.160.20.113"), Some("DE".to_string()));
+    assert_eq!(country("2001:218::1"), Some("JP".to_string()));
+    assert_eq!(country("240e::1"), Some("CN".to_string()));
+    assert_eq!(country("2600::1"), Some("US".to_string()));
+    assert_eq!(country("::ffff:1.2.3.4"), Some("CN".to_string()));
+    assert_eq!(country("8.8.8.8"), None);
+    assert_eq!(country("2001:db8::1"), None);
+    assert!(GeoIp::from_bytes(b"not a mmdb".to_vec()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_rule_engine() -> color_eyre::Result<()> {
+    init_test!();
+    let profile = converted_profile()?;
+    let geoip = test_geoip_db()?;
+    let engine = RuleEngine::new(&profile).with_geoip(Some(&geoip));
+
+    let query = |domain: Option<&str>, ip: Option<&str>| MatchQuery {
+        domain: domain.map(str::to_string),
+        ip: ip.map(|ip| ip.parse().unwrap()),
+        ..Default::default()
+    };
+
+    let result = engine.evaluate(&query(Some("WWW.BosLife.io."), None));
+    assert_eq!(result.rule.as_deref(), Some("DOMAIN-SUFFIX,boslife.io"));
+    assert!(result.rule_set.is_some_and(|url| url.contains("/rule-provider/surge")));
+    assert_eq!(result.policy.as_deref(), Some("BosLife"));
+    assert_eq!(result.chain.first().map(String::as_str), Some("BosLife"));
+    assert!(result.chain.len() >= 3, "策略链应当解析到节点: {:?}", result.chain);
+
+    let result = engine.evaluate(&query(None, Some("10.1.2.3")));
+    assert_eq!(result.rule.as_deref(), Some("IP-CIDR,10.0.0.0/8"));
+    assert_eq!(result.chain, vec!["DIRECT"]);
+
+    let result = engine.evaluate(&query(None, Some("1.2.3.4")));
+    assert_eq!(result.rule.as_deref(), Some("GEOIP,CN"));
+
+    let result = engine.evaluate(&MatchQuery {
+        user_agent: Some("Argo/1.0".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(result.rule.as_deref(), Some("USER-AGENT,Argo*"));
+
+    // 只有域名时不会解析 IP, 最终落到 FINAL
+    let result = engine.evaluate(&query(Some("unknown.example"), None));
+    assert_eq!(result.rule.as_deref(), Some("FINAL"));
+    assert_eq!(result.policy.as_deref(), Some("DIRECT"));
+    Ok(())
+}
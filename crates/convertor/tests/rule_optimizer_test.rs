@@ -0,0 +1,70 @@
+use convertor::core::profile::policy::Policy;
+use convertor::core::profile::rule::{Rule, RuleType};
+use convertor::core::rule_optimizer;
+use convertor::init_test;
+
+fn rule(line: &str) -> Rule {
+    let mut parts = line.split(',');
+    let rule_type = parts.next().unwrap().parse::<RuleType>().unwrap();
+    let value = parts.next().unwrap().to_string();
+    let name = parts.next().unwrap();
+    Rule {
+        rule_type,
+        value: Some(value),
+        policy: Policy::new(name, parts.next(), false),
+        comment: None,
+    }
+}
+
+fn lines(rules: &[Rule]) -> Vec<String> {
+    rules
+        .iter()
+        .map(|r| format!("{},{},{}", r.rule_type, r.value.as_deref().unwrap_or_default(), r.policy.name))
+        .collect()
+}
+
+#[test]
+fn test_optimize_rules() -> color_eyre::Result<()> {
+    init_test!();
+    let rules = [
+        "DOMAIN-SUFFIX,example.com,Proxy",
+        "DOMAIN,www.example.com,Proxy",
+        "DOMAIN-SUFFIX,Example.com,Proxy",
+        "DOMAIN,api.example.com,DIRECT",
+        "DOMAIN-KEYWORD,google,Proxy",
+        "DOMAIN-SUFFIX,google.com.hk,Proxy",
+        "IP-CIDR,10.0.0.0/25,DIRECT,no-resolve",
+        "IP-CIDR,10.0.0.128/25,DIRECT,no-resolve",
+        "IP-CIDR,10.0.0.64/26,DIRECT,no-resolve",
+        "IP-CIDR,192.168.0.0/25,DIRECT",
+        "IP-CIDR,192.168.0.128/25,Proxy",
+        "DOMAIN,other.net,Proxy",
+    ]
+    .map(rule)
+    .to_vec();
+
+    let (optimized, report) = rule_optimizer::optimize(rules);
+    assert_eq!(
+        lines(&optimized),
+        vec![
+            "DOMAIN-SUFFIX,example.com,Proxy",
+            "DOMAIN,api.example.com,DIRECT",
+            "DOMAIN-KEYWORD,google,Proxy",
+            "IP-CIDR,10.0.0.0/24,DIRECT",
+            "IP-CIDR,192.168.0.0/25,DIRECT",
+            "IP-CIDR,192.168.0.128/25,Proxy",
+            "DOMAIN,other.net,Proxy",
+        ]
+    );
+    assert_eq!(optimized[3].policy.option.as_deref(), Some("no-resolve"));
+    assert_eq!((report.before, report.after), (12, 7));
+    assert_eq!((report.duplicates, report.covered, report.merged), (1, 3, 1));
+    assert_eq!(report.shadowed.len(), 1);
+    assert_eq!(report.shadowed[0].rule, "DOMAIN,api.example.com,DIRECT");
+    assert_eq!(report.shadowed[0].by, "DOMAIN-SUFFIX,example.com,Proxy");
+
+    let diagnostics = report.diagnostics();
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics[0].contains("12 -> 7"));
+    Ok(())
+}
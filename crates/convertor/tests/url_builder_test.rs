@@ -1,8 +1,13 @@
+use convertor::common::encrypt::{decrypt, is_current_key, key_id};
 use convertor::config::proxy_client::ProxyClient;
+use convertor::core::profile::surge_header::SurgeHeader;
 use convertor::core::renderer::Renderer;
 use convertor::core::renderer::clash_renderer::ClashRenderer;
 use convertor::init_test;
 use convertor::testkit::policies;
+use convertor::url::convertor_url::UrlType;
+use convertor::url::query::ConvertorQuery;
+use convertor::url::short_link::ShortLink;
 use convertor::url::url_builder::UrlBuilder;
 use url::Url;
 
@@ -22,10 +27,10 @@ fn test_url_builder_surge() -> color_eyre::Result<()> {
     insta::assert_snapshot!(raw_url.to_string(), @"https://localhost/subscription?token=bppleman&flag=surge");
 
     let raw_profile_url = url_builder.build_raw_profile_url()?;
-    insta::assert_snapshot!(raw_profile_url.to_string(), @"http://127.0.0.1:8080/raw-profile/surge?interval=86400&strict=true&sub_url=qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3c6g0ZR2cc7lpxUFnkUEWW0fTRfMAmY3yU3f-ESJYD93o5YDKtEzSe1ATkzfrq9RxPdh7fMif0IOZXScDcg");
+    insta::assert_snapshot!(raw_profile_url.to_string(), @"http://127.0.0.1:8080/raw-profile/surge?interval=86400&strict=true&sub_url=v1.R-Clts9l.qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3iHxypHh2bxJ6a3S9ddjHxcNTaGWks9BCOCbc6gy54IqRCjnZtbKvKCCMqAtI-MfGRGlA8j-_8SBLtKHJxQ");
 
     let profile_url = url_builder.build_profile_url()?;
-    insta::assert_snapshot!(profile_url.to_string(), @"http://127.0.0.1:8080/profile/surge?interval=86400&strict=true&sub_url=qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3c6g0ZR2cc7lpxUFnkUEWW0fTRfMAmY3yU3f-ESJYD93o5YDKtEzSe1ATkzfrq9RxPdh7fMif0IOZXScDcg");
+    insta::assert_snapshot!(profile_url.to_string(), @"http://127.0.0.1:8080/profile/surge?interval=86400&strict=true&sub_url=v1.R-Clts9l.qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3iHxypHh2bxJ6a3S9ddjHxcNTaGWks9BCOCbc6gy54IqRCjnZtbKvKCCMqAtI-MfGRGlA8j-_8SBLtKHJxQ");
 
     let policies = policies();
     for policy in policies {
@@ -39,6 +44,28 @@ fn test_url_builder_surge() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_surge_header_keeps_provider_options() -> color_eyre::Result<()> {
+    init_test!();
+    let provider = "#!MANAGED-CONFIG https://localhost/sub?token=a=b interval=3600 strict=false update-on-launch=true";
+    let base = SurgeHeader::parse(provider).expect("应当能解析配置头");
+    assert_eq!(base.url.as_ref().map(ToString::to_string).as_deref(), Some("https://localhost/sub?token=a=b"));
+    assert_eq!(base.interval, Some(3600));
+    assert_eq!(base.strict, Some(false));
+    assert_eq!(base.options, vec!["update-on-launch=true".to_string()]);
+    assert_eq!(base.to_string(), provider);
+
+    // 链接中的 interval 与 strict 覆盖订阅商的配置, 其余选项保留
+    let header = url_builder(ProxyClient::Surge)?.build_surge_header(UrlType::Profile)?.inherit(&base);
+    insta::assert_snapshot!(header.to_string(), @"#!MANAGED-CONFIG http://127.0.0.1:8080/profile/surge?interval=86400&strict=true&sub_url=v1.R-Clts9l.qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3iHxypHh2bxJ6a3S9ddjHxcNTaGWks9BCOCbc6gy54IqRCjnZtbKvKCCMqAtI-MfGRGlA8j-_8SBLtKHJxQ interval=86400 strict=true update-on-launch=true");
+
+    let without_url = SurgeHeader::parse("#!MANAGED-CONFIG interval=259200 strict=true").expect("应当能解析配置头");
+    assert!(without_url.url.is_none());
+    assert!(SurgeHeader::parse("[General]").is_none());
+    assert!(SurgeHeader::parse("#!MANAGED-CONFIGX interval=1").is_none());
+    Ok(())
+}
+
 #[test]
 fn test_url_builder_clash_boslife() -> color_eyre::Result<()> {
     init_test!();
@@ -47,10 +74,10 @@ fn test_url_builder_clash_boslife() -> color_eyre::Result<()> {
     insta::assert_snapshot!(raw_url.to_string(), @"https://localhost/subscription?token=bppleman&flag=clash");
 
     let raw_profile_url = url_builder.build_raw_profile_url()?;
-    insta::assert_snapshot!(raw_profile_url.to_string(), @"http://127.0.0.1:8080/raw-profile/clash?interval=86400&strict=true&sub_url=qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3c6g0ZR2cc7lpxUFnkUEWW0fTRfMAmY3yU3f-ESJYD93o5YDKtEzSe1ATkzfrq9RxPdh7fMif0IOZXScDcg");
+    insta::assert_snapshot!(raw_profile_url.to_string(), @"http://127.0.0.1:8080/raw-profile/clash?interval=86400&strict=true&sub_url=v1.R-Clts9l.qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3iHxypHh2bxJ6a3S9ddjHxcNTaGWks9BCOCbc6gy54IqRCjnZtbKvKCCMqAtI-MfGRGlA8j-_8SBLtKHJxQ");
 
     let profile_url = url_builder.build_profile_url()?;
-    insta::assert_snapshot!(profile_url.to_string(), @"http://127.0.0.1:8080/profile/clash?interval=86400&strict=true&sub_url=qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3c6g0ZR2cc7lpxUFnkUEWW0fTRfMAmY3yU3f-ESJYD93o5YDKtEzSe1ATkzfrq9RxPdh7fMif0IOZXScDcg");
+    insta::assert_snapshot!(profile_url.to_string(), @"http://127.0.0.1:8080/profile/clash?interval=86400&strict=true&sub_url=v1.R-Clts9l.qDbvzIt3DcfaQVl8UVdIjXck4D-42Eo3iHxypHh2bxJ6a3S9ddjHxcNTaGWks9BCOCbc6gy54IqRCjnZtbKvKCCMqAtI-MfGRGlA8j-_8SBLtKHJxQ");
 
     let policies = policies();
     for policy in policies {
@@ -63,3 +90,36 @@ fn test_url_builder_clash_boslife() -> color_eyre::Result<()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_url_builder_reencrypts_after_secret_rotation() -> color_eyre::Result<()> {
+    init_test!();
+    let old_builder = url_builder(ProxyClient::Surge)?;
+    let new_secret = "rotated_secret";
+    let previous_secrets = vec![old_builder.secret.clone()];
+
+    // 旧链接的 sub_url 与 secret 均由旧密钥加密, 轮换后重新生成的链接改用当前密钥
+    let query_string = old_builder.build_sub_logs_url()?.query().unwrap_or_default().to_string();
+    let query_string = format!("{query_string}&{}", old_builder.as_profile_query().encode_to_profile_query()?);
+    let query = ConvertorQuery::parse_from_query_string(query_string, new_secret, &previous_secrets, old_builder.server.clone())?;
+    assert_eq!(query.secret.as_deref(), Some("bppleman_secret"));
+    assert!(is_current_key(old_builder.secret.as_bytes(), &query.enc_sub_url));
+
+    let builder = UrlBuilder::from_convertor_query(query, new_secret, ProxyClient::Surge)?;
+    assert_eq!(builder.secret, new_secret);
+    assert_eq!(builder.sub_url, old_builder.sub_url);
+    assert!(builder.enc_sub_url.starts_with(&format!("v1.{}.", key_id(new_secret.as_bytes()))));
+    assert!(is_current_key(new_secret.as_bytes(), &builder.enc_secret));
+    assert_eq!(decrypt(new_secret.as_bytes(), &builder.enc_secret)?, new_secret);
+    Ok(())
+}
+
+#[test]
+fn test_short_link_id_survives_secret_rotation() -> color_eyre::Result<()> {
+    init_test!();
+    let old_builder = url_builder(ProxyClient::Surge)?;
+    let mut new_builder = old_builder.clone();
+    new_builder.secret = "rotated_secret".to_string();
+    assert_eq!(ShortLink::id_for(&old_builder), ShortLink::id_for(&new_builder));
+    Ok(())
+}
@@ -19,13 +19,14 @@ async fn main() -> color_eyre::Result<()> {
     // 搜索可用配置文件
     let config: Config = Config::search(&base_dir, Option::<&str>::None)?;
     // 创建订阅供应商实例
-    let provider = SubsProvider::new(None, config.redis.as_ref().map(|r| r.prefix.as_str()));
+    let provider = SubsProvider::new(None, config.redis.as_ref().map(|r| r.prefix.as_str()), &config.cache.subscription)
+        .with_config(&config)?;
 
     // 获取原始订阅配置文件内容: 来源于 BosLife 机场;适用于 Surge
     let sub_url = config.subscription.sub_url.clone();
-    let raw_sub_content = provider.get_raw_profile(sub_url, [("User-Agent", "Surge Mac/8310")].into()).await?;
+    let raw_sub_content = provider.get_raw_profile(sub_url, [("User-Agent", "Surge Mac/8310")].into(), None).await?;
     // 解析原始配置文件内容为 SurgeProfile 对象
-    let mut profile = SurgeProfile::parse(raw_sub_content)?;
+    let mut profile = SurgeProfile::parse(raw_sub_content.content)?;
     // 创建 UrlBuilder 对象, 该 UrlBuilder 可用于创建适用于 Surge 的且使用 BosLife 订阅的 URL
     let url_builder = config.create_url_builder(ProxyClient::Surge)?;
     // 转换 SurgeProfile 对象